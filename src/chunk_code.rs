@@ -0,0 +1,317 @@
+use crate::chunk_sections::{with_heading, HEADING_PATH_SEPARATOR};
+use crate::chunk_text::word_indices;
+
+/// Width of a tab when comparing indentation
+const TAB_WIDTH: usize = 4;
+
+/// Splits source code into chunks of whole lines, aligned to its blocks where possible.
+///
+/// A block starts at a line following a blank line that is indented no more than the
+/// other lines of the enclosing block, so the comments, attributes and decorators directly
+/// above a function stay with it. Consecutive blocks are packed into chunks of at most
+/// `max_size`. Larger blocks are split the same way at the blocks nested within them, such
+/// as the methods of a class, and failing that into windows of whole lines. Lines that are too
+/// long on their own, e.g. in minified files, are split at word boundaries.
+///
+/// Chunks are measured along with their heading path, as embedded by `with_heading`, so they
+/// fit in `max_size` with it.
+///
+/// # Returns
+///
+/// Chunk indices without leading blank lines or trailing whitespace, each with the path of
+/// first lines of the blocks it was split out of, e.g. "impl Client > fn send(&self) {".
+/// Chunks made of whole blocks have no path.
+pub fn code_chunk_indices(
+    text: &str,
+    max_size: usize,
+    measure: impl Fn(&str) -> usize,
+) -> Vec<(usize, usize, Option<String>)> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+
+    let mut offset = 0;
+    let lines = text
+        .split_inclusive('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len();
+            (start, offset)
+        })
+        .collect();
+
+    let mut chunker = CodeChunker {
+        text,
+        lines,
+        max_size,
+        measure,
+        chunks: Vec::new(),
+    };
+    chunker.split(0, chunker.lines.len(), None, false);
+    chunker.chunks
+}
+
+struct CodeChunker<'a, M> {
+    text: &'a str,
+    /// Byte range of each line, including its line break
+    lines: Vec<(usize, usize)>,
+    max_size: usize,
+    measure: M,
+    chunks: Vec<(usize, usize, Option<String>)>,
+}
+
+impl<M: Fn(&str) -> usize> CodeChunker<'_, M> {
+    /// Chunks the lines `from..to`, packing the blocks they consist of. The first line of
+    /// a nested block is its opening line, which is left out when finding the blocks in it.
+    fn split(&mut self, from: usize, to: usize, heading: Option<String>, nested: bool) {
+        let indent_from = if nested { from + 1 } else { from };
+        let starts = self.block_starts(from, to, indent_from);
+        if starts.len() == 1 {
+            if nested {
+                self.split_lines(from, to, heading);
+            } else {
+                self.split_block(from, to, heading);
+            }
+            return;
+        }
+
+        let ends: Vec<usize> = starts.iter().skip(1).copied().chain([to]).collect();
+        let mut packed: Option<(usize, usize)> = None;
+        for (start, end) in starts.into_iter().zip(ends) {
+            if let Some((packed_start, packed_end)) = packed {
+                if self.fits(packed_start, end, heading.as_deref()) {
+                    packed = Some((packed_start, end));
+                    continue;
+                }
+                self.push(packed_start, packed_end, heading.clone());
+                packed = None;
+            }
+
+            if self.fits(start, end, heading.as_deref()) {
+                packed = Some((start, end));
+            } else if nested && start == from {
+                // The block starting at the opening line is already on the heading path
+                self.split_lines(start, end, heading.clone());
+            } else {
+                self.split_block(start, end, heading.clone());
+            }
+        }
+        if let Some((start, end)) = packed {
+            self.push(start, end, heading);
+        }
+    }
+
+    /// Chunks a block that is too large, adding its first line to the heading path
+    fn split_block(&mut self, from: usize, to: usize, heading: Option<String>) {
+        let from = (from..to).find(|&i| !self.is_blank(i)).unwrap_or(from);
+        let title = self.line(from).trim();
+        let heading = match heading {
+            Some(heading) => format!("{}{}{}", heading, HEADING_PATH_SEPARATOR, title),
+            None => title.to_string(),
+        };
+        self.split(from, to, Some(heading), true);
+    }
+
+    /// Chunks lines that have no blocks into windows of as many lines as fit
+    fn split_lines(&mut self, from: usize, to: usize, heading: Option<String>) {
+        let mut start = from;
+        for end in from + 1..=to {
+            if self.fits(start, end, heading.as_deref()) {
+                continue;
+            }
+            if end - 1 > start {
+                self.push(start, end - 1, heading.clone());
+                start = end - 1;
+            }
+            // A single line larger than a chunk is split into runs of words
+            if !self.fits(start, end, heading.as_deref()) {
+                self.split_words(start, heading.clone());
+                start = end;
+            }
+        }
+        if start < to {
+            self.push(start, to, heading);
+        }
+    }
+
+    /// Returns the lines of `from..to` at which blocks start, the first being `from`.
+    /// Blocks are indented as little as the lines of `indent_from..to`.
+    fn block_starts(&self, from: usize, to: usize, indent_from: usize) -> Vec<usize> {
+        let mut starts = vec![from];
+        let Some(indent) = (indent_from..to)
+            .filter(|&i| !self.is_closing(i))
+            .filter_map(|i| self.indent(i))
+            .min()
+        else {
+            return starts;
+        };
+
+        starts.extend((from + 1..to).filter(|&i| {
+            self.indent(i) == Some(indent) && self.is_blank(i - 1) && !self.is_closing(i)
+        }));
+        starts
+    }
+
+    /// Chunks a single line into runs of words that fit along with the heading path
+    fn split_words(&mut self, i: usize, heading: Option<String>) {
+        let Some(range) = self.chunk_range(i, i + 1) else {
+            return;
+        };
+        let reserved = heading
+            .as_ref()
+            .map_or(0, |heading| (self.measure)(&with_heading(heading, "")));
+        let max_size = self.max_size.saturating_sub(reserved).max(1);
+        for (start, end) in word_indices(self.text, range, max_size, &self.measure) {
+            self.chunks.push((start, end, heading.clone()));
+        }
+    }
+
+    /// Whether the lines `from..to` fit in a chunk along with the heading path
+    fn fits(&self, from: usize, to: usize, heading: Option<&str>) -> bool {
+        self.chunk_range(from, to).is_none_or(|(start, end)| {
+            let chunk = &self.text[start..end];
+            let size = match heading {
+                Some(heading) => (self.measure)(&with_heading(heading, chunk)),
+                None => (self.measure)(chunk),
+            };
+            size <= self.max_size
+        })
+    }
+
+    fn push(&mut self, from: usize, to: usize, heading: Option<String>) {
+        if let Some((start, end)) = self.chunk_range(from, to) {
+            self.chunks.push((start, end, heading));
+        }
+    }
+
+    /// Byte range of the lines `from..to` without blank lines at the start and whitespace
+    /// at the end, or None if they are blank
+    fn chunk_range(&self, from: usize, to: usize) -> Option<(usize, usize)> {
+        let first = (from..to).find(|&i| !self.is_blank(i))?;
+        let start = self.lines[first].0;
+        Some((start, start + self.range_text(first, to).trim_end().len()))
+    }
+
+    fn line(&self, i: usize) -> &str {
+        let (start, end) = self.lines[i];
+        &self.text[start..end]
+    }
+
+    fn range_text(&self, from: usize, to: usize) -> &str {
+        &self.text[self.lines[from].0..self.lines[to - 1].1]
+    }
+
+    fn is_blank(&self, i: usize) -> bool {
+        self.line(i).trim().is_empty()
+    }
+
+    /// Indentation of a line, or None if it is blank
+    fn indent(&self, i: usize) -> Option<usize> {
+        let line = self.line(i);
+        if line.trim().is_empty() {
+            return None;
+        }
+        Some(
+            line.chars()
+                .take_while(|c| c.is_whitespace())
+                .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+                .sum(),
+        )
+    }
+
+    /// Whether a line closes a block, e.g. `}` or `end`, rather than starting one
+    fn is_closing(&self, i: usize) -> bool {
+        let line = self.line(i).trim();
+        line.starts_with(['}', ')', ']']) || matches!(line, "end" | "end;" | "fi" | "done" | "esac")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk_code::code_chunk_indices;
+    use crate::chunk_sections::with_heading;
+
+    fn chunks(text: &str, max_size: usize) -> Vec<(&str, Option<String>)> {
+        code_chunk_indices(text, max_size, |text: &str| text.len())
+            .into_iter()
+            .map(|(start, end, heading)| (&text[start..end], heading))
+            .collect()
+    }
+
+    #[test]
+    fn test_blocks_are_packed() {
+        let text =
+            "use std::io;\n\n/// Adds\nfn add() {\n    1 + 1\n}\n\nfn sub() {\n    1 - 1\n}\n";
+        assert_eq!(
+            chunks(text, 45),
+            vec![
+                ("use std::io;\n\n/// Adds\nfn add() {\n    1 + 1\n}", None),
+                ("fn sub() {\n    1 - 1\n}", None),
+            ]
+        );
+        assert_eq!(chunks(text, 1000), vec![(text.trim_end(), None)]);
+    }
+
+    #[test]
+    fn test_large_block_split_at_nested_blocks() {
+        let text = "class Client:\n    \"\"\"A client\"\"\"\n\n    def send(self):\n        return self.retry()\n\n    def retry(self):\n        pass\n";
+        assert_eq!(
+            chunks(text, 65),
+            vec![
+                (
+                    "class Client:\n    \"\"\"A client\"\"\"",
+                    Some("class Client:".to_string())
+                ),
+                (
+                    "    def send(self):\n        return self.retry()",
+                    Some("class Client:".to_string())
+                ),
+                (
+                    "    def retry(self):\n        pass",
+                    Some("class Client:".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line_windows() {
+        let text = "fn main() {\n    let a = 1;\n    let b = 2;\n    let c = 3;\n}\n";
+        assert_eq!(
+            chunks(text, 42),
+            vec![
+                (
+                    "fn main() {\n    let a = 1;",
+                    Some("fn main() {".to_string())
+                ),
+                (
+                    "    let b = 2;\n    let c = 3;",
+                    Some("fn main() {".to_string())
+                ),
+                ("}", Some("fn main() {".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_long_line_split_with_heading() {
+        let text = "class Client:\n\n    def send(self):\n        return post(alpha, beta, gamma, delta, epsilon, zeta, eta, theta)\n\n    def close(self):\n        pass\n";
+        let chunks = chunks(text, 60);
+        let heading = "class Client: > def send(self):";
+        assert_eq!(
+            chunks[1..4],
+            [
+                ("    def send(self):", Some(heading.to_string())),
+                ("return post(alpha, beta,", Some(heading.to_string())),
+                ("gamma, delta, epsilon, zeta", Some(heading.to_string())),
+            ]
+        );
+        // Every chunk fits along with its heading path
+        for (chunk, heading) in &chunks {
+            let embedded = heading
+                .as_ref()
+                .map_or(chunk.to_string(), |h| with_heading(h, chunk));
+            assert!(embedded.len() <= 60, "{:?}", embedded);
+        }
+    }
+}
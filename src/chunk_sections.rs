@@ -0,0 +1,171 @@
+use crate::chunk_text::trim_indices;
+
+/// Separator between the titles of nested headings in a heading path
+pub const HEADING_PATH_SEPARATOR: &str = " > ";
+
+/// Text that is embedded for a chunk within a section, giving the chunk its context
+pub fn with_heading(heading: &str, text: &str) -> String {
+    format!("{}\n\n{}", heading, text)
+}
+
+/// A section heading detected in a document
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heading {
+    /// Page the section starts on
+    pub page_no: usize,
+    /// Nesting level, starting at 1 for top level sections
+    pub level: usize,
+    pub title: String,
+}
+
+/// Splits text joined by `concatenate_pages` into sections starting at each heading.
+///
+/// Headings are looked up on their page, ignoring ASCII case. Headings that cannot be
+/// found, e.g. because OCR read them differently, start their section at the top of the page.
+///
+/// # Arguments
+///
+/// * `text` - The text of the whole document.
+/// * `page_ranges` - The range each page occupies in `text`.
+/// * `headings` - The headings of the document, in document order.
+///
+/// # Returns
+///
+/// Trimmed section indices, each with the path of headings leading to it,
+/// e.g. "3 Methods > 3.2 Data". Text before the first heading has no path.
+pub fn section_indices(
+    text: &str,
+    page_ranges: &[(usize, usize)],
+    headings: &[Heading],
+) -> Vec<(usize, usize, Option<String>)> {
+    let lowercase_text = text.to_ascii_lowercase();
+
+    let mut starts: Vec<(usize, Option<String>)> = vec![(0, None)];
+    let mut path: Vec<&Heading> = Vec::new();
+    let mut position = 0;
+
+    for heading in headings {
+        let Some(&(page_start, page_end)) = page_ranges.get(heading.page_no) else {
+            log::debug!("Ignoring heading {} on missing page", heading.title);
+            continue;
+        };
+
+        // Sections never start before the previous one
+        let search_start = position.max(page_start);
+        let title = heading.title.trim().to_ascii_lowercase();
+        position = lowercase_text
+            .get(search_start..page_end)
+            .and_then(|page| page.find(&title))
+            .map_or(search_start, |offset| search_start + offset);
+
+        while path.last().is_some_and(|h| h.level >= heading.level) {
+            path.pop();
+        }
+        path.push(heading);
+
+        let heading_path = path
+            .iter()
+            .map(|h| h.title.trim())
+            .collect::<Vec<&str>>()
+            .join(HEADING_PATH_SEPARATOR);
+        starts.push((position, Some(heading_path)));
+    }
+
+    let ends = starts
+        .iter()
+        .skip(1)
+        .map(|(start, _)| *start)
+        .chain(std::iter::once(text.len()));
+
+    starts
+        .iter()
+        .zip(ends)
+        .filter_map(|((start, heading_path), end)| {
+            trim_indices(text, (*start, end)).map(|(start, end)| (start, end, heading_path.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk_sections::{section_indices, Heading};
+    use crate::chunk_text::concatenate_pages;
+
+    fn heading(page_no: usize, level: usize, title: &str) -> Heading {
+        Heading {
+            page_no,
+            level,
+            title: title.to_string(),
+        }
+    }
+
+    fn sections(pages: &[&str], headings: &[Heading]) -> Vec<(String, Option<String>)> {
+        let (text, ranges) = concatenate_pages(pages, "\n\n");
+        section_indices(&text, &ranges, headings)
+            .into_iter()
+            .map(|(start, end, path)| (text[start..end].to_string(), path))
+            .collect()
+    }
+
+    #[test]
+    fn test_sections() {
+        let pages = [
+            "Preface text.\n3 METHODS\nIntro to methods.",
+            "3.1 Setup\nSetup text.\n3.2 Data\nData text.",
+            "4 Results\nResults text.",
+        ];
+        let headings = [
+            heading(0, 1, "3 Methods"),
+            heading(1, 2, "3.1 Setup"),
+            heading(1, 2, "3.2 Data"),
+            heading(2, 1, "4 Results"),
+        ];
+        assert_eq!(
+            sections(&pages, &headings),
+            vec![
+                ("Preface text.".to_string(), None),
+                (
+                    "3 METHODS\nIntro to methods.".to_string(),
+                    Some("3 Methods".to_string())
+                ),
+                (
+                    "3.1 Setup\nSetup text.".to_string(),
+                    Some("3 Methods > 3.1 Setup".to_string())
+                ),
+                (
+                    "3.2 Data\nData text.".to_string(),
+                    Some("3 Methods > 3.2 Data".to_string())
+                ),
+                (
+                    "4 Results\nResults text.".to_string(),
+                    Some("4 Results".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_heading_starts_at_page() {
+        let pages = ["Intro.", "OCR garbled heading\nBody."];
+        let headings = [heading(1, 1, "Chapter 2")];
+        assert_eq!(
+            sections(&pages, &headings),
+            vec![
+                ("Intro.".to_string(), None),
+                (
+                    "OCR garbled heading\nBody.".to_string(),
+                    Some("Chapter 2".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_headings() {
+        let pages = ["Only text."];
+        assert_eq!(
+            sections(&pages, &[]),
+            vec![("Only text.".to_string(), None)]
+        );
+    }
+}
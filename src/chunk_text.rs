@@ -1,5 +1,48 @@
+use serde::{Deserialize, Serialize};
+use tokenizers::Tokenizer;
 use unicode_segmentation::UnicodeSegmentation;
 
+/// Unit the size and stride of chunks are measured in
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkUnit {
+    /// Grapheme clusters of the text
+    Graphemes,
+    /// Tokens of the embedding model's tokenizer
+    Tokens,
+}
+
+/// How the text of a page is split into chunks
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkStrategy {
+    /// Overlapping windows of a fixed size
+    SlidingWindow,
+    /// Whole sentences packed up to the chunk size
+    Sentence,
+    /// Whole paragraphs packed up to the chunk size, split into sentences when too large
+    Paragraph,
+    /// Sections of the document outline or headings, chunked like paragraphs
+    Section,
+}
+
+/// Checks that a chunk size and stride describe a sliding window without gaps
+pub fn validate_sliding_window(chunk_size: usize, stride: usize) -> anyhow::Result<()> {
+    if chunk_size == 0 {
+        return Err(anyhow::anyhow!("Chunk size must be greater than 0"));
+    }
+
+    if stride == 0 || stride > chunk_size {
+        return Err(anyhow::anyhow!(
+            "Chunk stride must be between 1 and the chunk size ({}), got {}",
+            chunk_size,
+            stride
+        ));
+    }
+
+    Ok(())
+}
+
 /// Returns indices of chunks using sliding window chunking, respecting grapheme clusters.
 ///
 /// # Arguments
@@ -10,14 +53,15 @@ use unicode_segmentation::UnicodeSegmentation;
 ///
 /// # Returns
 ///
-/// Iterator of chunk indices
+/// Iterator of chunk indices, as byte offsets into `text`
 pub fn sliding_window_chunk_indices(
     text: &str,
     chunk_size: usize,
     stride: usize,
 ) -> impl Iterator<Item = (usize, usize)> + '_ {
-    // assert that chunk_size is greater than 0
+    // assert that chunk_size is greater than 0 and chunks do not leave gaps
     assert!(chunk_size > 0);
+    assert!(stride > 0 && stride <= chunk_size);
 
     // Extra state for when end of text is reached
     let mut done = false;
@@ -44,6 +88,357 @@ pub fn sliding_window_chunk_indices(
         })
 }
 
+/// Returns indices of chunks using sliding window chunking over the tokens produced by `tokenizer`.
+///
+/// Chunks start and end on token boundaries, so the indices are byte offsets into `text`
+/// like those of `sliding_window_chunk_indices`. No chunk tokenizes to more than
+/// `max_tokens` tokens on its own, windows are shrunk and the chunk size clamped to make sure of it.
+///
+/// # Arguments
+///
+/// * `text` - The input string to be chunked.
+/// * `tokenizer` - The tokenizer of the embedding model.
+/// * `chunk_size` - The desired size of each chunk in tokens.
+/// * `stride` - The number of tokens to skip between chunks.
+/// * `max_tokens` - The maximum number of tokens the model embeds without truncation.
+///
+/// # Returns
+///
+/// Vector of chunk indices
+pub fn token_window_chunk_indices(
+    text: &str,
+    tokenizer: &Tokenizer,
+    chunk_size: usize,
+    stride: usize,
+    max_tokens: usize,
+) -> anyhow::Result<Vec<(usize, usize)>> {
+    // Chunks must have a size and not leave gaps
+    if chunk_size == 0 || max_tokens == 0 {
+        anyhow::bail!(
+            "Chunks must have at least 1 token, got a size of {} and a model limit of {}",
+            chunk_size,
+            max_tokens
+        );
+    }
+    if stride == 0 || stride > chunk_size {
+        anyhow::bail!(
+            "The chunk stride must be from 1 to the chunk size of {} tokens, got {}",
+            chunk_size,
+            stride
+        );
+    }
+
+    if chunk_size > max_tokens {
+        log::debug!(
+            "Clamping chunk size of {} tokens to the model limit of {} tokens",
+            chunk_size,
+            max_tokens
+        );
+    }
+    let chunk_size = chunk_size.min(max_tokens);
+    let stride = stride.min(chunk_size);
+
+    // The whole text is tokenized at once, so the model's truncation must be disabled
+    let tokenizer = untruncated_tokenizer(tokenizer)?;
+
+    let encoding = tokenizer.encode(text, false).map_err(anyhow::Error::msg)?;
+    let offsets = encoding.get_offsets();
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < offsets.len() {
+        let mut end = (start + chunk_size).min(offsets.len());
+
+        // A chunk tokenized on its own can differ at the edges, shrink it until it fits
+        while end > start + 1
+            && token_count(&tokenizer, &text[offsets[start].0..offsets[end - 1].1]) > max_tokens
+        {
+            end -= 1;
+        }
+
+        chunks.push((offsets[start].0, offsets[end - 1].1));
+
+        if end == offsets.len() {
+            break;
+        }
+
+        // Never skip past the end of a shrunk chunk
+        start = (start + stride).min(end);
+    }
+
+    Ok(chunks)
+}
+
+/// Returns a copy of the tokenizer that neither truncates nor pads its output
+pub fn untruncated_tokenizer(tokenizer: &Tokenizer) -> anyhow::Result<Tokenizer> {
+    let mut tokenizer = tokenizer.clone();
+    tokenizer
+        .with_truncation(None)
+        .map_err(anyhow::Error::msg)?
+        .with_padding(None);
+
+    Ok(tokenizer)
+}
+
+/// Length of a text in tokens, text that cannot be tokenized never fits in a chunk
+pub fn token_count(tokenizer: &Tokenizer, text: &str) -> usize {
+    tokenizer
+        .encode(text, false)
+        .map(|encoding| encoding.len())
+        .unwrap_or(usize::MAX)
+}
+
+/// Length of a text in grapheme clusters
+pub fn grapheme_count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+/// Returns indices of chunks made of whole sentences.
+///
+/// Sentences are packed into a chunk as long as it measures at most `max_size`.
+/// The last `overlap` sentences of a chunk are repeated at the start of the next one.
+/// Sentences that are too long on their own are split at word boundaries.
+///
+/// # Arguments
+///
+/// * `text` - The input string to be chunked.
+/// * `max_size` - The maximum size of each chunk, as returned by `measure`.
+/// * `overlap` - The number of sentences shared by consecutive chunks.
+/// * `measure` - Function returning the size of a piece of text.
+///
+/// # Returns
+///
+/// Vector of chunk indices
+pub fn sentence_chunk_indices(
+    text: &str,
+    max_size: usize,
+    overlap: usize,
+    measure: impl Fn(&str) -> usize,
+) -> Vec<(usize, usize)> {
+    assert!(max_size > 0);
+
+    let sentences = sentence_indices(text, (0, text.len()), max_size, &measure);
+    pack_indices(text, &sentences, max_size, overlap, &measure)
+}
+
+/// Returns indices of chunks made of whole paragraphs, separated by blank lines.
+///
+/// Consecutive paragraphs are packed into a chunk as long as it measures at most `max_size`.
+/// Paragraphs that are too long on their own are chunked like `sentence_chunk_indices`,
+/// with `overlap` sentences shared by consecutive chunks of the same paragraph.
+///
+/// # Arguments
+///
+/// * `text` - The input string to be chunked.
+/// * `max_size` - The maximum size of each chunk, as returned by `measure`.
+/// * `overlap` - The number of sentences shared by consecutive chunks of a paragraph.
+/// * `measure` - Function returning the size of a piece of text.
+///
+/// # Returns
+///
+/// Vector of chunk indices
+pub fn paragraph_chunk_indices(
+    text: &str,
+    max_size: usize,
+    overlap: usize,
+    measure: impl Fn(&str) -> usize,
+) -> Vec<(usize, usize)> {
+    assert!(max_size > 0);
+
+    let mut chunks = Vec::new();
+    let mut paragraphs: Vec<(usize, usize)> = Vec::new();
+
+    for paragraph in paragraph_indices(text) {
+        if measure(&text[paragraph.0..paragraph.1]) <= max_size {
+            paragraphs.push(paragraph);
+            continue;
+        }
+
+        // Flush the paragraphs before the one that needs splitting into sentences
+        chunks.extend(pack_indices(text, &paragraphs, max_size, 0, &measure));
+        paragraphs.clear();
+
+        let sentences = sentence_indices(text, paragraph, max_size, &measure);
+        chunks.extend(pack_indices(text, &sentences, max_size, overlap, &measure));
+    }
+    chunks.extend(pack_indices(text, &paragraphs, max_size, 0, &measure));
+
+    chunks
+}
+
+/// Greedily packs consecutive pieces of text into chunks of at most `max_size`,
+/// repeating the last `overlap` pieces of a chunk at the start of the next one
+fn pack_indices(
+    text: &str,
+    pieces: &[(usize, usize)],
+    max_size: usize,
+    overlap: usize,
+    measure: &impl Fn(&str) -> usize,
+) -> Vec<(usize, usize)> {
+    let mut chunks = Vec::new();
+    let mut first = 0;
+
+    while first < pieces.len() {
+        let mut last = first;
+        while last + 1 < pieces.len()
+            && measure(&text[pieces[first].0..pieces[last + 1].1]) <= max_size
+        {
+            last += 1;
+        }
+
+        chunks.push((pieces[first].0, pieces[last].1));
+
+        if last + 1 == pieces.len() {
+            break;
+        }
+
+        // Always move forward, even when the overlap covers the whole chunk
+        first = (last + 1).saturating_sub(overlap).max(first + 1);
+    }
+
+    chunks
+}
+
+/// Splits a range of text into trimmed sentences, splitting sentences longer
+/// than `max_size` into runs of words that fit
+fn sentence_indices(
+    text: &str,
+    (start, end): (usize, usize),
+    max_size: usize,
+    measure: &impl Fn(&str) -> usize,
+) -> Vec<(usize, usize)> {
+    text[start..end]
+        .split_sentence_bound_indices()
+        .filter_map(|(from, sentence)| {
+            trim_indices(text, (start + from, start + from + sentence.len()))
+        })
+        .flat_map(|sentence| {
+            if measure(&text[sentence.0..sentence.1]) <= max_size {
+                vec![sentence]
+            } else {
+                word_indices(text, sentence, max_size, measure)
+            }
+        })
+        .collect()
+}
+
+/// Splits a range of text into runs of whole words of at most `max_size`.
+/// Words that are too long on their own are split into grapheme clusters.
+pub fn word_indices(
+    text: &str,
+    (start, end): (usize, usize),
+    max_size: usize,
+    measure: &impl Fn(&str) -> usize,
+) -> Vec<(usize, usize)> {
+    let words: Vec<(usize, usize)> = text[start..end]
+        .split_word_bound_indices()
+        .filter_map(|(from, word)| trim_indices(text, (start + from, start + from + word.len())))
+        .flat_map(|word| {
+            if measure(&text[word.0..word.1]) <= max_size {
+                vec![word]
+            } else {
+                text[word.0..word.1]
+                    .grapheme_indices(true)
+                    .map(|(from, grapheme)| (word.0 + from, word.0 + from + grapheme.len()))
+                    .collect()
+            }
+        })
+        .collect();
+
+    pack_indices(text, &words, max_size, 0, measure)
+}
+
+/// Splits text into trimmed paragraphs separated by blank lines
+fn paragraph_indices(text: &str) -> Vec<(usize, usize)> {
+    let mut paragraphs = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            paragraphs.extend(current.take());
+        } else {
+            let start = current.map_or(offset, |(start, _)| start);
+            current = Some((start, offset + line.len()));
+        }
+        offset += line.len();
+    }
+    paragraphs.extend(current);
+
+    paragraphs
+        .into_iter()
+        .filter_map(|paragraph| trim_indices(text, paragraph))
+        .collect()
+}
+
+/// Shrinks a range of text to exclude leading and trailing whitespace,
+/// returning None if nothing but whitespace is left
+pub fn trim_indices(text: &str, (start, end): (usize, usize)) -> Option<(usize, usize)> {
+    let slice = &text[start..end];
+    let trimmed_start = start + slice.len() - slice.trim_start().len();
+    let trimmed_end = start + slice.trim_end().len();
+
+    (trimmed_start < trimmed_end).then_some((trimmed_start, trimmed_end))
+}
+
+/// Joins the text of pages with a separator so it can be chunked as a whole.
+///
+/// # Returns
+///
+/// The joined text and the range each page occupies in it
+pub fn concatenate_pages(pages: &[&str], separator: &str) -> (String, Vec<(usize, usize)>) {
+    let mut text = String::new();
+    let mut ranges = Vec::with_capacity(pages.len());
+
+    for (i, page) in pages.iter().enumerate() {
+        if i > 0 {
+            text.push_str(separator);
+        }
+        ranges.push((text.len(), text.len() + page.len()));
+        text.push_str(page);
+    }
+
+    (text, ranges)
+}
+
+/// Maps a chunk of text joined by `concatenate_pages` back to the pages it covers.
+/// Chunk edges falling on a separator are moved to the closest page.
+///
+/// # Returns
+///
+/// The index of the start page and the offset into it, and the index of
+/// the end page and the offset into it
+pub fn locate_in_pages(
+    page_ranges: &[(usize, usize)],
+    (start, end): (usize, usize),
+) -> ((usize, usize), (usize, usize)) {
+    // First page ending after the chunk starts
+    let start_page = page_ranges
+        .iter()
+        .position(|&(_, page_end)| start < page_end)
+        .unwrap_or(page_ranges.len() - 1);
+    // Last page starting before the chunk ends
+    let end_page = page_ranges
+        .iter()
+        .rposition(|&(page_start, _)| page_start < end)
+        .unwrap_or(0)
+        .max(start_page);
+
+    let (start_page_start, start_page_end) = page_ranges[start_page];
+    let (end_page_start, end_page_end) = page_ranges[end_page];
+
+    (
+        (
+            start_page,
+            start.clamp(start_page_start, start_page_end) - start_page_start,
+        ),
+        (
+            end_page,
+            end.clamp(end_page_start, end_page_end) - end_page_start,
+        ),
+    )
+}
+
 /// Chunks a string using sliding window chunking, respecting grapheme clusters.
 ///
 /// # Arguments
@@ -66,7 +461,36 @@ pub fn sliding_window_chunk(
 
 #[cfg(test)]
 mod tests {
-    use crate::chunk_text::sliding_window_chunk;
+    use crate::chunk_text::{
+        concatenate_pages, grapheme_count, locate_in_pages, paragraph_chunk_indices,
+        sentence_chunk_indices, sliding_window_chunk, token_window_chunk_indices,
+        validate_sliding_window,
+    };
+    use std::collections::HashMap;
+    use tokenizers::models::wordlevel::WordLevel;
+    use tokenizers::pre_tokenizers::whitespace::Whitespace;
+    use tokenizers::Tokenizer;
+
+    /// Tokenizer splitting words and punctuation into one token each
+    fn word_tokenizer() -> Tokenizer {
+        let vocab = HashMap::from([("[UNK]".to_string(), 0)]);
+        let model = WordLevel::builder()
+            .vocab(vocab)
+            .unk_token("[UNK]".to_string())
+            .build()
+            .unwrap();
+        let mut tokenizer = Tokenizer::new(model);
+        tokenizer.with_pre_tokenizer(Whitespace {});
+        tokenizer
+    }
+
+    fn token_window_chunk(text: &str, chunk_size: usize, stride: usize) -> Vec<&str> {
+        token_window_chunk_indices(text, &word_tokenizer(), chunk_size, stride, 100)
+            .unwrap()
+            .into_iter()
+            .map(|(from, to)| &text[from..to])
+            .collect()
+    }
 
     #[test]
     fn test_sliding_window_chunk() {
@@ -129,4 +553,172 @@ mod tests {
         let chunks: Vec<&str> = sliding_window_chunk(text, 2, 1).collect();
         assert_eq!(chunks, vec!["e\u{301}e\u{301}", "e\u{301}e\u{301}"]);
     }
+
+    #[test]
+    fn test_token_window_chunk() {
+        let text = "This is a sample text for chunking.";
+        let chunks = token_window_chunk(text, 3, 2);
+        assert_eq!(
+            chunks,
+            vec![
+                "This is a",
+                "a sample text",
+                "text for chunking",
+                "chunking."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_token_window_short_input() {
+        assert_eq!(token_window_chunk("Short", 10, 5), vec!["Short"]);
+        assert!(token_window_chunk("", 10, 5).is_empty());
+    }
+
+    #[test]
+    fn test_token_window_unicode() {
+        let text = "über straße こんにちは 世界";
+        let chunks = token_window_chunk(text, 2, 2);
+        assert_eq!(chunks, vec!["über straße", "こんにちは 世界"]);
+    }
+
+    #[test]
+    fn test_token_window_clamped_to_max_tokens() {
+        let text = "one two three four five";
+        let chunks: Vec<&str> = token_window_chunk_indices(text, &word_tokenizer(), 10, 10, 2)
+            .unwrap()
+            .into_iter()
+            .map(|(from, to)| &text[from..to])
+            .collect();
+        assert_eq!(chunks, vec!["one two", "three four", "five"]);
+    }
+
+    #[test]
+    fn test_token_window_invalid_sizes() {
+        let tokenizer = word_tokenizer();
+        assert!(token_window_chunk_indices("one two", &tokenizer, 10, 5, 0).is_err());
+        assert!(token_window_chunk_indices("one two", &tokenizer, 0, 0, 10).is_err());
+        assert!(token_window_chunk_indices("one two", &tokenizer, 10, 0, 10).is_err());
+        assert!(token_window_chunk_indices("one two", &tokenizer, 5, 6, 10).is_err());
+    }
+
+    fn sentence_chunk(text: &str, max_size: usize, overlap: usize) -> Vec<&str> {
+        sentence_chunk_indices(text, max_size, overlap, grapheme_count)
+            .into_iter()
+            .map(|(from, to)| &text[from..to])
+            .collect()
+    }
+
+    fn paragraph_chunk(text: &str, max_size: usize, overlap: usize) -> Vec<&str> {
+        paragraph_chunk_indices(text, max_size, overlap, grapheme_count)
+            .into_iter()
+            .map(|(from, to)| &text[from..to])
+            .collect()
+    }
+
+    #[test]
+    fn test_sentence_chunk() {
+        let text = "This is a sample text. It has three sentences. The last one is here.";
+        assert_eq!(
+            sentence_chunk(text, 50, 0),
+            vec![
+                "This is a sample text. It has three sentences.",
+                "The last one is here."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sentence_overlap() {
+        let text = "This is a sample text. It has three sentences. The last one is here.";
+        assert_eq!(
+            sentence_chunk(text, 50, 1),
+            vec![
+                "This is a sample text. It has three sentences.",
+                "It has three sentences. The last one is here."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sentence_short_input() {
+        assert_eq!(sentence_chunk("Short", 10, 1), vec!["Short"]);
+        assert!(sentence_chunk(" \n ", 10, 1).is_empty());
+    }
+
+    #[test]
+    fn test_long_sentence_split_on_words() {
+        let text = "Short. This sentence is much too long to fit.";
+        assert_eq!(
+            sentence_chunk(text, 10, 0),
+            vec!["Short.", "This", "sentence", "is much", "too long", "to fit."]
+        );
+    }
+
+    #[test]
+    fn test_sentence_unicode() {
+        let text = "こんにちは。世界です。";
+        assert_eq!(
+            sentence_chunk(text, 6, 0),
+            vec!["こんにちは。", "世界です。"]
+        );
+        assert_eq!(sentence_chunk(text, 11, 0), vec!["こんにちは。世界です。"]);
+    }
+
+    #[test]
+    fn test_paragraph_chunk() {
+        let text = "First paragraph. Still first.\n\nSecond paragraph.\n\n  \nThird one is a bit longer. It has two sentences.\n";
+        assert_eq!(
+            paragraph_chunk(text, 50, 0),
+            vec![
+                "First paragraph. Still first.\n\nSecond paragraph.",
+                "Third one is a bit longer. It has two sentences."
+            ]
+        );
+        assert_eq!(
+            paragraph_chunk(text, 30, 0),
+            vec![
+                "First paragraph. Still first.",
+                "Second paragraph.",
+                "Third one is a bit longer.",
+                "It has two sentences."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chunks_across_pages() {
+        let pages = ["First page ends mid", "sentence. Second page.", "Third"];
+        let (text, ranges) = concatenate_pages(&pages, "\n\n");
+        assert_eq!(
+            text,
+            "First page ends mid\n\nsentence. Second page.\n\nThird"
+        );
+        assert_eq!(ranges, vec![(0, 19), (21, 43), (45, 50)]);
+
+        let chunks = sentence_chunk_indices(&text, 30, 0, grapheme_count);
+        let locations: Vec<_> = chunks
+            .iter()
+            .map(|&c| locate_in_pages(&ranges, c))
+            .collect();
+        assert_eq!(locations, vec![((0, 0), (1, 9)), ((1, 10), (2, 5))]);
+    }
+
+    #[test]
+    fn test_locate_on_separator() {
+        let ranges = [(0, 10), (12, 20)];
+        // Starting and ending inside the separator
+        assert_eq!(locate_in_pages(&ranges, (5, 11)), ((0, 5), (0, 10)));
+        assert_eq!(locate_in_pages(&ranges, (11, 15)), ((1, 0), (1, 3)));
+        assert_eq!(locate_in_pages(&ranges, (0, 20)), ((0, 0), (1, 8)));
+    }
+
+    #[test]
+    fn test_validate_sliding_window() {
+        assert!(validate_sliding_window(512, 64).is_ok());
+        assert!(validate_sliding_window(5, 5).is_ok());
+        assert!(validate_sliding_window(0, 0).is_err());
+        assert!(validate_sliding_window(5, 0).is_err());
+        assert!(validate_sliding_window(5, 6).is_err());
+    }
 }
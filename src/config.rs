@@ -0,0 +1,778 @@
+use crate::chunk_text::{validate_sliding_window, ChunkStrategy, ChunkUnit};
+use crate::generate_embeddings::parse_embedding_model;
+use crate::image_preprocess::PreprocessStep;
+use crate::lazy_init::DATA_DIR_NAME;
+use fastembed::EmbeddingModel;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Settings that determine the contents of an index. They are recorded in the
+/// database when a collection is created and only change when it is rebuilt.
+pub const INDEX_SETTINGS: &[&str] = &[
+    "chunking.size",
+    "chunking.stride",
+    "chunking.unit",
+    "chunking.strategy",
+    "chunking.sentence_overlap",
+    "chunking.span_pages",
+    "embedding.model",
+    "embedding.multilingual_model",
+];
+
+/// Where the effective value of a setting came from
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Default,
+    Global(PathBuf),
+    Collection(PathBuf),
+    CommandLine,
+    Index,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::Global(path) => write!(f, "global config {}", path.display()),
+            Source::Collection(path) => write!(f, "collection config {}", path.display()),
+            Source::CommandLine => write!(f, "command line"),
+            Source::Index => write!(f, "recorded in index"),
+        }
+    }
+}
+
+/// A single configuration value along with the dotted key it is read from
+/// and the place its current value came from
+#[derive(Clone, Debug)]
+pub struct Setting<T> {
+    pub key: &'static str,
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    fn new(key: &'static str, value: T) -> Setting<T> {
+        Setting {
+            key,
+            value,
+            source: Source::Default,
+        }
+    }
+
+    /// Replace the value if one is given, recording where it came from
+    fn set(&mut self, value: Option<T>, source: Source) {
+        if let Some(value) = value {
+            self.value = value;
+            self.source = source;
+        }
+    }
+}
+
+/// Type erased access to a setting, used to merge config files and print the config
+trait AnySetting {
+    fn key(&self) -> &'static str;
+    fn source(&self) -> &Source;
+    fn merge(&mut self, value: toml::Value, source: &Source) -> anyhow::Result<()>;
+    fn to_toml(&self) -> anyhow::Result<toml::Value>;
+}
+
+impl<T: Serialize + DeserializeOwned> AnySetting for Setting<T> {
+    fn key(&self) -> &'static str {
+        self.key
+    }
+
+    fn source(&self) -> &Source {
+        &self.source
+    }
+
+    fn merge(&mut self, value: toml::Value, source: &Source) -> anyhow::Result<()> {
+        let value = value
+            .try_into()
+            .map_err(|e| anyhow::anyhow!("Invalid value for {} in {}: {}", self.key, source, e))?;
+        self.set(Some(value), source.clone());
+        Ok(())
+    }
+
+    fn to_toml(&self) -> anyhow::Result<toml::Value> {
+        Ok(toml::Value::try_from(&self.value)?)
+    }
+}
+
+/// Declares the `Config` struct from a list of settings, each with its dotted
+/// config file key and default value
+macro_rules! settings {
+    ($($(#[$doc:meta])* $field:ident: $ty:ty = $key:literal => $default:expr,)*) => {
+        #[derive(Clone, Debug)]
+        pub struct Config {
+            $($(#[$doc])* pub $field: Setting<$ty>,)*
+        }
+
+        impl Default for Config {
+            fn default() -> Self {
+                Config {
+                    $($field: Setting::new($key, $default),)*
+                }
+            }
+        }
+
+        impl Config {
+            fn settings(&self) -> Vec<&dyn AnySetting> {
+                vec![$(&self.$field),*]
+            }
+
+            fn settings_mut(&mut self) -> Vec<&mut dyn AnySetting> {
+                vec![$(&mut self.$field),*]
+            }
+        }
+    };
+}
+
+settings! {
+    /// Size of each chunk, measured in `chunk_unit`
+    chunk_size: usize = "chunking.size" => 512,
+    /// Distance between the start of consecutive chunks, measured in `chunk_unit`
+    chunk_stride: usize = "chunking.stride" => 64,
+    /// Whether chunks are measured in graphemes or model tokens
+    chunk_unit: ChunkUnit = "chunking.unit" => ChunkUnit::Graphemes,
+    /// How pages are split into chunks
+    chunk_strategy: ChunkStrategy = "chunking.strategy" => ChunkStrategy::SlidingWindow,
+    /// Number of sentences repeated between chunks by the sentence and paragraph strategies
+    sentence_overlap: usize = "chunking.sentence_overlap" => 1,
+    /// Whether chunks are made from the text of the whole document instead of each page
+    span_pages: bool = "chunking.span_pages" => false,
+    /// Name of the fastembed model used to generate embeddings
+    embedding_model: String = "embedding.model" => String::from("BGEBaseENV15"),
+    /// Name of the fastembed model used for pages in languages other than English, or empty
+    /// to use `embedding_model` for all pages
+    multilingual_model: String = "embedding.multilingual_model" => String::new(),
+    /// Number of chunks embedded in a single batch
+    embedding_batch_size: usize = "embedding.batch_size" => 256,
+    /// Number of results returned by a search
+    top_k: usize = "search.top_k" => 10,
+    /// Tesseract language(s), e.g. "eng" or "eng+deu"
+    ocr_language: String = "ocr.language" => String::from("eng"),
+    /// Resolution tesseract assumes for rendered pages
+    ocr_dpi: i32 = "ocr.dpi" => 150,
+    /// Tesseract page segmentation mode, 0 to 13
+    ocr_psm: i32 = "ocr.psm" => 3,
+    /// Tesseract OCR engine mode, 0 to 3
+    ocr_oem: i32 = "ocr.oem" => 3,
+    /// Characters tesseract is limited to recognizing, or empty for any
+    ocr_whitelist: String = "ocr.whitelist" => String::new(),
+    /// Tesseract config variables as `name=value`, e.g. "preserve_interword_spaces=1"
+    ocr_variables: Vec<String> = "ocr.variables" => Vec::new(),
+    /// Tesseract config file of `name value` lines, such as a copy of parameters.txt
+    ocr_config_file: String = "ocr.config_file" => String::new(),
+    /// Steps images are prepared with before OCR, e.g. ["deskew", "binarize"]
+    ocr_preprocess: Vec<PreprocessStep> = "ocr.preprocess" => Vec::new(),
+    /// Mean word confidence, from 0 to 100, below which pages read with OCR are reported
+    /// and flagged in search results
+    ocr_min_confidence: f32 = "ocr.min_confidence" => 60.0,
+    /// Whether text read with OCR is cached by image and OCR settings, so the same page isn't
+    /// read again when re-indexing or in another document or collection
+    ocr_cache: bool = "ocr.cache" => true,
+    /// Whether the language of each page is detected, running OCR again with the detected
+    /// language when it differs from `ocr_language`
+    detect_language: bool = "language.detect" => false,
+    /// Width in pixels PDF pages are rendered at before OCR
+    render_width: i32 = "render.target_width" => 2000,
+    /// Maximum height in pixels PDF pages are rendered at before OCR
+    render_max_height: i32 = "render.max_height" => 2000,
+    /// Whether PDF files attached to emails are indexed along with the message
+    email_pdf_attachments: bool = "email.pdf_attachments" => false,
+    /// TOML file mapping glob patterns of paths to the password opening the encrypted PDF
+    /// files they match, or empty for none
+    pdf_password_file: String = "pdf.password_file" => String::new(),
+}
+
+/// Command line flags overriding values from the config files
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct ConfigOverrides {
+    /// Chunk size in chunk units
+    #[clap(long, global = true)]
+    pub chunk_size: Option<usize>,
+    /// Chunk stride in chunk units
+    #[clap(long, global = true)]
+    pub chunk_stride: Option<usize>,
+    /// Unit chunk size and stride are measured in
+    #[clap(long, global = true, value_enum)]
+    pub chunk_unit: Option<ChunkUnit>,
+    /// Strategy used to split pages into chunks
+    #[clap(long, global = true, value_enum)]
+    pub chunk_strategy: Option<ChunkStrategy>,
+    /// Number of sentences shared by consecutive chunks
+    #[clap(long, global = true)]
+    pub sentence_overlap: Option<usize>,
+    /// Chunk the whole document so chunks can span page boundaries
+    #[clap(long, global = true)]
+    pub span_pages: bool,
+    /// Chunk each page on its own, even when the config spans pages
+    #[clap(long, global = true, conflicts_with = "span_pages")]
+    pub no_span_pages: bool,
+    /// Embedding model name, e.g. BGEBaseENV15
+    #[clap(long, global = true)]
+    pub model: Option<String>,
+    /// Embedding model for pages in languages other than English, e.g. MultilingualE5Base
+    #[clap(long, global = true)]
+    pub multilingual_model: Option<String>,
+    /// Number of chunks embedded per batch
+    #[clap(long, global = true)]
+    pub batch_size: Option<usize>,
+    /// Number of search results to return
+    #[clap(short = 'k', long, global = true)]
+    pub top_k: Option<usize>,
+    /// Tesseract language(s), e.g. eng+deu
+    #[clap(long, global = true)]
+    pub ocr_lang: Option<String>,
+    /// DPI passed to tesseract
+    #[clap(long, global = true)]
+    pub ocr_dpi: Option<i32>,
+    /// Tesseract page segmentation mode
+    #[clap(long, global = true)]
+    pub ocr_psm: Option<i32>,
+    /// Tesseract OCR engine mode
+    #[clap(long, global = true)]
+    pub ocr_oem: Option<i32>,
+    /// Characters tesseract is limited to recognizing
+    #[clap(long, global = true)]
+    pub ocr_whitelist: Option<String>,
+    /// Tesseract config variable as name=value, may be repeated
+    #[clap(long = "ocr-var", global = true)]
+    pub ocr_variables: Vec<String>,
+    /// Tesseract config file of `name value` lines
+    #[clap(long = "ocr-config", global = true)]
+    pub ocr_config_file: Option<String>,
+    /// Preprocessing steps applied to images before OCR, e.g. deskew,binarize
+    #[clap(long, global = true, value_enum, value_delimiter = ',')]
+    pub preprocess: Vec<PreprocessStep>,
+    /// OCR confidence, from 0 to 100, below which pages are considered poorly recognized
+    #[clap(long, global = true)]
+    pub min_confidence: Option<f32>,
+    /// Read every page with OCR again rather than taking its text from the OCR cache
+    #[clap(long, global = true)]
+    pub no_ocr_cache: bool,
+    /// Detect the language of each page and run OCR with it
+    #[clap(long, global = true)]
+    pub detect_language: bool,
+    /// Run OCR with the configured languages, even when the config detects them
+    #[clap(long, global = true, conflicts_with = "detect_language")]
+    pub no_detect_language: bool,
+    /// Width in pixels PDF pages are rendered at
+    #[clap(long, global = true)]
+    pub render_width: Option<i32>,
+    /// Index the PDF attachments of emails
+    #[clap(long, global = true)]
+    pub pdf_attachments: bool,
+    /// Leave out the PDF attachments of emails, even when the config indexes them
+    #[clap(long, global = true, conflicts_with = "pdf_attachments")]
+    pub no_pdf_attachments: bool,
+    /// TOML file mapping glob patterns of paths to the password of the encrypted PDF files
+    /// they match
+    #[clap(long, global = true)]
+    pub password_file: Option<String>,
+}
+
+impl Config {
+    /// Load the effective configuration for a collection.
+    ///
+    /// Values are layered in order of increasing priority: built-in defaults,
+    /// the global config file, the collection's config file and finally the
+    /// command line overrides.
+    pub fn load(collection_dir: &Path, overrides: &ConfigOverrides) -> anyhow::Result<Config> {
+        let mut config = Config::default();
+
+        if let Some(path) = global_config_path() {
+            config.merge_file(&path, Source::Global(path.clone()))?;
+        }
+
+        if let Some(path) = collection_config_path(collection_dir) {
+            config.merge_file(&path, Source::Collection(path.clone()))?;
+        }
+
+        config.apply_overrides(overrides);
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Check that the settings are usable, failing early rather than after OCR
+    pub fn validate(&self) -> anyhow::Result<()> {
+        validate_sliding_window(self.chunk_size.value, self.chunk_stride.value)?;
+        self.embedding_model()?;
+        self.multilingual_model()?;
+
+        if !(0..=13).contains(&self.ocr_psm.value) {
+            anyhow::bail!(
+                "ocr.psm must be between 0 and 13, got {}",
+                self.ocr_psm.value
+            );
+        }
+        if !(0..=3).contains(&self.ocr_oem.value) {
+            anyhow::bail!(
+                "ocr.oem must be between 0 and 3, got {}",
+                self.ocr_oem.value
+            );
+        }
+        self.ocr_config_variables()?;
+        if !(0.0..=100.0).contains(&self.ocr_min_confidence.value) {
+            anyhow::bail!(
+                "ocr.min_confidence must be between 0 and 100, got {}",
+                self.ocr_min_confidence.value
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Values of the settings in `INDEX_SETTINGS`, encoded as TOML values
+    pub fn index_settings(&self) -> anyhow::Result<Vec<(String, String)>> {
+        self.settings()
+            .into_iter()
+            .filter(|s| INDEX_SETTINGS.contains(&s.key()))
+            .map(|s| Ok((s.key().to_string(), s.to_toml()?.to_string())))
+            .collect()
+    }
+
+    /// Replace index settings with the values recorded in a collection's database.
+    ///
+    /// A warning is logged for values explicitly configured to something else, as
+    /// they only take effect once the collection is rebuilt.
+    pub fn merge_index_settings(&mut self, stored: &[(String, String)]) -> anyhow::Result<()> {
+        for (key, value) in stored {
+            let value = stored_value(value)?;
+            let Some(setting) = self.settings_mut().into_iter().find(|s| s.key() == key) else {
+                log::warn!("Unknown setting {} recorded in index", key);
+                continue;
+            };
+
+            if setting.source() != &Source::Default && setting.to_toml()? != value {
+                log::warn!(
+                    "Ignoring {} = {} from {}, the collection was indexed with {} = {}. Run rechunk to change it.",
+                    key,
+                    setting.to_toml()?,
+                    setting.source(),
+                    key,
+                    value
+                );
+            }
+
+            setting.merge(value, &Source::Index)?;
+        }
+
+        Ok(())
+    }
+
+    /// Replace index settings with the values recorded in a collection's database, except those
+    /// given on the command line, e.g. to rebuild the collection with only them changed
+    pub fn merge_index_settings_below_command_line(
+        &mut self,
+        stored: &[(String, String)],
+    ) -> anyhow::Result<()> {
+        for (key, value) in stored {
+            let value = stored_value(value)?;
+            let Some(setting) = self.settings_mut().into_iter().find(|s| s.key() == key) else {
+                log::warn!("Unknown setting {} recorded in index", key);
+                continue;
+            };
+
+            if setting.source() != &Source::CommandLine {
+                setting.merge(value, &Source::Index)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Merge the values of a TOML config file, if it exists
+    fn merge_file(&mut self, path: &Path, source: Source) -> anyhow::Result<()> {
+        if !path.exists() {
+            return Ok(());
+        }
+
+        log::debug!("Loading config file {}", path.display());
+
+        let contents = std::fs::read_to_string(path)?;
+        let table: toml::Table = contents
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Unable to parse {}: {}", path.display(), e))?;
+
+        for (key, value) in flatten_table(table, None) {
+            match self.settings_mut().into_iter().find(|s| s.key() == key) {
+                Some(setting) => setting.merge(value, &source)?,
+                None => log::warn!("Unknown config key {} in {}", key, path.display()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_overrides(&mut self, overrides: &ConfigOverrides) {
+        let source = Source::CommandLine;
+        self.chunk_size.set(overrides.chunk_size, source.clone());
+        self.chunk_stride
+            .set(overrides.chunk_stride, source.clone());
+        self.chunk_unit.set(overrides.chunk_unit, source.clone());
+        self.chunk_strategy
+            .set(overrides.chunk_strategy, source.clone());
+        self.sentence_overlap
+            .set(overrides.sentence_overlap, source.clone());
+        self.span_pages.set(
+            flag_value(overrides.span_pages, overrides.no_span_pages),
+            source.clone(),
+        );
+        self.embedding_model
+            .set(overrides.model.clone(), source.clone());
+        self.multilingual_model
+            .set(overrides.multilingual_model.clone(), source.clone());
+        self.embedding_batch_size
+            .set(overrides.batch_size, source.clone());
+        self.top_k.set(overrides.top_k, source.clone());
+        self.ocr_language
+            .set(overrides.ocr_lang.clone(), source.clone());
+        self.ocr_dpi.set(overrides.ocr_dpi, source.clone());
+        self.ocr_psm.set(overrides.ocr_psm, source.clone());
+        self.ocr_oem.set(overrides.ocr_oem, source.clone());
+        self.ocr_whitelist
+            .set(overrides.ocr_whitelist.clone(), source.clone());
+        self.ocr_variables.set(
+            (!overrides.ocr_variables.is_empty()).then(|| overrides.ocr_variables.clone()),
+            source.clone(),
+        );
+        self.ocr_config_file
+            .set(overrides.ocr_config_file.clone(), source.clone());
+        self.ocr_preprocess.set(
+            (!overrides.preprocess.is_empty()).then(|| overrides.preprocess.clone()),
+            source.clone(),
+        );
+        self.ocr_min_confidence
+            .set(overrides.min_confidence, source.clone());
+        self.ocr_cache
+            .set(overrides.no_ocr_cache.then_some(false), source.clone());
+        self.detect_language.set(
+            flag_value(overrides.detect_language, overrides.no_detect_language),
+            source.clone(),
+        );
+        self.render_width
+            .set(overrides.render_width, source.clone());
+        self.email_pdf_attachments.set(
+            flag_value(overrides.pdf_attachments, overrides.no_pdf_attachments),
+            source.clone(),
+        );
+        self.pdf_password_file
+            .set(overrides.password_file.clone(), source);
+    }
+
+    pub fn embedding_model(&self) -> anyhow::Result<EmbeddingModel> {
+        parse_embedding_model(&self.embedding_model.value)
+    }
+
+    /// Embedding model for pages in languages other than English, if one is configured
+    pub fn multilingual_model(&self) -> anyhow::Result<Option<EmbeddingModel>> {
+        let name = &self.multilingual_model.value;
+        if name.is_empty() {
+            return Ok(None);
+        }
+        parse_embedding_model(name).map(Some)
+    }
+
+    /// Tesseract config variables from the config file, the whitelist and `ocr.variables`,
+    /// later ones taking priority
+    pub fn ocr_config_variables(&self) -> anyhow::Result<BTreeMap<String, String>> {
+        let mut variables = BTreeMap::new();
+
+        let path = &self.ocr_config_file.value;
+        if !path.is_empty() {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("Unable to read OCR config file {}: {}", path, e))?;
+            for line in contents.lines().map(str::trim) {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let (name, value) = line.split_once(char::is_whitespace).ok_or_else(|| {
+                    anyhow::anyhow!("Invalid line in OCR config file {}: {}", path, line)
+                })?;
+                variables.insert(name.to_string(), value.trim().to_string());
+            }
+        }
+
+        if !self.ocr_whitelist.value.is_empty() {
+            variables.insert(
+                "tessedit_char_whitelist".to_string(),
+                self.ocr_whitelist.value.clone(),
+            );
+        }
+
+        for variable in &self.ocr_variables.value {
+            let (name, value) = variable
+                .split_once('=')
+                .filter(|(name, _)| !name.trim().is_empty())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Invalid OCR variable {}, expected name=value", variable)
+                })?;
+            variables.insert(name.trim().to_string(), value.trim().to_string());
+        }
+
+        Ok(variables)
+    }
+
+    /// Render the effective configuration as TOML, annotating every value with its source
+    pub fn to_annotated_toml(&self) -> anyhow::Result<String> {
+        let mut settings = self.settings();
+        // Group by section, keeping the declaration order within each
+        settings.sort_by_key(|s| s.key().split_once('.').unwrap().0);
+
+        let mut output = String::new();
+        let mut current_section = "";
+        for setting in settings {
+            let (section, name) = setting.key().split_once('.').unwrap();
+            if section != current_section {
+                if !output.is_empty() {
+                    output.push('\n');
+                }
+                output.push_str(&format!("[{}]\n", section));
+                current_section = section;
+            }
+            output.push_str(&format!(
+                "{} = {}  # {}\n",
+                name,
+                setting.to_toml()?,
+                setting.source()
+            ));
+        }
+
+        Ok(output)
+    }
+}
+
+/// Path to the global config file shared by all collections
+pub fn global_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(DATA_DIR_NAME).join(CONFIG_FILE_NAME))
+}
+
+/// Path to the config file of a single collection, or None when it is the global config file
+pub fn collection_config_path(collection_dir: &Path) -> Option<PathBuf> {
+    unless_global(
+        collection_dir.join(CONFIG_FILE_NAME),
+        global_config_path().as_deref(),
+    )
+}
+
+/// Returns the path of a collection config file unless it is the global one. On macOS the
+/// config and local data directories are the same, so the default collection, which lives in
+/// the data directory, would otherwise read the global config file twice.
+fn unless_global(path: PathBuf, global: Option<&Path>) -> Option<PathBuf> {
+    (global != Some(path.as_path())).then_some(path)
+}
+
+/// Parses a setting value recorded in a collection's database
+fn stored_value(value: &str) -> anyhow::Result<toml::Value> {
+    let value = format!("value = {}", value)
+        .parse::<toml::Table>()?
+        .remove("value")
+        .unwrap();
+    Ok(value)
+}
+
+/// Value of a setting that is switched on by one flag and off by another, if either was given
+fn flag_value(on: bool, off: bool) -> Option<bool> {
+    (on || off).then_some(on)
+}
+
+/// Flattens nested TOML tables into dotted keys, e.g. `[chunking] size = 1` becomes `chunking.size`
+fn flatten_table(table: toml::Table, prefix: Option<&str>) -> Vec<(String, toml::Value)> {
+    table
+        .into_iter()
+        .flat_map(|(key, value)| {
+            let key = match prefix {
+                Some(prefix) => format!("{}.{}", prefix, key),
+                None => key,
+            };
+
+            match value {
+                toml::Value::Table(table) => flatten_table(table, Some(&key)),
+                value => vec![(key, value)],
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk_text::ChunkStrategy;
+    use crate::config::{unless_global, Config, ConfigOverrides, Source};
+    use std::path::{Path, PathBuf};
+    use tempfile::tempdir;
+
+    fn write_config(dir: &Path, contents: &str) -> (PathBuf, Config) {
+        let path = dir.join("config.toml");
+        std::fs::write(&path, contents).unwrap();
+
+        let mut config = Config::default();
+        config
+            .merge_file(&path, Source::Collection(path.clone()))
+            .unwrap();
+        (path, config)
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = Config::default();
+        assert_eq!(config.chunk_size.value, 512);
+        assert_eq!(config.chunk_stride.value, 64);
+        assert_eq!(config.chunk_size.source, Source::Default);
+    }
+
+    #[test]
+    fn test_merge_file() {
+        let dir = tempdir().unwrap();
+        let (path, config) = write_config(
+            dir.path(),
+            "[chunking]\nsize = 256\n\n[search]\ntop_k = 3\n",
+        );
+        assert_eq!(config.chunk_size.value, 256);
+        assert_eq!(config.chunk_size.source, Source::Collection(path));
+        assert_eq!(config.top_k.value, 3);
+        assert_eq!(config.chunk_stride.source, Source::Default);
+    }
+
+    #[test]
+    fn test_overrides_take_priority() {
+        let dir = tempdir().unwrap();
+        let (_, mut config) = write_config(dir.path(), "[chunking]\nsize = 256\n");
+        config.apply_overrides(&ConfigOverrides {
+            chunk_size: Some(128),
+            ..Default::default()
+        });
+        assert_eq!(config.chunk_size.value, 128);
+        assert_eq!(config.chunk_size.source, Source::CommandLine);
+    }
+
+    #[test]
+    fn test_flags_switch_settings_off() {
+        let dir = tempdir().unwrap();
+        let (_, mut config) = write_config(dir.path(), "[chunking]\nspan_pages = true\n");
+        config.apply_overrides(&ConfigOverrides {
+            no_span_pages: true,
+            ..Default::default()
+        });
+        assert!(!config.span_pages.value);
+        assert_eq!(config.span_pages.source, Source::CommandLine);
+
+        config.apply_overrides(&ConfigOverrides {
+            span_pages: true,
+            ..Default::default()
+        });
+        assert!(config.span_pages.value);
+    }
+
+    #[test]
+    fn test_annotated_toml() {
+        let output = Config::default().to_annotated_toml().unwrap();
+        assert!(output.starts_with("[chunking]\nsize = 512  # default\n"));
+        assert!(output.contains("model = \"BGEBaseENV15\"  # default\n"));
+    }
+
+    #[test]
+    fn test_index_settings_round_trip() {
+        let mut config = Config::default();
+        config.apply_overrides(&ConfigOverrides {
+            chunk_size: Some(128),
+            ..Default::default()
+        });
+        let stored = config.index_settings().unwrap();
+
+        let mut config = Config::default();
+        config.apply_overrides(&ConfigOverrides {
+            chunk_size: Some(256),
+            ..Default::default()
+        });
+        config.merge_index_settings(&stored).unwrap();
+        assert_eq!(config.chunk_size.value, 128);
+        assert_eq!(config.chunk_size.source, Source::Index);
+        assert_eq!(config.embedding_model.value, "BGEBaseENV15");
+    }
+
+    #[test]
+    fn test_command_line_over_index_settings() {
+        let dir = tempdir().unwrap();
+        let (_, mut config) = write_config(dir.path(), "[chunking]\nstrategy = \"sentence\"\n");
+        config.apply_overrides(&ConfigOverrides {
+            chunk_stride: Some(32),
+            ..Default::default()
+        });
+
+        let mut stored = Config::default();
+        stored.chunk_size.value = 128;
+        stored.chunk_stride.value = 16;
+        config
+            .merge_index_settings_below_command_line(&stored.index_settings().unwrap())
+            .unwrap();
+
+        assert_eq!(config.chunk_size.value, 128);
+        assert_eq!(config.chunk_size.source, Source::Index);
+        assert_eq!(config.chunk_stride.value, 32);
+        assert_eq!(config.chunk_stride.source, Source::CommandLine);
+        // Config files don't change the settings a collection was indexed with
+        assert_eq!(config.chunk_strategy.value, ChunkStrategy::SlidingWindow);
+    }
+
+    #[test]
+    fn test_ocr_config_variables() {
+        let dir = tempdir().unwrap();
+        let (path, mut config) = write_config(
+            dir.path(),
+            "[ocr]\nwhitelist = \"0123456789\"\nvariables = [\"load_system_dawg=0\"]\n",
+        );
+        let parameters = path.with_file_name("parameters.txt");
+        std::fs::write(
+            &parameters,
+            "# Comment\nload_system_dawg\t1\ntextord_heavy_nr   1\n",
+        )
+        .unwrap();
+        config.ocr_config_file.value = parameters.to_string_lossy().into_owned();
+
+        let variables = config.ocr_config_variables().unwrap();
+        assert_eq!(variables["tessedit_char_whitelist"], "0123456789");
+        assert_eq!(variables["load_system_dawg"], "0");
+        assert_eq!(variables["textord_heavy_nr"], "1");
+
+        config.ocr_variables.value = vec!["no_value".to_string()];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = Config::default();
+        config.chunk_stride.value = 0;
+        assert!(config.validate().is_err());
+        config.chunk_stride.value = config.chunk_size.value + 1;
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.ocr_psm.value = 14;
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.ocr_min_confidence.value = 101.0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_unless_global() {
+        let global = Path::new("/config/semantic_search_cli/config.toml");
+        let collection = PathBuf::from("/data/semantic_search_cli/config.toml");
+
+        assert_eq!(
+            unless_global(collection.clone(), Some(global)),
+            Some(collection.clone())
+        );
+        assert_eq!(unless_global(collection.clone(), None), Some(collection));
+        assert_eq!(unless_global(global.to_path_buf(), Some(global)), None);
+    }
+}
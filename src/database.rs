@@ -1,12 +1,140 @@
-use rusqlite::{params, Connection, Transaction};
-use std::path::PathBuf;
+use crate::chunk_sections::Heading;
+use crate::document_source::{SourcePage, MEMBER_SEPARATOR};
+use crate::image_to_text::OcrWord;
+use crate::page_regions::{text_rects, PageRegion};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Separator placed between the text of consecutive pages when chunks span pages
+pub const PAGE_SEPARATOR: &str = "\n\n";
 
 pub struct Database {
     pub conn: Connection,
 }
 
+/// Location of a chunk, which starts in one page and ends in the same or a later page.
+/// The indices are byte offsets into the UTF-8 text of the start and end page respectively,
+/// as returned by the functions in `chunk_text`.
+pub struct ChunkLocation {
+    pub start_page_id: i64,
+    pub start: usize,
+    pub end_page_id: i64,
+    pub end: usize,
+    /// Path of the section headings the chunk belongs to
+    pub heading: Option<String>,
+    /// Annotation of the start page the chunk is the text of, in which case the indices are
+    /// byte offsets into the annotation text
+    pub annotation_id: Option<i64>,
+}
+
+/// Descriptive metadata of a document, for formats that record it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// Date the document was written or sent, as an ISO 8601 date or date and time
+    pub date: Option<String>,
+    pub subject: Option<String>,
+    /// Keywords as recorded by the document, usually separated by commas or semicolons
+    pub keywords: Option<String>,
+    /// Programming language of source code files, e.g. `rust`
+    pub code_language: Option<String>,
+}
+
+/// Restricts a search to the chunks of documents with matching metadata
+#[derive(Clone, Debug, Default)]
+pub struct SearchFilter {
+    /// Text the author must contain, ignoring ASCII case
+    pub author: Option<String>,
+    /// Earliest date, inclusive. A prefix such as `2024-03` matches the whole month.
+    pub after: Option<String>,
+    /// Latest date, exclusive. A prefix such as `2024-03` excludes the whole month.
+    pub before: Option<String>,
+    /// Programming languages of source code files, matching any of them
+    pub code_languages: Vec<String>,
+    /// Text the title must contain, ignoring ASCII case
+    pub title: Option<String>,
+    /// Text the subject must contain, ignoring ASCII case
+    pub subject: Option<String>,
+    /// Text the keywords must contain, ignoring ASCII case
+    pub keywords: Option<String>,
+}
+
+impl SearchFilter {
+    pub fn is_empty(&self) -> bool {
+        self.author.is_none()
+            && self.after.is_none()
+            && self.before.is_none()
+            && self.code_languages.is_empty()
+            && self.title.is_none()
+            && self.subject.is_none()
+            && self.keywords.is_none()
+    }
+}
+
+/// Document id, page id, text and language of a stored page
+pub type StoredPage = (i64, i64, String, Option<String>);
+
+/// A page read with OCR along with the document it belongs to
+pub struct OcrPage {
+    pub document_id: i64,
+    pub page_id: i64,
+    pub path: String,
+    pub page_no: usize,
+    /// Mean confidence of the words of the page, from 0 to 100
+    pub confidence: f32,
+    pub words: Vec<OcrWord>,
+}
+
+/// A comment or other annotation a user added to a page of a document
+#[derive(Clone, Debug, PartialEq)]
+pub struct PageAnnotation {
+    pub page_no: usize,
+    /// Kind of annotation, e.g. `text` for a note or `highlight`
+    pub kind: String,
+    pub author: Option<String>,
+    pub text: String,
+}
+
+/// A page that could not be read, along with the document it belongs to
+pub struct FailedPage {
+    pub document_id: i64,
+    pub page_id: i64,
+    pub path: String,
+    pub page_no: usize,
+    pub error: String,
+}
+
+/// Annotation id, page id and text of a stored annotation
+pub type StoredAnnotation = (i64, i64, String);
+
+/// A chunk along with the document it belongs to
+pub struct DocumentChunk {
+    pub path: String,
+    pub metadata: DocumentMetadata,
+    pub page_no: usize,
+    pub end_page_no: usize,
+    /// Title of the page the chunk starts in, e.g. the chapter of a book
+    pub page_title: Option<String>,
+    /// Line of the source file the chunk starts at, for text based documents
+    pub line_no: Option<usize>,
+    /// Line of the source file the chunk ends at, for text based documents
+    pub end_line_no: Option<usize>,
+    /// Language detected in the page the chunk starts in
+    pub language: Option<String>,
+    /// OCR confidence of the page the chunk starts in, from 0 to 100
+    pub confidence: Option<f32>,
+    /// Rectangles covered by the chunk on pages read with OCR
+    pub regions: Vec<PageRegion>,
+    pub heading: Option<String>,
+    /// Annotation the chunk is the text of, for chunks of annotations rather than pages
+    pub annotation: Option<PageAnnotation>,
+    pub text: String,
+}
+
 impl Database {
-    pub fn new(path: &PathBuf) -> anyhow::Result<Database> {
+    pub fn new(path: &Path) -> anyhow::Result<Database> {
         log::debug!("Initializing database");
 
         let conn = Connection::open(path).map_err(|e| anyhow::anyhow!(e))?;
@@ -21,10 +149,24 @@ impl Database {
             r#"
             CREATE TABLE IF NOT EXISTS documents (
                 id INTEGER PRIMARY KEY,
-                path TEXT NOT NULL
+                path TEXT NOT NULL,
+                title TEXT,
+                author TEXT,
+                date TEXT,
+                code_language TEXT,
+                content_hash TEXT,
+                subject TEXT,
+                keywords TEXT
             )"#,
             [],
         )?;
+        self.add_column_if_missing("documents", "title", "TEXT")?;
+        self.add_column_if_missing("documents", "author", "TEXT")?;
+        self.add_column_if_missing("documents", "date", "TEXT")?;
+        self.add_column_if_missing("documents", "code_language", "TEXT")?;
+        self.add_column_if_missing("documents", "subject", "TEXT")?;
+        self.add_column_if_missing("documents", "keywords", "TEXT")?;
+        self.add_column_if_missing("documents", "content_hash", "TEXT")?;
 
         self.conn.execute(
             r#"
@@ -41,13 +183,31 @@ impl Database {
                 document_id INTEGER NOT NULL,
                 page_no INTEGER NOT NULL,
                 text TEXT NOT NULL,
+                line_no INTEGER,
+                title TEXT,
+                language TEXT,
+                confidence REAL,
+                words TEXT,
+                image_width INTEGER,
+                image_height INTEGER,
+                error TEXT,
                 FOREIGN KEY (document_id) REFERENCES documents(id)
             )"#,
             [],
         )?;
+        self.add_column_if_missing("pages", "line_no", "INTEGER")?;
+        self.add_column_if_missing("pages", "title", "TEXT")?;
+        self.add_column_if_missing("pages", "language", "TEXT")?;
+        self.add_column_if_missing("pages", "confidence", "REAL")?;
+        self.add_column_if_missing("pages", "words", "TEXT")?;
+        self.add_column_if_missing("pages", "image_width", "INTEGER")?;
+        self.add_column_if_missing("pages", "image_height", "INTEGER")?;
+        self.add_column_if_missing("pages", "error", "TEXT")?;
 
         // Create the chunks table that as a foreign key to the documents table
         // And also stores the chunk indices
+        // The chunk ends in the page end_page_id, which is NULL for chunks indexed
+        // before chunks could span pages
         self.conn.execute(
             r#"
             CREATE TABLE IF NOT EXISTS chunks (
@@ -55,37 +215,279 @@ impl Database {
                 page_id INTEGER NOT NULL,
                 chunk_index_start INTEGER NOT NULL,
                 chunk_index_end INTEGER NOT NULL,
+                end_page_id INTEGER,
+                FOREIGN KEY (page_id) REFERENCES pages(id),
+                FOREIGN KEY (end_page_id) REFERENCES pages(id)
+            )"#,
+            [],
+        )?;
+        self.add_column_if_missing("chunks", "end_page_id", "INTEGER REFERENCES pages(id)")?;
+        self.add_column_if_missing("chunks", "heading", "TEXT")?;
+        self.add_column_if_missing(
+            "chunks",
+            "annotation_id",
+            "INTEGER REFERENCES annotations(id)",
+        )?;
+
+        // Create the annotations table that stores the comments users added to pages
+        self.conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS annotations (
+                id INTEGER PRIMARY KEY,
+                document_id INTEGER NOT NULL,
+                page_id INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                author TEXT,
+                text TEXT NOT NULL,
+                FOREIGN KEY (document_id) REFERENCES documents(id),
                 FOREIGN KEY (page_id) REFERENCES pages(id)
             )"#,
             [],
         )?;
 
+        // Create the headings table that stores the section structure of documents
+        self.conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS headings (
+                id INTEGER PRIMARY KEY,
+                document_id INTEGER NOT NULL,
+                page_no INTEGER NOT NULL,
+                level INTEGER NOT NULL,
+                title TEXT NOT NULL,
+                FOREIGN KEY (document_id) REFERENCES documents(id)
+            )"#,
+            [],
+        )?;
+
+        // Create the settings table that records how the collection was indexed
+        self.conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            )"#,
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    /// Function to add a column to a table created by an older version, if it is missing
+    fn add_column_if_missing(
+        &self,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> anyhow::Result<()> {
+        let exists: u8 = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+            params![table, column],
+            |row| row.get(0),
+        )?;
+
+        if exists == 0 {
+            log::debug!("Adding column {} to table {}", column, table);
+            self.conn.execute(
+                &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+                [],
+            )?;
+        }
+
         Ok(())
     }
 
+    /// Function to get the settings recorded for the collection as key value pairs
+    pub fn get_settings(&self) -> anyhow::Result<Vec<(String, String)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT key, value FROM settings ORDER BY key")?;
+        let settings = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<(String, String)>, rusqlite::Error>>()?;
+
+        Ok(settings)
+    }
+
+    /// Function to check if any document has been indexed
+    pub fn has_documents(&self) -> anyhow::Result<bool> {
+        let exists: u8 =
+            self.conn
+                .query_row("SELECT EXISTS(SELECT 1 FROM documents)", [], |row| {
+                    row.get(0)
+                })?;
+
+        Ok(exists == 1)
+    }
+
     /// Function to get the chunk text from the database given a chunk id
     /// Joins the chunks table with the documents table
-    pub fn get_document(&self, chunk_id: u64) -> rusqlite::Result<(String, usize, String)> {
+    pub fn get_document(&self, chunk_id: u64) -> rusqlite::Result<DocumentChunk> {
         log::debug!("Getting document text for chunk id {}", chunk_id);
         let mut stmt = self.conn.prepare(
             r#"
             SELECT d.path,
+                   d.title,
+                   d.author,
+                   d.date,
+                   d.code_language,
                    p.page_no,
-                   substr(p.text, c.chunk_index_start, c.chunk_index_end - c.chunk_index_start) as chunk_text
+                   e.page_no,
+                   p.title,
+                   p.line_no,
+                   c.heading,
+                   p.document_id,
+                   c.chunk_index_start,
+                   c.chunk_index_end,
+                   p.language,
+                   p.confidence,
+                   d.subject,
+                   d.keywords,
+                   a.kind,
+                   a.author,
+                   a.text
             FROM pages p
                      INNER JOIN chunks c
                                 ON p.id = c.page_id
+                     INNER JOIN pages e
+                                ON e.id = COALESCE(c.end_page_id, c.page_id)
                      INNER JOIN documents d
                                 ON d.id = p.document_id
+                     LEFT JOIN annotations a
+                               ON a.id = c.annotation_id
             WHERE c.id = ?1
         "#,
         )?;
-        stmt.query_row(params![chunk_id], |row| {
-            let path: String = row.get_unwrap(0);
-            let page_no: usize = row.get_unwrap(1);
-            let text: String = row.get_unwrap(2);
-            Ok((path, page_no, text))
-        })
+        // The text is filled in below, and the line number of the page moved to the chunk
+        let (mut chunk, document_id, start, end) = stmt.query_row(params![chunk_id], |row| {
+            let mut chunk = DocumentChunk {
+                path: row.get_unwrap(0),
+                metadata: DocumentMetadata {
+                    title: row.get_unwrap(1),
+                    author: row.get_unwrap(2),
+                    date: row.get_unwrap(3),
+                    subject: row.get_unwrap(15),
+                    keywords: row.get_unwrap(16),
+                    code_language: row.get_unwrap(4),
+                },
+                page_no: row.get_unwrap(5),
+                end_page_no: row.get_unwrap(6),
+                page_title: row.get_unwrap(7),
+                line_no: row.get_unwrap(8),
+                end_line_no: None,
+                language: row.get_unwrap(13),
+                confidence: row.get_unwrap(14),
+                regions: Vec::new(),
+                heading: row.get_unwrap(9),
+                annotation: None,
+                text: String::new(),
+            };
+            let annotation_text: Option<String> = row.get_unwrap(19);
+            chunk.annotation = annotation_text.map(|text| PageAnnotation {
+                page_no: chunk.page_no,
+                kind: row.get_unwrap(17),
+                author: row.get_unwrap(18),
+                text,
+            });
+            let document_id: i64 = row.get_unwrap(10);
+            let start: usize = row.get_unwrap(11);
+            let end: usize = row.get_unwrap(12);
+            Ok((chunk, document_id, start, end))
+        })?;
+
+        // Chunks of annotations are sliced out of the annotation text instead of the page
+        if let Some(annotation) = &chunk.annotation {
+            chunk.text = slice_chunk(&annotation.text, start, end).to_string();
+            chunk.line_no = None;
+            return Ok(chunk);
+        }
+
+        // The chunk indices are byte offsets into the UTF-8 page text, so the text is
+        // sliced here rather than with SQLite's character based substr
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT page_no, text, line_no, words, image_width, image_height
+            FROM pages
+            WHERE document_id = ?1 AND page_no BETWEEN ?2 AND ?3
+            ORDER BY page_no
+        "#,
+        )?;
+        let pages = stmt
+            .query_map(
+                params![document_id, chunk.page_no, chunk.end_page_no],
+                |row| {
+                    let image_width: Option<u32> = row.get(4)?;
+                    let image_height: Option<u32> = row.get(5)?;
+                    Ok(ChunkPage {
+                        page_no: row.get(0)?,
+                        text: row.get(1)?,
+                        line_no: row.get(2)?,
+                        words: row.get(3)?,
+                        image_size: image_width.zip(image_height),
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<ChunkPage>>>()?;
+
+        // Count the lines of the first page before the chunk, and of the last page up to its end
+        if let (Some(line_no), Some(first_page)) = (chunk.line_no, pages.first()) {
+            chunk.line_no = Some(
+                line_no
+                    + slice_chunk(&first_page.text, 0, start)
+                        .matches('\n')
+                        .count(),
+            );
+        }
+        if let Some(ChunkPage {
+            text: last_page,
+            line_no: Some(line_no),
+            ..
+        }) = pages.last()
+        {
+            let before_end = slice_chunk(last_page, 0, end).trim_end_matches('\n');
+            chunk.end_line_no = Some(line_no + before_end.matches('\n').count());
+        }
+
+        // The chunk covers the end of the first page, the pages in between and the start of
+        // the last page
+        let (first_page_no, last_page_no) = (chunk.page_no, chunk.end_page_no);
+        let range = |page: &ChunkPage| {
+            let from = if page.page_no == first_page_no {
+                start
+            } else {
+                0
+            };
+            let to = if page.page_no == last_page_no {
+                end
+            } else {
+                page.text.len()
+            };
+            (from, to)
+        };
+        chunk.text = pages
+            .iter()
+            .map(|page| {
+                let (from, to) = range(page);
+                slice_chunk(&page.text, from, to)
+            })
+            .collect::<Vec<&str>>()
+            .join(PAGE_SEPARATOR);
+
+        // Pages read with OCR locate the chunk by the boxes of its words
+        for page in &pages {
+            let Some(image_size) = page.image_size else {
+                continue;
+            };
+            let (from, to) = range(page);
+            let words = decode_words(page.words.as_deref());
+            for rect in text_rects(&page.text, &words, image_size, from, to) {
+                chunk.regions.push(PageRegion {
+                    page_no: page.page_no,
+                    rect,
+                });
+            }
+        }
+
+        Ok(chunk)
     }
 
     /// Function to check if a document with the given path exists in the database
@@ -104,15 +506,214 @@ impl Database {
 
         Ok(exists == 1)
     }
+
+    /// Function to get the id and content hash of the document with the given path
+    /// Only members of archives record a content hash
+    pub fn find_document(&self, path: &str) -> anyhow::Result<Option<(i64, Option<String>)>> {
+        let document = self
+            .conn
+            .query_row(
+                "SELECT id, content_hash FROM documents WHERE path = ?1",
+                params![path],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        Ok(document)
+    }
+
+    /// Function to get the id and path of the documents within a container file, such as
+    /// the members of an archive
+    pub fn member_documents(&self, container: &str) -> anyhow::Result<Vec<(i64, String)>> {
+        let prefix = format!("{}{}", container, MEMBER_SEPARATOR);
+        let mut stmt = self.conn.prepare(
+            "SELECT id, path FROM documents WHERE substr(path, 1, length(?1)) = ?1 ORDER BY path",
+        )?;
+        let documents = stmt
+            .query_map(params![prefix], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(i64, String)>>>()?;
+
+        Ok(documents)
+    }
+
+    /// Function to get the pages read with OCR whose confidence is below the given minimum
+    /// Ordered from the lowest confidence
+    pub fn low_confidence_pages(&self, min_confidence: f32) -> anyhow::Result<Vec<OcrPage>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT p.document_id, p.id, d.path, p.page_no, p.confidence, p.words
+            FROM pages p
+                     INNER JOIN documents d
+                                ON d.id = p.document_id
+            WHERE p.confidence < ?1
+            ORDER BY p.confidence, d.path, p.page_no
+        "#,
+        )?;
+        let pages = stmt
+            .query_map(params![min_confidence], |row| {
+                Ok(OcrPage {
+                    document_id: row.get(0)?,
+                    page_id: row.get(1)?,
+                    path: row.get(2)?,
+                    page_no: row.get(3)?,
+                    confidence: row.get(4)?,
+                    words: decode_words(row.get::<_, Option<String>>(5)?.as_deref()),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<OcrPage>>>()?;
+
+        Ok(pages)
+    }
+
+    /// Function to get the pages that could not be read along with their error
+    /// Ordered by document and page number
+    pub fn failed_pages(&self) -> anyhow::Result<Vec<FailedPage>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT p.document_id, p.id, d.path, p.page_no, p.error
+            FROM pages p
+                     INNER JOIN documents d
+                                ON d.id = p.document_id
+            WHERE p.error IS NOT NULL
+            ORDER BY d.path, p.page_no
+        "#,
+        )?;
+        let pages = stmt
+            .query_map([], |row| {
+                Ok(FailedPage {
+                    document_id: row.get(0)?,
+                    page_id: row.get(1)?,
+                    path: row.get(2)?,
+                    page_no: row.get(3)?,
+                    error: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<FailedPage>>>()?;
+
+        Ok(pages)
+    }
+
+    /// Function to get the ids of the chunks of documents matching a search filter
+    /// Documents without the metadata a filter is on don't match it
+    pub fn filtered_chunk_ids(&self, filter: &SearchFilter) -> anyhow::Result<HashSet<u64>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT c.id
+            FROM chunks c
+                     INNER JOIN pages p
+                                ON p.id = c.page_id
+                     INNER JOIN documents d
+                                ON d.id = p.document_id
+            WHERE (?1 IS NULL OR instr(lower(d.author), lower(?1)) > 0)
+              AND (?2 IS NULL OR d.date >= ?2)
+              AND (?3 IS NULL OR d.date < ?3)
+              AND (?4 IS NULL OR instr(?4, ',' || d.code_language || ',') > 0)
+              AND (?5 IS NULL OR instr(lower(d.title), lower(?5)) > 0)
+              AND (?6 IS NULL OR instr(lower(d.subject), lower(?6)) > 0)
+              AND (?7 IS NULL OR instr(lower(d.keywords), lower(?7)) > 0)
+        "#,
+        )?;
+        // Languages are matched as a comma separated list, e.g. ",rust,python,"
+        let code_languages = (!filter.code_languages.is_empty())
+            .then(|| format!(",{},", filter.code_languages.join(",")));
+        let ids = stmt
+            .query_map(
+                params![
+                    filter.author,
+                    filter.after,
+                    filter.before,
+                    code_languages,
+                    filter.title,
+                    filter.subject,
+                    filter.keywords
+                ],
+                |row| row.get(0),
+            )?
+            .collect::<rusqlite::Result<HashSet<u64>>>()?;
+
+        Ok(ids)
+    }
+}
+
+/// A page covered by a chunk, as read to fill in the text and location of the chunk
+struct ChunkPage {
+    page_no: usize,
+    text: String,
+    line_no: Option<usize>,
+    words: Option<String>,
+    image_size: Option<(u32, u32)>,
+}
+
+/// Function to slice a chunk out of the text of a page given byte offsets
+/// Offsets that are out of range or not on a character boundary yield an empty string
+fn slice_chunk(text: &str, start: usize, end: usize) -> &str {
+    text.get(start..end.min(text.len())).unwrap_or_else(|| {
+        log::warn!("Invalid chunk indices {}..{}", start, end);
+        ""
+    })
+}
+
+/// Function to encode the words of a page as a line per word of its bounding box,
+/// confidence and text separated by tabs
+/// Returns None for pages without words
+pub fn encode_words(words: &[OcrWord]) -> Option<String> {
+    (!words.is_empty()).then(|| {
+        words
+            .iter()
+            .map(|word| {
+                format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    word.left, word.top, word.width, word.height, word.confidence, word.text
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    })
 }
 
-/// Function to insert a document into the database
+/// Function to decode the words of a page encoded by `encode_words`
+/// Lines that can't be decoded are skipped
+pub fn decode_words(encoded: Option<&str>) -> Vec<OcrWord> {
+    encoded
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(6, '\t');
+            Some(OcrWord {
+                left: fields.next()?.parse().ok()?,
+                top: fields.next()?.parse().ok()?,
+                width: fields.next()?.parse().ok()?,
+                height: fields.next()?.parse().ok()?,
+                confidence: fields.next()?.parse().ok()?,
+                text: fields.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Function to insert a document into the database along with its metadata and content hash
 /// And returns the document id
-pub fn insert_document(tx: &Transaction, path: &str) -> anyhow::Result<i64> {
+pub fn insert_document(
+    tx: &Transaction,
+    path: &str,
+    metadata: &DocumentMetadata,
+    content_hash: Option<&str>,
+) -> anyhow::Result<i64> {
     log::debug!("Inserting document into database");
 
-    let mut stmt = tx.prepare("INSERT INTO documents (path) VALUES (?1)")?;
-    let id = stmt.insert(params![path])?;
+    let mut stmt = tx.prepare(
+        "INSERT INTO documents (path, title, author, date, code_language, content_hash, subject, keywords) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )?;
+    let id = stmt.insert(params![
+        path,
+        metadata.title,
+        metadata.author,
+        metadata.date,
+        metadata.code_language,
+        content_hash,
+        metadata.subject,
+        metadata.keywords
+    ])?;
     Ok(id)
 }
 
@@ -122,41 +723,719 @@ pub fn insert_page(
     tx: &Transaction,
     document_id: i64,
     page_no: usize,
-    text: &str,
+    page: &SourcePage,
 ) -> anyhow::Result<i64> {
     log::debug!("Inserting page into database");
 
-    let mut stmt =
-        tx.prepare("INSERT INTO pages (document_id, page_no, text) VALUES (?1, ?2, ?3)")?;
-    let id = stmt.insert(params![document_id, page_no, text])?;
+    let mut stmt = tx.prepare(
+        "INSERT INTO pages (document_id, page_no, text, line_no, title, language, confidence, words, image_width, image_height) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+    )?;
+    let id = stmt.insert(params![
+        document_id,
+        page_no,
+        page.text,
+        page.line_no,
+        page.title,
+        page.language,
+        page.confidence,
+        encode_words(&page.words),
+        page.image_size.map(|size| size.0),
+        page.image_size.map(|size| size.1)
+    ])?;
+    Ok(id)
+}
+
+/// Function to insert a page that could not be read, without text, along with its error
+/// And returns the page id
+pub fn insert_failed_page(
+    tx: &Transaction,
+    document_id: i64,
+    page_no: usize,
+    error: &str,
+) -> anyhow::Result<i64> {
+    log::debug!("Inserting failed page into database");
+
+    let mut stmt = tx
+        .prepare("INSERT INTO pages (document_id, page_no, text, error) VALUES (?1, ?2, '', ?3)")?;
+    let id = stmt.insert(params![document_id, page_no, error])?;
     Ok(id)
 }
 
+/// Function to replace the error of a page that still could not be read
+pub fn update_page_error(tx: &Transaction, page_id: i64, error: &str) -> anyhow::Result<()> {
+    tx.execute(
+        "UPDATE pages SET error = ?2 WHERE id = ?1",
+        params![page_id, error],
+    )?;
+    Ok(())
+}
+
+/// Function to replace the text, language, OCR confidence and words of a page read again,
+/// e.g. with OCR, clearing the error of a page that could not be read before
+pub fn update_page_ocr(tx: &Transaction, page_id: i64, page: &SourcePage) -> anyhow::Result<()> {
+    log::debug!("Updating page {} in database", page_id);
+
+    tx.execute(
+        "UPDATE pages SET text = ?2, language = ?3, confidence = ?4, words = ?5, image_width = ?6, image_height = ?7, error = NULL WHERE id = ?1",
+        params![
+            page_id,
+            page.text,
+            page.language,
+            page.confidence,
+            encode_words(&page.words),
+            page.image_size.map(|size| size.0),
+            page.image_size.map(|size| size.1)
+        ],
+    )?;
+    Ok(())
+}
+
 /// Function to insert multiple chunks into the database
 /// And return the chunk ids in the same order as the chunks
-pub fn insert_chunks(
-    tx: &Transaction,
-    page_id: i64,
-    chunks: &[(usize, usize)],
-) -> anyhow::Result<Vec<i64>> {
-    log::debug!(
-        "Inserting {} chunks for page id {} into database",
-        chunks.len(),
-        page_id
-    );
+pub fn insert_chunks(tx: &Transaction, chunks: &[&ChunkLocation]) -> anyhow::Result<Vec<i64>> {
+    log::debug!("Inserting {} chunks into database", chunks.len());
 
     let ids = {
         let mut stmt = tx.prepare(
-            "INSERT INTO chunks (page_id, chunk_index_start, chunk_index_end) VALUES (?1, ?2, ?3)",
+            "INSERT INTO chunks (page_id, chunk_index_start, end_page_id, chunk_index_end, heading, annotation_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
         )?;
 
         chunks
             .iter()
-            .map(|(chunk_index_start, chunk_index_end)| {
-                stmt.insert(params![page_id, *chunk_index_start, *chunk_index_end])
+            .map(|chunk| {
+                stmt.insert(params![
+                    chunk.start_page_id,
+                    chunk.start,
+                    chunk.end_page_id,
+                    chunk.end,
+                    chunk.heading,
+                    chunk.annotation_id
+                ])
             })
             .collect::<Result<Vec<i64>, rusqlite::Error>>()?
     };
 
     Ok(ids)
 }
+
+/// Function to insert or replace the settings recorded for the collection
+pub fn insert_settings(tx: &Transaction, settings: &[(String, String)]) -> anyhow::Result<()> {
+    log::debug!("Recording {} settings in database", settings.len());
+
+    let mut stmt = tx.prepare("INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)")?;
+    for (key, value) in settings {
+        stmt.execute(params![key, value])?;
+    }
+
+    Ok(())
+}
+
+/// Function to get the document id, page id, text and language of every page in the database
+/// Ordered by document and page number
+pub fn get_pages(tx: &Transaction) -> anyhow::Result<Vec<StoredPage>> {
+    let mut stmt = tx.prepare(
+        "SELECT document_id, id, text, language FROM pages ORDER BY document_id, page_no",
+    )?;
+    let pages = stmt
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .collect::<Result<Vec<StoredPage>, rusqlite::Error>>()?;
+
+    Ok(pages)
+}
+
+/// Function to get the pages of a document, as returned by `get_pages`
+pub fn get_document_pages(tx: &Transaction, document_id: i64) -> anyhow::Result<Vec<StoredPage>> {
+    let mut stmt = tx.prepare(
+        "SELECT document_id, id, text, language FROM pages WHERE document_id = ?1 ORDER BY page_no",
+    )?;
+    let pages = stmt
+        .query_map(params![document_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .collect::<Result<Vec<StoredPage>, rusqlite::Error>>()?;
+
+    Ok(pages)
+}
+
+/// Function to insert the section headings of a document into the database
+pub fn insert_headings(
+    tx: &Transaction,
+    document_id: i64,
+    headings: &[Heading],
+) -> anyhow::Result<()> {
+    log::debug!("Inserting {} headings into database", headings.len());
+
+    let mut stmt = tx.prepare(
+        "INSERT INTO headings (document_id, page_no, level, title) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    for heading in headings {
+        stmt.execute(params![
+            document_id,
+            heading.page_no,
+            heading.level,
+            heading.title
+        ])?;
+    }
+
+    Ok(())
+}
+
+/// Function to get the section headings of a document in document order
+pub fn get_headings(tx: &Transaction, document_id: i64) -> anyhow::Result<Vec<Heading>> {
+    let mut stmt = tx.prepare(
+        "SELECT page_no, level, title FROM headings WHERE document_id = ?1 ORDER BY page_no, id",
+    )?;
+    let headings = stmt
+        .query_map(params![document_id], |row| {
+            Ok(Heading {
+                page_no: row.get(0)?,
+                level: row.get(1)?,
+                title: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<Heading>, rusqlite::Error>>()?;
+
+    Ok(headings)
+}
+
+/// Function to insert an annotation of a page into the database
+/// And returns the annotation id
+pub fn insert_annotation(
+    tx: &Transaction,
+    document_id: i64,
+    page_id: i64,
+    annotation: &PageAnnotation,
+) -> anyhow::Result<i64> {
+    let mut stmt = tx.prepare(
+        "INSERT INTO annotations (document_id, page_id, kind, author, text) VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    let id = stmt.insert(params![
+        document_id,
+        page_id,
+        annotation.kind,
+        annotation.author,
+        annotation.text
+    ])?;
+    Ok(id)
+}
+
+/// Function to get the annotations of a document, as stored by `insert_annotation`
+/// Ordered by page
+pub fn get_annotations(
+    tx: &Transaction,
+    document_id: i64,
+) -> anyhow::Result<Vec<StoredAnnotation>> {
+    let mut stmt = tx.prepare(
+        r#"
+        SELECT a.id, a.page_id, a.text
+        FROM annotations a
+                 INNER JOIN pages p
+                            ON p.id = a.page_id
+        WHERE a.document_id = ?1
+        ORDER BY p.page_no, a.id
+    "#,
+    )?;
+    let annotations = stmt
+        .query_map(params![document_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect::<Result<Vec<StoredAnnotation>, rusqlite::Error>>()?;
+
+    Ok(annotations)
+}
+
+/// Function to get the metadata of a document
+pub fn get_metadata(tx: &Transaction, document_id: i64) -> anyhow::Result<DocumentMetadata> {
+    let metadata = tx.query_row(
+        "SELECT title, author, date, subject, keywords, code_language FROM documents WHERE id = ?1",
+        params![document_id],
+        |row| {
+            Ok(DocumentMetadata {
+                title: row.get(0)?,
+                author: row.get(1)?,
+                date: row.get(2)?,
+                subject: row.get(3)?,
+                keywords: row.get(4)?,
+                code_language: row.get(5)?,
+            })
+        },
+    )?;
+
+    Ok(metadata)
+}
+
+/// Function to delete every chunk in the database
+pub fn delete_chunks(tx: &Transaction) -> anyhow::Result<()> {
+    log::debug!("Deleting all chunks from database");
+
+    tx.execute("DELETE FROM chunks", [])?;
+    Ok(())
+}
+
+/// Function to delete a document along with its pages, headings, annotations and chunks
+/// And returns the ids of the deleted chunks, to be removed from the vector index
+pub fn delete_document(tx: &Transaction, document_id: i64) -> anyhow::Result<Vec<u64>> {
+    log::debug!("Deleting document {} from database", document_id);
+
+    let chunk_ids = delete_document_chunks(tx, document_id)?;
+    tx.execute(
+        "DELETE FROM annotations WHERE document_id = ?1",
+        params![document_id],
+    )?;
+    tx.execute(
+        "DELETE FROM pages WHERE document_id = ?1",
+        params![document_id],
+    )?;
+    tx.execute(
+        "DELETE FROM headings WHERE document_id = ?1",
+        params![document_id],
+    )?;
+    tx.execute("DELETE FROM documents WHERE id = ?1", params![document_id])?;
+
+    Ok(chunk_ids)
+}
+
+/// Function to delete the chunks of a document, e.g. to chunk its pages again
+/// And returns the ids of the deleted chunks, to be removed from the vector index
+pub fn delete_document_chunks(tx: &Transaction, document_id: i64) -> anyhow::Result<Vec<u64>> {
+    let mut stmt = tx.prepare(
+        r#"
+        SELECT c.id
+        FROM chunks c
+                 INNER JOIN pages p
+                            ON p.id = c.page_id
+        WHERE p.document_id = ?1
+    "#,
+    )?;
+    let chunk_ids = stmt
+        .query_map(params![document_id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<u64>>>()?;
+
+    tx.execute(
+        "DELETE FROM chunks WHERE page_id IN (SELECT id FROM pages WHERE document_id = ?1)",
+        params![document_id],
+    )?;
+
+    Ok(chunk_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk_text::sliding_window_chunk_indices;
+    use crate::database::{
+        delete_document, delete_document_chunks, get_annotations, get_document_pages,
+        insert_annotation, insert_chunks, insert_document, insert_failed_page, insert_page,
+        update_page_error, update_page_ocr, ChunkLocation, Database, DocumentMetadata,
+        PageAnnotation, SearchFilter,
+    };
+    use crate::document_source::SourcePage;
+    use crate::image_to_text::OcrWord;
+    use crate::page_regions::{PageRegion, Rect};
+    use rusqlite::Connection;
+
+    fn test_db() -> Database {
+        let db = Database {
+            conn: Connection::open_in_memory().unwrap(),
+        };
+        db.init_tables().unwrap();
+        db
+    }
+
+    fn page(text: &str, line_no: Option<usize>, title: Option<&str>) -> SourcePage {
+        SourcePage {
+            text: text.to_string(),
+            line_no,
+            title: title.map(str::to_string),
+            language: None,
+            confidence: None,
+            words: Vec::new(),
+            image_size: None,
+        }
+    }
+
+    #[test]
+    fn test_get_document_multi_byte_text() {
+        let mut db = test_db();
+        let pages = ["Grüße aus Köln — こんにちは世界 👨‍👩‍👧‍👦 done", "Straße 🙂 end"];
+        let chunks: Vec<(usize, usize)> = sliding_window_chunk_indices(pages[0], 10, 5).collect();
+
+        let tx = db.conn.transaction().unwrap();
+        let doc_id =
+            insert_document(&tx, "/tmp/doc.pdf", &DocumentMetadata::default(), None).unwrap();
+        let first_page_id = insert_page(&tx, doc_id, 0, &page(pages[0], None, None)).unwrap();
+        let second_page_id = insert_page(&tx, doc_id, 1, &page(pages[1], None, None)).unwrap();
+
+        let mut locations: Vec<ChunkLocation> = chunks
+            .iter()
+            .map(|&(start, end)| ChunkLocation {
+                start_page_id: first_page_id,
+                start,
+                end_page_id: first_page_id,
+                end,
+                heading: None,
+                annotation_id: None,
+            })
+            .collect();
+        // A chunk spanning from the middle of the first page into the second page
+        locations.push(ChunkLocation {
+            start_page_id: first_page_id,
+            start: pages[0].find('👨').unwrap(),
+            end_page_id: second_page_id,
+            end: "Straße".len(),
+            heading: Some("Grüße > Straße".to_string()),
+            annotation_id: None,
+        });
+        let ids = insert_chunks(&tx, &locations.iter().collect::<Vec<_>>()).unwrap();
+        tx.commit().unwrap();
+
+        for (id, &(start, end)) in ids.iter().zip(chunks.iter()) {
+            let chunk = db.get_document(*id as u64).unwrap();
+            assert_eq!(chunk.path, "/tmp/doc.pdf");
+            assert_eq!(chunk.metadata, DocumentMetadata::default());
+            assert_eq!(chunk.page_title, None);
+            assert_eq!((chunk.page_no, chunk.end_page_no), (0, 0));
+            assert_eq!(chunk.text, &pages[0][start..end]);
+            assert_eq!(chunk.heading, None);
+            assert_eq!(chunk.line_no, None);
+            assert_eq!(chunk.end_line_no, None);
+        }
+
+        let chunk = db.get_document(ids[chunks.len()] as u64).unwrap();
+        assert_eq!((chunk.page_no, chunk.end_page_no), (0, 1));
+        assert_eq!(chunk.text, "👨‍👩‍👧‍👦 done\n\nStraße");
+        assert_eq!(chunk.heading.as_deref(), Some("Grüße > Straße"));
+    }
+
+    #[test]
+    fn test_get_document_location_and_metadata() {
+        let mut db = test_db();
+        let text = "# Notes\nfirst line\nsecond line\n";
+        let metadata = DocumentMetadata {
+            title: Some("Notes".to_string()),
+            author: Some("Ann Author".to_string()),
+            date: Some("2024-03-01".to_string()),
+            ..Default::default()
+        };
+
+        let tx = db.conn.transaction().unwrap();
+        let doc_id = insert_document(&tx, "/tmp/notes.md", &metadata, None).unwrap();
+        let mut notes_page = page(text, Some(120), Some("Notes"));
+        notes_page.language = Some("eng".to_string());
+        let page_id = insert_page(&tx, doc_id, 0, &notes_page).unwrap();
+        let start = text.find("second").unwrap();
+        let location = ChunkLocation {
+            start_page_id: page_id,
+            start,
+            end_page_id: page_id,
+            end: text.len(),
+            heading: None,
+            annotation_id: None,
+        };
+        let ids = insert_chunks(&tx, &[&location]).unwrap();
+        tx.commit().unwrap();
+
+        let chunk = db.get_document(ids[0] as u64).unwrap();
+        assert_eq!(chunk.line_no, Some(122));
+        assert_eq!(chunk.end_line_no, Some(122));
+        assert_eq!(chunk.language.as_deref(), Some("eng"));
+        assert_eq!(chunk.metadata, metadata);
+        assert_eq!(chunk.page_title.as_deref(), Some("Notes"));
+        assert_eq!(chunk.text, "second line\n");
+    }
+
+    #[test]
+    fn test_filtered_chunk_ids() {
+        let mut db = test_db();
+        let documents = [
+            (
+                "/tmp/a.eml",
+                Some("Ann <ann@example.com>"),
+                Some("2024-02-28T09:00:00Z"),
+            ),
+            (
+                "/tmp/b.eml",
+                Some("Bob <bob@example.com>"),
+                Some("2024-03-01T10:00:00Z"),
+            ),
+            ("/tmp/c.rs", None, None),
+        ];
+
+        let tx = db.conn.transaction().unwrap();
+        let mut ids = Vec::new();
+        for (path, author, date) in documents {
+            let metadata = DocumentMetadata {
+                author: author.map(str::to_string),
+                date: date.map(str::to_string),
+                code_language: path.ends_with(".rs").then(|| "rust".to_string()),
+                ..Default::default()
+            };
+            let doc_id = insert_document(&tx, path, &metadata, None).unwrap();
+            let page_id = insert_page(&tx, doc_id, 0, &page("text", None, None)).unwrap();
+            let location = ChunkLocation {
+                start_page_id: page_id,
+                start: 0,
+                end_page_id: page_id,
+                end: 4,
+                heading: None,
+                annotation_id: None,
+            };
+            ids.push(insert_chunks(&tx, &[&location]).unwrap()[0] as u64);
+        }
+        tx.commit().unwrap();
+
+        let matching = |filter: SearchFilter| {
+            let matches = db.filtered_chunk_ids(&filter).unwrap();
+            ids.iter()
+                .enumerate()
+                .filter(|(_, id)| matches.contains(id))
+                .map(|(i, _)| i)
+                .collect::<Vec<usize>>()
+        };
+
+        assert_eq!(matching(SearchFilter::default()), vec![0, 1, 2]);
+        assert_eq!(
+            matching(SearchFilter {
+                author: Some("ANN@".to_string()),
+                ..Default::default()
+            }),
+            vec![0]
+        );
+        assert_eq!(
+            matching(SearchFilter {
+                after: Some("2024-03".to_string()),
+                ..Default::default()
+            }),
+            vec![1]
+        );
+        assert_eq!(
+            matching(SearchFilter {
+                before: Some("2024-03-01".to_string()),
+                ..Default::default()
+            }),
+            vec![0]
+        );
+        assert_eq!(
+            matching(SearchFilter {
+                code_languages: vec!["python".to_string(), "rust".to_string()],
+                ..Default::default()
+            }),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn test_annotations() {
+        let mut db = test_db();
+        let metadata = DocumentMetadata {
+            title: Some("Lease".to_string()),
+            subject: Some("Office rental agreement".to_string()),
+            keywords: Some("lease; rent; Berlin".to_string()),
+            ..Default::default()
+        };
+        let note = PageAnnotation {
+            page_no: 1,
+            kind: "text".to_string(),
+            author: Some("Ann".to_string()),
+            text: "Ask about the deposit".to_string(),
+        };
+
+        let tx = db.conn.transaction().unwrap();
+        let doc_id = insert_document(&tx, "/tmp/lease.pdf", &metadata, None).unwrap();
+        insert_page(&tx, doc_id, 0, &page("Parties", None, None)).unwrap();
+        let page_id =
+            insert_page(&tx, doc_id, 1, &page("Rent is due monthly", None, None)).unwrap();
+        let annotation_id = insert_annotation(&tx, doc_id, page_id, &note).unwrap();
+        let location = ChunkLocation {
+            start_page_id: page_id,
+            start: 0,
+            end_page_id: page_id,
+            end: note.text.len(),
+            heading: None,
+            annotation_id: Some(annotation_id),
+        };
+        let ids = insert_chunks(&tx, &[&location]).unwrap();
+        assert_eq!(
+            get_annotations(&tx, doc_id).unwrap(),
+            vec![(annotation_id, page_id, note.text.clone())]
+        );
+        tx.commit().unwrap();
+
+        let chunk = db.get_document(ids[0] as u64).unwrap();
+        assert_eq!(chunk.text, "Ask about the deposit");
+        assert_eq!(chunk.page_no, 1);
+        assert_eq!(chunk.metadata, metadata);
+        assert_eq!(chunk.annotation, Some(note));
+
+        let matches = |filter: SearchFilter| !db.filtered_chunk_ids(&filter).unwrap().is_empty();
+        assert!(matches(SearchFilter {
+            subject: Some("RENTAL".to_string()),
+            keywords: Some("berlin".to_string()),
+            ..Default::default()
+        }));
+        assert!(matches(SearchFilter {
+            title: Some("lease".to_string()),
+            ..Default::default()
+        }));
+        assert!(!matches(SearchFilter {
+            keywords: Some("Paris".to_string()),
+            ..Default::default()
+        }));
+
+        let tx = db.conn.transaction().unwrap();
+        assert_eq!(delete_document(&tx, doc_id).unwrap(), vec![ids[0] as u64]);
+        assert!(get_annotations(&tx, doc_id).unwrap().is_empty());
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn test_failed_pages() {
+        let mut db = test_db();
+
+        let tx = db.conn.transaction().unwrap();
+        let doc_id =
+            insert_document(&tx, "/tmp/scan.pdf", &DocumentMetadata::default(), None).unwrap();
+        insert_page(&tx, doc_id, 0, &page("Cover", None, None)).unwrap();
+        let page_id = insert_failed_page(&tx, doc_id, 1, "Failed to render page").unwrap();
+        insert_page(&tx, doc_id, 2, &page("Appendix", None, None)).unwrap();
+        tx.commit().unwrap();
+
+        let failed = db.failed_pages().unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(
+            (failed[0].document_id, failed[0].page_id, failed[0].page_no),
+            (doc_id, page_id, 1)
+        );
+        assert_eq!(failed[0].path, "/tmp/scan.pdf");
+        assert_eq!(failed[0].error, "Failed to render page");
+
+        let tx = db.conn.transaction().unwrap();
+        update_page_error(&tx, page_id, "Tesseract failed").unwrap();
+        tx.commit().unwrap();
+        assert_eq!(db.failed_pages().unwrap()[0].error, "Tesseract failed");
+
+        let tx = db.conn.transaction().unwrap();
+        update_page_ocr(&tx, page_id, &page("Findings", None, None)).unwrap();
+        let pages = get_document_pages(&tx, doc_id).unwrap();
+        tx.commit().unwrap();
+        assert!(db.failed_pages().unwrap().is_empty());
+        let texts: Vec<&str> = pages.iter().map(|(_, _, text, _)| text.as_str()).collect();
+        assert_eq!(texts, vec!["Cover", "Findings", "Appendix"]);
+    }
+
+    #[test]
+    fn test_archive_members() {
+        let mut db = test_db();
+        let metadata = DocumentMetadata::default();
+        let members = [
+            ("/tmp/bundle.zip!/docs/spec.pdf", "a1"),
+            ("/tmp/bundle.zip!/notes.md", "b2"),
+            ("/tmp/bundle.zip.md", "c3"),
+        ];
+
+        let tx = db.conn.transaction().unwrap();
+        let mut chunk_ids = Vec::new();
+        for (path, hash) in members {
+            let doc_id = insert_document(&tx, path, &metadata, Some(hash)).unwrap();
+            let page_id = insert_page(&tx, doc_id, 0, &page("text", None, None)).unwrap();
+            let location = ChunkLocation {
+                start_page_id: page_id,
+                start: 0,
+                end_page_id: page_id,
+                end: 4,
+                heading: None,
+                annotation_id: None,
+            };
+            chunk_ids.push(insert_chunks(&tx, &[&location]).unwrap()[0] as u64);
+        }
+        tx.commit().unwrap();
+
+        let (doc_id, hash) = db.find_document(members[0].0).unwrap().unwrap();
+        assert_eq!(hash.as_deref(), Some("a1"));
+        assert_eq!(db.find_document("/tmp/other.pdf").unwrap(), None);
+
+        let documents = db.member_documents("/tmp/bundle.zip").unwrap();
+        let paths: Vec<&str> = documents.iter().map(|(_, path)| path.as_str()).collect();
+        assert_eq!(paths, vec![members[0].0, members[1].0]);
+
+        let tx = db.conn.transaction().unwrap();
+        assert_eq!(delete_document(&tx, doc_id).unwrap(), vec![chunk_ids[0]]);
+        tx.commit().unwrap();
+
+        assert_eq!(db.find_document(members[0].0).unwrap(), None);
+        assert_eq!(db.member_documents("/tmp/bundle.zip").unwrap().len(), 1);
+        assert!(db.get_document(chunk_ids[0]).is_err());
+        assert!(db.get_document(chunk_ids[1]).is_ok());
+    }
+
+    #[test]
+    fn test_low_confidence_pages() {
+        let mut db = test_db();
+        let word = |text: &str, confidence: f32| OcrWord {
+            text: text.to_string(),
+            confidence,
+            left: 12,
+            top: 34,
+            width: 56,
+            height: 78,
+        };
+
+        let tx = db.conn.transaction().unwrap();
+        let doc_id =
+            insert_document(&tx, "/tmp/scan.pdf", &DocumentMetadata::default(), None).unwrap();
+        let mut pages = Vec::new();
+        for (page_no, confidence) in [Some(91.5), Some(42.0), None].into_iter().enumerate() {
+            let mut scanned = page("Tota1 due", None, None);
+            scanned.confidence = confidence;
+            scanned.words = vec![word("Tota1", 30.0), word("due", 54.0)];
+            scanned.image_size = Some((200, 100));
+            pages.push(insert_page(&tx, doc_id, page_no, &scanned).unwrap());
+        }
+        let location = ChunkLocation {
+            start_page_id: pages[1],
+            start: 0,
+            end_page_id: pages[1],
+            end: 5,
+            heading: None,
+            annotation_id: None,
+        };
+        let ids = insert_chunks(&tx, &[&location]).unwrap();
+        tx.commit().unwrap();
+
+        let low = db.low_confidence_pages(60.0).unwrap();
+        assert_eq!(low.len(), 1);
+        assert_eq!((low[0].page_id, low[0].page_no), (pages[1], 1));
+        assert_eq!(low[0].confidence, 42.0);
+        assert_eq!(low[0].words, vec![word("Tota1", 30.0), word("due", 54.0)]);
+        let chunk = db.get_document(ids[0] as u64).unwrap();
+        assert_eq!(chunk.confidence, Some(42.0));
+        assert_eq!(
+            chunk.regions,
+            vec![PageRegion {
+                page_no: 1,
+                rect: Rect {
+                    x: 0.06,
+                    y: 0.34,
+                    width: 0.28,
+                    height: 0.78,
+                },
+            }]
+        );
+
+        let tx = db.conn.transaction().unwrap();
+        let mut retried = page("Total due", None, None);
+        retried.confidence = Some(88.0);
+        retried.words = vec![word("Total", 85.0), word("due", 91.0)];
+        update_page_ocr(&tx, pages[1], &retried).unwrap();
+        assert_eq!(
+            delete_document_chunks(&tx, doc_id).unwrap(),
+            vec![ids[0] as u64]
+        );
+        tx.commit().unwrap();
+
+        assert!(db.low_confidence_pages(60.0).unwrap().is_empty());
+        assert_eq!(db.low_confidence_pages(90.0).unwrap()[0].page_id, pages[1]);
+        assert!(db.get_document(ids[0] as u64).is_err());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::chunk_sections::Heading;
+use crate::config::Config;
+use crate::database::{DocumentMetadata, PageAnnotation};
+use crate::image_to_text::{OcrText, OcrWord};
+use crate::pdf_to_image::PDFImages;
+use crate::source_archive::{archive_kind, read_archive};
+use crate::source_code::{extension_language, CodeSource};
+use crate::source_email::{read_mailbox, EmailSource};
+use crate::source_epub::EpubSource;
+use crate::source_html::HtmlSource;
+use crate::source_image::{self, ImageSource};
+use crate::source_office::OfficeSource;
+use crate::source_pdf::PdfSource;
+use crate::source_text::TextSource;
+use image::DynamicImage;
+use pdfium_render::prelude::Pdfium;
+use std::iter;
+use std::path::Path;
+
+/// Separates the path of a container file, such as a mailbox or archive, from a document
+/// within it
+pub const MEMBER_SEPARATOR: &str = "!/";
+
+/// Extensions of the document files that can be indexed, besides source code
+const DOCUMENT_EXTENSIONS: &[&str] = &[
+    "pdf", "md", "markdown", "txt", "text", "html", "htm", "xhtml", "mhtml", "mht", "epub", "docx",
+    "odt", "pptx", "png", "jpg", "jpeg", "tif", "tiff", "eml",
+];
+
+/// A logical page of a document
+pub struct SourcePage {
+    pub text: String,
+    /// Line of the source file the page starts at, counting from 1, for text based formats
+    pub line_no: Option<usize>,
+    /// Title of the page, e.g. the chapter of a book
+    pub title: Option<String>,
+    /// Tesseract code of the language of the page, where it was detected during OCR
+    pub language: Option<String>,
+    /// Mean confidence of the words recognized by OCR, from 0 to 100, for pages read with OCR
+    pub confidence: Option<f32>,
+    /// Words recognized by OCR along with their confidence and position
+    pub words: Vec<OcrWord>,
+    /// Width and height of the image the words are positioned in
+    pub image_size: Option<(u32, u32)>,
+}
+
+impl From<OcrText> for SourcePage {
+    fn from(ocr: OcrText) -> SourcePage {
+        SourcePage {
+            text: ocr.text,
+            line_no: None,
+            title: None,
+            language: ocr.language,
+            confidence: ocr.confidence,
+            words: ocr.words,
+            image_size: Some(ocr.image_size),
+        }
+    }
+}
+
+/// A document that can be indexed, read as a sequence of logical pages.
+///
+/// Pages are read lazily as they may be expensive to produce, e.g. by running OCR.
+pub trait DocumentSource: Iterator<Item = anyhow::Result<SourcePage>> {
+    /// Metadata of the document, for formats that record it
+    fn metadata(&self) -> DocumentMetadata {
+        DocumentMetadata::default()
+    }
+
+    /// Section headings of the document, in document order
+    fn headings(&self) -> Vec<Heading>;
+
+    /// Comments and other annotations users added to the pages of the document, for formats
+    /// that record them
+    fn annotations(&self) -> Vec<PageAnnotation> {
+        Vec::new()
+    }
+
+    /// Paths of the attachments that were skipped as they are encrypted and no password opens
+    /// them, e.g. `mail.eml!/report.pdf`
+    fn encrypted_attachments(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Opens the source of a document
+type OpenSource<'a> = Box<dyn FnOnce() -> anyhow::Result<Box<dyn DocumentSource + 'a>> + 'a>;
+
+/// A document of a file, only opened once it is known to need indexing
+pub struct Document<'a> {
+    /// Path the document is indexed under
+    pub path: String,
+    /// Hash of the contents of archive members, to only index them again when they change
+    pub content_hash: Option<String>,
+    open: OpenSource<'a>,
+}
+
+impl<'a> Document<'a> {
+    pub fn new(
+        path: String,
+        content_hash: Option<String>,
+        open: impl FnOnce() -> anyhow::Result<Box<dyn DocumentSource + 'a>> + 'a,
+    ) -> Document<'a> {
+        Document {
+            path,
+            content_hash,
+            open: Box::new(open),
+        }
+    }
+
+    /// Opens the document to read its pages
+    pub fn open(self) -> anyhow::Result<Box<dyn DocumentSource + 'a>> {
+        (self.open)()
+    }
+}
+
+/// Lists the documents of a file. Most files are a single document indexed under their
+/// own path, while each message of a mailbox and each member of an archive is a document
+/// of its own. An error ends the list when the rest of the file can't be read.
+pub fn open_documents<'a>(
+    pdfium: &'a Pdfium,
+    path: &'a Path,
+    config: &'a Config,
+) -> anyhow::Result<Box<dyn Iterator<Item = anyhow::Result<Document<'a>>> + 'a>> {
+    if file_extension(path) == "mbox" {
+        return Ok(Box::new(read_mailbox(pdfium, path, config)?));
+    }
+
+    if let Some(kind) = archive_kind(path) {
+        return Ok(Box::new(read_archive(pdfium, path, kind, config)?));
+    }
+
+    Ok(Box::new(iter::once_with(move || {
+        Ok(Document::new(
+            path.to_string_lossy().into_owned(),
+            None,
+            move || open_document(pdfium, path, config),
+        ))
+    })))
+}
+
+/// Opens a document with the source matching its file extension
+pub fn open_document<'a>(
+    pdfium: &'a Pdfium,
+    path: &Path,
+    config: &Config,
+) -> anyhow::Result<Box<dyn DocumentSource + 'a>> {
+    let source: Box<dyn DocumentSource> = match file_extension(path).as_str() {
+        "pdf" => Box::new(PdfSource::new(pdfium, path, config)?),
+        "md" | "markdown" => Box::new(TextSource::markdown(path)?),
+        "txt" | "text" => Box::new(TextSource::plain(path)?),
+        "html" | "htm" | "xhtml" => Box::new(HtmlSource::html(path)?),
+        "mhtml" | "mht" => Box::new(HtmlSource::mhtml(path)?),
+        "epub" => Box::new(EpubSource::new(path)?),
+        "docx" => Box::new(OfficeSource::docx(path)?),
+        "odt" => Box::new(OfficeSource::odt(path)?),
+        "pptx" => Box::new(OfficeSource::pptx(path)?),
+        "png" | "jpg" | "jpeg" | "tif" | "tiff" => Box::new(ImageSource::new(path, config)?),
+        "eml" => Box::new(EmailSource::eml(pdfium, path, config)?),
+        extension => match extension_language(extension) {
+            Some(language) => Box::new(CodeSource::new(path, language)?),
+            None => anyhow::bail!("Unsupported file type {}", path.display()),
+        },
+    };
+
+    Ok(source)
+}
+
+/// Renders a page of a PDF or image file again, counting from 0, e.g. to run OCR on it with
+/// other settings. Returns None for documents whose pages are not images, and for documents
+/// within another file, such as attachments and archive members.
+pub fn page_image(
+    pdfium: &Pdfium,
+    path: &str,
+    page_no: usize,
+    config: &Config,
+) -> anyhow::Result<Option<DynamicImage>> {
+    if path.contains(MEMBER_SEPARATOR) {
+        return Ok(None);
+    }
+
+    let path = Path::new(path);
+    let image = match file_extension(path).as_str() {
+        "pdf" => {
+            let page = u16::try_from(page_no)?;
+            PDFImages::new(pdfium, &path, config)?.render(page)?
+        }
+        "png" | "jpg" | "jpeg" | "tif" | "tiff" => source_image::page_image(path, page_no)?,
+        _ => return Ok(None),
+    };
+
+    Ok(Some(image))
+}
+
+/// Whether a file is of a type that can be indexed, judged by its name
+pub fn is_supported(path: &Path) -> bool {
+    is_document(path) || is_container(path)
+}
+
+/// Whether a file holds documents of its own, such as a mailbox or an archive
+pub fn is_container(path: &Path) -> bool {
+    file_extension(path) == "mbox" || archive_kind(path).is_some()
+}
+
+/// Whether a file is a single document that can be opened with `open_document`
+pub fn is_document(path: &Path) -> bool {
+    let extension = file_extension(path);
+    DOCUMENT_EXTENSIONS.contains(&extension.as_str()) || extension_language(&extension).is_some()
+}
+
+/// Whether indexing a file may run OCR: PDF and image files, archives that may contain them,
+/// and emails when their PDF attachments are indexed
+pub fn may_use_ocr(path: &Path, config: &Config) -> bool {
+    match file_extension(path).as_str() {
+        "pdf" | "png" | "jpg" | "jpeg" | "tif" | "tiff" => true,
+        "eml" | "mbox" => config.email_pdf_attachments.value,
+        _ => archive_kind(path).is_some(),
+    }
+}
+
+fn file_extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
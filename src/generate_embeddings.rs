@@ -1,25 +1,61 @@
-use crate::lazy_init::TEXT_EMBEDDING;
-use fastembed::Embedding;
+use crate::lazy_init::text_embedding;
+use fastembed::{Embedding, EmbeddingModel, TextEmbedding};
+use tokenizers::Tokenizer;
 
-pub const EMBEDDING_BATCH_SIZE: usize = 256;
-pub const EMBEDDING_DIMENSIONS: usize = 768;
+/// Instruction prepended to every string before it is embedded
+const PASSAGE_PREFIX: &str = "Represent this sentence for searching relevant passages: ";
 
 /// Generates embeddings for a list of strings and returns them as a vector of vectors
 /// Each vector represents an embedding for a string
-pub fn generate_embeddings(strings: Vec<&str>) -> anyhow::Result<Vec<Embedding>> {
+pub fn generate_embeddings(
+    strings: Vec<&str>,
+    model: &EmbeddingModel,
+    batch_size: usize,
+) -> anyhow::Result<Vec<Embedding>> {
     log::debug!("Generating embeddings for {} strings", strings.len());
 
-    // Generate embeddings with the default batch size, 256
-    TEXT_EMBEDDING.embed(
+    text_embedding(model)?.embed(
         strings
             .iter()
-            .map(|s| {
-                format!(
-                    "Represent this sentence for searching relevant passages: {}",
-                    s
-                )
-            })
+            .map(|s| format!("{}{}", PASSAGE_PREFIX, s))
             .collect(),
-        Some(EMBEDDING_BATCH_SIZE),
+        Some(batch_size),
     )
 }
+
+/// Finds a supported embedding model by its name, e.g. `BGEBaseENV15`
+pub fn parse_embedding_model(name: &str) -> anyhow::Result<EmbeddingModel> {
+    TextEmbedding::list_supported_models()
+        .into_iter()
+        .map(|info| info.model)
+        .find(|model| format!("{:?}", model).eq_ignore_ascii_case(name))
+        .ok_or_else(|| anyhow::anyhow!("Unknown embedding model {}", name))
+}
+
+/// Maximum number of tokens a chunk can have without being truncated by the model,
+/// once the passage prefix and special tokens are added
+pub fn max_chunk_tokens(tokenizer: &Tokenizer) -> anyhow::Result<usize> {
+    let max_length = tokenizer
+        .get_truncation()
+        .map(|truncation| truncation.max_length)
+        .unwrap_or(usize::MAX);
+    let overhead = tokenizer
+        .encode(PASSAGE_PREFIX, true)
+        .map_err(anyhow::Error::msg)?
+        .len();
+
+    if max_length <= overhead {
+        anyhow::bail!(
+            "The model embeds at most {} tokens, leaving no room after the {} of the passage prefix",
+            max_length,
+            overhead
+        );
+    }
+
+    Ok(max_length - overhead)
+}
+
+/// Number of dimensions of the embeddings generated by a model
+pub fn embedding_dimensions(model: &EmbeddingModel) -> anyhow::Result<usize> {
+    Ok(TextEmbedding::get_model_info(model)?.dim)
+}
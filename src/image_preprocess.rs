@@ -0,0 +1,436 @@
+use crate::config::Config;
+use crate::image_to_text::ocr_args;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GenericImageView, GrayImage, Luma};
+use rusty_tesseract::{Args, Image};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// Luma below which pixels are taken to be dark when looking for scanner borders
+const BORDER_LUMA: u8 = 96;
+/// Share of dark pixels above which a row or column at the edge is taken to be a border
+const BORDER_DARK_SHARE: f32 = 0.5;
+/// Largest share of the width or height cropped from each side as border
+const MAX_BORDER_SHARE: u32 = 4;
+
+/// Largest skew in degrees that is corrected, either way
+const MAX_SKEW: f32 = 5.0;
+/// Step in degrees between the skew angles that are tried
+const SKEW_STEP: f32 = 0.25;
+/// Width images are scaled down to for estimating their skew
+const SKEW_SAMPLE_WIDTH: u32 = 800;
+
+/// A preprocessing step applied to images before OCR. The steps run in the order they are
+/// declared here, regardless of the order they are configured in.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PreprocessStep {
+    /// Turn the image upright, as detected by tesseract's orientation detection
+    Orientation,
+    /// Convert to grayscale, which every other step besides orientation implies
+    Grayscale,
+    /// Crop the dark borders around scanned pages
+    CropBorders,
+    /// Remove speckles with a 3x3 median filter
+    Denoise,
+    /// Straighten text scanned at a slight angle
+    Deskew,
+    /// Convert to black and white at a threshold chosen with Otsu's method
+    Binarize,
+}
+
+/// How a preprocessed image was made from the original one, to map positions in it back
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ImageTransform {
+    /// Clockwise rotation in degrees turning the original image upright
+    rotation: u32,
+    /// Size of the image once turned upright
+    upright_size: (u32, u32),
+    /// Top left corner of the region of the upright image kept when cropping borders
+    crop_offset: (u32, u32),
+    /// Size of the cropped image, which deskewing rotates about its center
+    cropped_size: (u32, u32),
+    /// Angle in degrees the cropped image was straightened by
+    skew: f32,
+}
+
+impl ImageTransform {
+    /// Maps a point of the preprocessed image to the original image
+    pub fn original_point(&self, x: f32, y: f32) -> (f32, f32) {
+        // Undo deskewing, which rotated the image about its center
+        let (center_x, center_y) = (
+            self.cropped_size.0 as f32 / 2.0,
+            self.cropped_size.1 as f32 / 2.0,
+        );
+        let (sin, cos) = self.skew.to_radians().sin_cos();
+        let (dx, dy) = (x - center_x, y - center_y);
+        let (x, y) = (
+            center_x + dx * cos - dy * sin,
+            center_y + dx * sin + dy * cos,
+        );
+
+        // Undo cropping and the clockwise rotation
+        let (x, y) = (x + self.crop_offset.0 as f32, y + self.crop_offset.1 as f32);
+        let (width, height) = (self.upright_size.0 as f32, self.upright_size.1 as f32);
+        match self.rotation {
+            90 => (y, width - x),
+            180 => (width - x, height - y),
+            270 => (height - y, x),
+            _ => (x, y),
+        }
+    }
+
+    /// Maps a rectangle of the preprocessed image to the smallest rectangle of the original
+    /// image containing it, returned as left, top, width and height
+    pub fn original_rect(&self, left: i32, top: i32, width: i32, height: i32) -> [i32; 4] {
+        let corners = [
+            (left, top),
+            (left + width, top),
+            (left, top + height),
+            (left + width, top + height),
+        ]
+        .map(|(x, y)| self.original_point(x as f32, y as f32));
+
+        let min_x = corners.iter().map(|c| c.0).fold(f32::MAX, f32::min);
+        let max_x = corners.iter().map(|c| c.0).fold(f32::MIN, f32::max);
+        let min_y = corners.iter().map(|c| c.1).fold(f32::MAX, f32::min);
+        let max_y = corners.iter().map(|c| c.1).fold(f32::MIN, f32::max);
+        [
+            min_x.round() as i32,
+            min_y.round() as i32,
+            (max_x - min_x).round() as i32,
+            (max_y - min_y).round() as i32,
+        ]
+    }
+}
+
+/// Prepares an image for OCR with the steps configured in `ocr.preprocess`, along with the
+/// transform mapping positions in it back to the original image.
+/// The image is returned as is when no steps are configured.
+pub fn preprocess_image<'a>(
+    image: &'a DynamicImage,
+    config: &Config,
+) -> (Cow<'a, DynamicImage>, ImageTransform) {
+    let steps = &config.ocr_preprocess.value;
+    let mut image = Cow::Borrowed(image);
+    let mut transform = ImageTransform::default();
+
+    if steps.contains(&PreprocessStep::Orientation) {
+        match detect_rotation(&image, config) {
+            Ok(rotation @ (90 | 180 | 270)) => {
+                image = Cow::Owned(match rotation {
+                    90 => image.rotate90(),
+                    180 => image.rotate180(),
+                    _ => image.rotate270(),
+                });
+                transform.rotation = rotation;
+            }
+            Ok(_) => {}
+            // Detection fails on images with too little text, which are read as they are
+            Err(e) => log::debug!("Unable to detect the orientation of the image: {}", e),
+        }
+    }
+    transform.upright_size = image.dimensions();
+    transform.cropped_size = image.dimensions();
+    if steps
+        .iter()
+        .all(|step| *step == PreprocessStep::Orientation)
+    {
+        return (image, transform);
+    }
+
+    let mut gray = image.to_luma8();
+    if steps.contains(&PreprocessStep::CropBorders) {
+        let (left, top, width, height) = border_crop(&gray);
+        gray = imageops::crop_imm(&gray, left, top, width, height).to_image();
+        transform.crop_offset = (left, top);
+        transform.cropped_size = (width, height);
+    }
+    if steps.contains(&PreprocessStep::Denoise) {
+        gray = median_filter(&gray);
+    }
+    if steps.contains(&PreprocessStep::Deskew) {
+        let angle = skew_angle(&gray);
+        if angle != 0.0 {
+            log::debug!("Straightening image skewed by {} degrees", angle);
+            gray = rotate(&gray, angle);
+            transform.skew = angle;
+        }
+    }
+    if steps.contains(&PreprocessStep::Binarize) {
+        let threshold = otsu_threshold(&gray);
+        for pixel in gray.pixels_mut() {
+            pixel[0] = if pixel[0] <= threshold { 0 } else { 255 };
+        }
+    }
+
+    (Cow::Owned(DynamicImage::ImageLuma8(gray)), transform)
+}
+
+/// Clockwise rotation in degrees that turns an image upright, from tesseract's orientation
+/// and script detection
+fn detect_rotation(image: &DynamicImage, config: &Config) -> anyhow::Result<u32> {
+    let args = Args {
+        psm: Some(0),
+        ..ocr_args(config)?
+    };
+    let osd = rusty_tesseract::image_to_string(&Image::from_dynamic_image(image)?, &args)?;
+
+    let rotation = osd
+        .lines()
+        .find_map(|line| line.strip_prefix("Rotate:"))
+        .ok_or_else(|| anyhow::anyhow!("No rotation in orientation detection output"))?;
+    Ok(rotation.trim().parse()?)
+}
+
+/// Finds the region within the rows and columns at the edges that are mostly dark, as left
+/// around pages by scanners with their lid open, up to a quarter of the image from each side.
+/// Returns its left, top, width and height.
+fn border_crop(image: &GrayImage) -> (u32, u32, u32, u32) {
+    let (width, height) = image.dimensions();
+    let dark_share = |pixels: &mut dyn Iterator<Item = u8>, len: u32| {
+        pixels.filter(|luma| *luma < BORDER_LUMA).count() as f32 / len as f32
+    };
+    let row_is_border = |y: u32| {
+        dark_share(&mut (0..width).map(|x| image.get_pixel(x, y)[0]), width) > BORDER_DARK_SHARE
+    };
+    let column_is_border = |x: u32| {
+        dark_share(&mut (0..height).map(|y| image.get_pixel(x, y)[0]), height) > BORDER_DARK_SHARE
+    };
+
+    let (max_rows, max_columns) = (height / MAX_BORDER_SHARE, width / MAX_BORDER_SHARE);
+    let top = (0..max_rows)
+        .find(|y| !row_is_border(*y))
+        .unwrap_or(max_rows);
+    let bottom = (height - max_rows..height)
+        .rev()
+        .find(|y| !row_is_border(*y))
+        .map_or(height - max_rows, |y| y + 1);
+    let left = (0..max_columns)
+        .find(|x| !column_is_border(*x))
+        .unwrap_or(max_columns);
+    let right = (width - max_columns..width)
+        .rev()
+        .find(|x| !column_is_border(*x))
+        .map_or(width - max_columns, |x| x + 1);
+
+    (left, top, right - left, bottom - top)
+}
+
+/// Replaces every pixel by the median of its 3x3 neighbourhood, removing isolated specks
+/// while keeping the edges of characters
+fn median_filter(image: &GrayImage) -> GrayImage {
+    let (width, height) = image.dimensions();
+    GrayImage::from_fn(width, height, |x, y| {
+        let mut window = [0u8; 9];
+        for (i, (dx, dy)) in (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+            .enumerate()
+        {
+            let x = x.saturating_add_signed(dx).min(width - 1);
+            let y = y.saturating_add_signed(dy).min(height - 1);
+            window[i] = image.get_pixel(x, y)[0];
+        }
+        window.sort_unstable();
+        Luma([window[4]])
+    })
+}
+
+/// Estimates the angle in degrees lines of text descend by from left to right, by finding
+/// the rotation at which the dark pixels are most concentrated in a few rows
+fn skew_angle(image: &GrayImage) -> f32 {
+    let sample = if image.width() > SKEW_SAMPLE_WIDTH {
+        let height = image.height() * SKEW_SAMPLE_WIDTH / image.width();
+        Cow::Owned(imageops::resize(
+            image,
+            SKEW_SAMPLE_WIDTH,
+            height.max(1),
+            FilterType::Triangle,
+        ))
+    } else {
+        Cow::Borrowed(image)
+    };
+
+    let threshold = otsu_threshold(&sample);
+    let dark: Vec<(f32, f32)> = sample
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel[0] <= threshold)
+        .map(|(x, y, _)| (x as f32, y as f32))
+        .collect();
+    if dark.is_empty() || dark.len() == (sample.width() * sample.height()) as usize {
+        return 0.0;
+    }
+
+    // Rows of the rotated image are counted with room for the rows rotated above and below
+    let offset = sample.width() as f32;
+    let rows = (sample.height() + 2 * sample.width()) as usize;
+    let steps = (MAX_SKEW / SKEW_STEP) as i32;
+    let mut best: (f32, f64) = (0.0, 0.0);
+    for step in -steps..=steps {
+        let angle = step as f32 * SKEW_STEP;
+        let (sin, cos) = angle.to_radians().sin_cos();
+
+        let mut profile = vec![0u64; rows];
+        for (x, y) in &dark {
+            let row = (y * cos - x * sin + offset).round() as usize;
+            profile[row.min(rows - 1)] += 1;
+        }
+        // The sum of squares grows as the same number of pixels fill fewer rows
+        let score: f64 = profile.iter().map(|count| (*count * *count) as f64).sum();
+        if score > best.1 || (score == best.1 && angle.abs() < best.0.abs()) {
+            best = (angle, score);
+        }
+    }
+
+    best.0
+}
+
+/// Rotates an image about its center so lines descending by the angle in degrees become
+/// level, keeping its size and filling the uncovered corners with white
+fn rotate(image: &GrayImage, angle: f32) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (center_x, center_y) = (width as f32 / 2.0, height as f32 / 2.0);
+
+    GrayImage::from_fn(width, height, |x, y| {
+        let (dx, dy) = (x as f32 - center_x, y as f32 - center_y);
+        let source_x = center_x + dx * cos - dy * sin;
+        let source_y = center_y + dx * sin + dy * cos;
+        imageops::interpolate_bilinear(image, source_x, source_y).unwrap_or(Luma([255]))
+    })
+}
+
+/// Threshold separating dark from light pixels that maximizes the variance between the two
+/// classes, as in Otsu's method. Pixels at or below it are dark.
+fn otsu_threshold(image: &GrayImage) -> u8 {
+    let mut histogram = [0u64; 256];
+    for pixel in image.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+
+    let total = (image.width() * image.height()) as f64;
+    let sum: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(value, count)| value as f64 * *count as f64)
+        .sum();
+
+    let (mut dark_weight, mut dark_sum) = (0.0, 0.0);
+    let (mut threshold, mut best_variance) = (0, 0.0);
+    for (value, count) in histogram.iter().enumerate() {
+        dark_weight += *count as f64;
+        dark_sum += value as f64 * *count as f64;
+        let light_weight = total - dark_weight;
+        if dark_weight == 0.0 || light_weight == 0.0 {
+            continue;
+        }
+
+        let dark_mean = dark_sum / dark_weight;
+        let light_mean = (sum - dark_sum) / light_weight;
+        let variance = dark_weight * light_weight * (dark_mean - light_mean).powi(2);
+        if variance > best_variance {
+            threshold = value as u8;
+            best_variance = variance;
+        }
+    }
+
+    threshold
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::image_preprocess::{
+        border_crop, median_filter, otsu_threshold, preprocess_image, rotate, skew_angle,
+        ImageTransform, PreprocessStep,
+    };
+    use image::{DynamicImage, GrayImage, Luma};
+
+    /// A white page with dark lines of "text" descending by the angle in degrees
+    fn page(angle: f32) -> GrayImage {
+        let slope = angle.to_radians().tan();
+        GrayImage::from_fn(600, 400, |x, y| {
+            let y = y as f32 - x as f32 * slope;
+            let on_line = (60.0..340.0).contains(&y) && (y as u32 % 40) < 8;
+            let in_margin = !(50..550).contains(&x);
+            Luma([if on_line && !in_margin { 20 } else { 240 }])
+        })
+    }
+
+    #[test]
+    fn test_otsu_threshold() {
+        let image = GrayImage::from_fn(10, 10, |x, _| Luma([if x < 3 { 40 } else { 210 }]));
+        let threshold = otsu_threshold(&image);
+        assert!((40..210).contains(&threshold));
+    }
+
+    #[test]
+    fn test_deskew() {
+        assert_eq!(skew_angle(&page(0.0)), 0.0);
+        assert_eq!(skew_angle(&page(2.0)), 2.0);
+        assert_eq!(skew_angle(&page(-3.5)), -3.5);
+
+        let straightened = rotate(&page(2.0), 2.0);
+        assert!(skew_angle(&straightened).abs() <= 0.25);
+    }
+
+    #[test]
+    fn test_border_crop() {
+        // A page with a black border 10 pixels wide at the top and 20 at the left
+        let image = GrayImage::from_fn(200, 100, |x, y| {
+            Luma([if x < 20 || y < 10 { 0 } else { 255 }])
+        });
+        assert_eq!(border_crop(&image), (20, 10, 180, 90));
+
+        let blank = GrayImage::from_pixel(200, 100, Luma([255]));
+        assert_eq!(border_crop(&blank), (0, 0, 200, 100));
+    }
+
+    #[test]
+    fn test_median_filter() {
+        let mut image = GrayImage::from_pixel(5, 5, Luma([255]));
+        image.put_pixel(2, 2, Luma([0]));
+        assert!(median_filter(&image).pixels().all(|pixel| pixel[0] == 255));
+    }
+
+    #[test]
+    fn test_preprocess_image() {
+        let image = DynamicImage::ImageRgba8(DynamicImage::ImageLuma8(page(0.0)).to_rgba8());
+        let mut config = Config::default();
+        let (preprocessed, transform) = preprocess_image(&image, &config);
+        assert!(matches!(preprocessed, std::borrow::Cow::Borrowed(_)));
+        assert_eq!(transform.original_point(12.0, 34.0), (12.0, 34.0));
+
+        config.ocr_preprocess.value = vec![PreprocessStep::Binarize, PreprocessStep::Grayscale];
+        let (preprocessed, _) = preprocess_image(&image, &config);
+        let gray = preprocessed.as_luma8().unwrap();
+        assert!(gray.pixels().all(|pixel| pixel[0] == 0 || pixel[0] == 255));
+    }
+
+    #[test]
+    fn test_image_transform() {
+        // A 200x100 image turned upright by a clockwise quarter turn is 100x200, of which
+        // the region from (10, 20) was kept
+        let transform = ImageTransform {
+            rotation: 90,
+            upright_size: (100, 200),
+            crop_offset: (10, 20),
+            cropped_size: (80, 160),
+            skew: 0.0,
+        };
+        // The top left corner of the upright image was the bottom left corner of the original
+        assert_eq!(transform.original_point(-10.0, -20.0), (0.0, 100.0));
+        assert_eq!(transform.original_point(0.0, 0.0), (20.0, 90.0));
+        assert_eq!(transform.original_rect(0, 0, 5, 10), [20, 85, 10, 5]);
+
+        let transform = ImageTransform {
+            cropped_size: (100, 100),
+            skew: 90.0,
+            ..Default::default()
+        };
+        let (x, y) = transform.original_point(50.0, 0.0);
+        assert!((x - 100.0).abs() < 1e-3 && (y - 50.0).abs() < 1e-3);
+    }
+}
@@ -1,12 +1,268 @@
+use crate::config::Config;
+use crate::image_preprocess::{preprocess_image, PreprocessStep};
+use crate::language::detect_language;
+use crate::ocr_cache::cached_ocr;
 use anyhow;
-use image::DynamicImage;
-use rusty_tesseract::{Args, Image};
+use image::{DynamicImage, GenericImageView};
+use rusty_tesseract::{Args, Data, Image};
+use std::sync::OnceLock;
 
-pub fn image_ocr(image: &DynamicImage) -> anyhow::Result<String> {
-    log::debug!("Extracting text from PDF Image");
+/// Text recognized in an image
+pub struct OcrText {
+    pub text: String,
+    /// Language detected in the text, when `language.detect` is enabled
+    pub language: Option<String>,
+    /// Mean confidence of the recognized words, from 0 to 100, or None without any words
+    pub confidence: Option<f32>,
+    /// Recognized words in reading order
+    pub words: Vec<OcrWord>,
+    /// Width and height of the image the words are positioned in
+    pub image_size: (u32, u32),
+}
+
+/// A word recognized by tesseract, with its bounding box in pixels of the image
+#[derive(Clone, Debug, PartialEq)]
+pub struct OcrWord {
+    pub text: String,
+    /// Confidence from 0 to 100
+    pub confidence: f32,
+    pub left: i32,
+    pub top: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Tesseract level of the rows describing single words
+const WORD_LEVEL: i32 = 5;
+
+/// Recognizes the text of an image with the configured languages. When language detection
+/// is enabled and the text is detected to be in another installed language, the image is
+/// recognized again with that language.
+///
+/// Images read before with the same OCR settings are taken from the OCR cache instead.
+pub fn image_ocr(image: &DynamicImage, config: &Config) -> anyhow::Result<OcrText> {
+    cached_ocr(image, config, || read_image(image, config))
+}
+
+/// Recognizes the text of an image as described for `image_ocr`, without the cache
+fn read_image(image: &DynamicImage, config: &Config) -> anyhow::Result<OcrText> {
+    log::debug!("Extracting text from image");
+
+    let (preprocessed, transform) = preprocess_image(image, config);
+    let ocr = recognize(&preprocessed, config)?;
+
+    // Words are positioned in the image as given rather than the preprocessed one, so they
+    // can be shown on the page
+    let words = ocr
+        .words
+        .into_iter()
+        .map(|word| {
+            let [left, top, width, height] =
+                transform.original_rect(word.left, word.top, word.width, word.height);
+            OcrWord {
+                left,
+                top,
+                width,
+                height,
+                ..word
+            }
+        })
+        .collect();
+    Ok(OcrText {
+        words,
+        image_size: image.dimensions(),
+        ..ocr
+    })
+}
+
+/// Recognizes the text of an image, with language detection as described for `image_ocr`
+fn recognize(image: &DynamicImage, config: &Config) -> anyhow::Result<OcrText> {
+    let img = Image::from_dynamic_image(image)?;
+    let mut args = ocr_args(config)?;
+    let ocr = ocr_text(
+        &rusty_tesseract::image_to_data(&img, &args)?.data,
+        image.dimensions(),
+    );
+    if !config.detect_language.value {
+        return Ok(ocr);
+    }
+
+    let Some(detected) = detect_language(&ocr.text) else {
+        return Ok(ocr);
+    };
+    let configured = args.lang.split('+').any(|language| language == detected);
+    if configured || !installed_languages().contains(&detected) {
+        return Ok(OcrText {
+            language: Some(detected),
+            ..ocr
+        });
+    }
+
+    log::debug!("Extracting text again with detected language {}", detected);
+    args.lang = detected.clone();
+    Ok(OcrText {
+        language: Some(detected),
+        ..ocr_text(
+            &rusty_tesseract::image_to_data(&img, &args)?.data,
+            image.dimensions(),
+        )
+    })
+}
+
+/// Builds the text of an image from the words tesseract recognized, with a line per line
+/// of text and a blank line between paragraphs, as `image_to_string` lays it out
+fn ocr_text(data: &[Data], image_size: (u32, u32)) -> OcrText {
+    let mut text = String::new();
+    let mut words = Vec::new();
+    let mut previous: Option<&Data> = None;
+    for word in data
+        .iter()
+        .filter(|row| row.level == WORD_LEVEL && !row.text.trim().is_empty())
+    {
+        if let Some(previous) = previous {
+            let same_paragraph =
+                (previous.block_num, previous.par_num) == (word.block_num, word.par_num);
+            text.push_str(if !same_paragraph {
+                "\n\n"
+            } else if previous.line_num != word.line_num {
+                "\n"
+            } else {
+                " "
+            });
+        }
+        text.push_str(&word.text);
+        previous = Some(word);
+
+        words.push(OcrWord {
+            text: word.text.clone(),
+            confidence: word.conf.max(0.0),
+            left: word.left,
+            top: word.top,
+            width: word.width,
+            height: word.height,
+        });
+    }
+
+    let confidence = (!words.is_empty())
+        .then(|| words.iter().map(|word| word.confidence).sum::<f32>() / words.len() as f32);
+    OcrText {
+        text,
+        language: None,
+        confidence,
+        words,
+        image_size,
+    }
+}
+
+/// Tesseract arguments from the OCR settings
+pub fn ocr_args(config: &Config) -> anyhow::Result<Args> {
+    Ok(Args {
+        lang: config.ocr_language.value.clone(),
+        config_variables: config.ocr_config_variables()?.into_iter().collect(),
+        dpi: Some(config.ocr_dpi.value),
+        psm: Some(config.ocr_psm.value),
+        oem: Some(config.ocr_oem.value),
+    })
+}
+
+/// Checks that the traineddata of every configured OCR language is installed, so indexing
+/// fails before any page is read rather than on every page
+pub fn check_ocr_languages(config: &Config) -> anyhow::Result<()> {
+    let installed = installed_languages();
+    if installed.is_empty() {
+        log::warn!("Unable to list the tesseract languages, OCR may fail");
+        return Ok(());
+    }
+
+    // Orientation detection needs the traineddata of tesseract's script detection
+    let orientation = config
+        .ocr_preprocess
+        .value
+        .contains(&PreprocessStep::Orientation)
+        .then_some("osd");
+    let missing: Vec<&str> = config
+        .ocr_language
+        .value
+        .split('+')
+        .chain(orientation)
+        .filter(|language| !installed.iter().any(|installed| installed == language))
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Tesseract traineddata is not installed for {}. Installed languages: {}",
+            missing.join(", "),
+            installed.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Languages tesseract has traineddata for, listed once per run
+fn installed_languages() -> &'static [String] {
+    static INSTALLED: OnceLock<Vec<String>> = OnceLock::new();
+    INSTALLED.get_or_init(|| {
+        rusty_tesseract::get_tesseract_langs().unwrap_or_else(|e| {
+            log::debug!("Unable to list the tesseract languages: {}", e);
+            Vec::new()
+        })
+    })
+}
+
+/// Version of tesseract, looked up once per run, or empty when it can't be
+pub fn tesseract_version() -> &'static str {
+    static VERSION: OnceLock<String> = OnceLock::new();
+    VERSION.get_or_init(|| {
+        rusty_tesseract::get_tesseract_version().unwrap_or_else(|e| {
+            log::debug!("Unable to get the tesseract version: {}", e);
+            String::new()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image_to_text::ocr_text;
+    use rusty_tesseract::Data;
+
+    fn row(level: i32, block: i32, par: i32, line: i32, conf: f32, text: &str) -> Data {
+        Data {
+            level,
+            page_num: 1,
+            block_num: block,
+            par_num: par,
+            line_num: line,
+            word_num: 1,
+            left: 10,
+            top: 20,
+            width: 30,
+            height: 40,
+            conf,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_ocr_text() {
+        let data = [
+            row(1, 0, 0, 0, -1.0, ""),
+            row(4, 1, 1, 1, -1.0, ""),
+            row(5, 1, 1, 1, 90.0, "Total"),
+            row(5, 1, 1, 1, 80.0, "due"),
+            row(5, 1, 1, 2, 70.0, "today"),
+            row(5, 1, 1, 2, 95.0, " "),
+            row(5, 2, 1, 1, 40.0, "Thanks"),
+        ];
+
+        let ocr = ocr_text(&data, (100, 50));
+        assert_eq!(ocr.text, "Total due\ntoday\n\nThanks");
+        assert_eq!(ocr.words.len(), 4);
+        assert_eq!(ocr.words[3].text, "Thanks");
+        assert_eq!(ocr.words[3].confidence, 40.0);
+        assert_eq!(ocr.confidence, Some(70.0));
 
-    let img = Image::from_dynamic_image(&image)?;
-    let default_args = Args::default();
+        assert_eq!(ocr.image_size, (100, 50));
 
-    Ok(rusty_tesseract::image_to_string(&img, &default_args)?)
+        assert_eq!(ocr_text(&data[..2], (100, 50)).confidence, None);
+    }
 }
@@ -1,41 +1,169 @@
-use crate::database::Database;
-use crate::index_pdf::index_pdf;
-use crate::search_index::search_index;
+use crate::config::{collection_config_path, global_config_path, Config};
+use crate::database::{
+    delete_chunks, delete_document, delete_document_chunks, get_annotations, get_document_pages,
+    get_headings, get_metadata, get_pages, insert_settings, update_page_error, update_page_ocr,
+    Database, FailedPage, OcrPage, SearchFilter, StoredPage,
+};
+use crate::document_source::{
+    is_container, is_supported, may_use_ocr, open_document, open_documents, page_image, SourcePage,
+    MEMBER_SEPARATOR,
+};
+use crate::image_to_text::{check_ocr_languages, image_ocr, OcrWord};
+use crate::index_document::{index_annotations, index_document, index_document_chunks};
+use crate::language::detect_language;
+use crate::page_regions::{highlight_rects, page_bounds, Rect};
+use crate::pdf_annotate::annotate_hit;
+use crate::pdf_password::{load_pdf, EncryptedPdf};
+use crate::search_index::{search_index, SearchResult};
 use pdfium_render::prelude::*;
 
-use crate::lazy_init::DATA_DIR;
-use crate::vector_index;
-use crate::vector_index::load_vector_index;
-use std::path::PathBuf;
+use crate::vector_index::VectorIndexes;
+use ignore::WalkBuilder;
+use rusqlite::Transaction;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
 const DB_NAME: &str = "db.sqlite";
 
-pub fn index_files(files: &Vec<String>) -> anyhow::Result<()> {
-    log::debug!("Data directory: {}", &DATA_DIR.display());
+/// Number of the least certain words shown for each page of the low confidence report
+const UNCERTAIN_WORDS_SHOWN: usize = 10;
+
+pub fn index_files(files: &Vec<String>, data_dir: &Path, config: &Config) -> anyhow::Result<()> {
+    log::debug!("Data directory: {}", data_dir.display());
 
     // Load sqlite database
-    let mut db = get_db(&DATA_DIR)?;
-    let index = load_vector_index(&DATA_DIR)?;
+    let mut db = get_db(data_dir)?;
+
+    // Record the index settings when the collection is created
+    if db.get_settings()?.is_empty() && !db.has_documents()? {
+        let tx = db.conn.transaction()?;
+        insert_settings(&tx, &config.index_settings()?)?;
+        tx.commit()?;
+    }
+
+    let config = &collection_config(&db, config)?;
+    let paths = input_paths(files);
+    // Files that are never read with OCR are indexed whether tesseract is set up or not
+    if paths.iter().any(|path| may_use_ocr(path, config)) {
+        check_ocr_languages(config)?;
+    }
+    if config.multilingual_model()?.is_some() && !config.detect_language.value {
+        log::warn!(
+            "embedding.multilingual_model is only used for pages when language.detect is enabled"
+        );
+    }
+    let indexes = VectorIndexes::load(data_dir, config)?;
     let pdfium = Pdfium::new(Pdfium::bind_to_statically_linked_library()?);
 
-    for file in files.iter() {
-        let path = PathBuf::from(file);
-        if !path.exists() {
-            log::warn!("File {} does not exists", path.display());
-            continue;
-        }
+    // Encrypted files and attachments no password opens are listed at the end
+    let mut encrypted = Vec::new();
+    let mut encrypted_attachments = Vec::new();
 
+    for path in paths {
+        // Normalize path
         let path = path.canonicalize()?;
-        if db.document_exists(&path.to_str().unwrap())? {
+        // Files indexed as a single document are skipped before they are opened
+        if db.document_exists(&path.to_string_lossy())? {
             log::warn!("File {} is already indexed", path.display());
             continue;
         }
 
-        index_pdf(&pdfium, &path, &mut db, &index)
-            .unwrap_or_else(|e| log::error!("Error indexing file {}: {}", path.display(), e));
+        let documents = match open_documents(&pdfium, &path, config) {
+            Ok(documents) => documents,
+            Err(e) => {
+                report_error(&mut encrypted, &path.to_string_lossy(), e);
+                continue;
+            }
+        };
+
+        // Members are only removed when every member of the archive or mailbox was read
+        let mut document_paths = HashSet::new();
+        let mut complete = true;
+        for document in documents {
+            let document = match document {
+                Ok(document) => document,
+                Err(e) => {
+                    report_error(&mut encrypted, &path.to_string_lossy(), e);
+                    complete = false;
+                    break;
+                }
+            };
+            document_paths.insert(document.path.clone());
+
+            // Archive members and messages are indexed again when their contents change
+            match (db.find_document(&document.path)?, &document.content_hash) {
+                (Some((_, Some(indexed))), Some(hash)) if &indexed == hash => {
+                    log::info!("Document {} is unchanged", document.path);
+                    continue;
+                }
+                (Some((document_id, _)), Some(_)) => {
+                    log::info!("Document {} has changed, indexing it again", document.path);
+                    remove_document(&mut db, &indexes, document_id)?;
+                }
+                (Some(_), None) => {
+                    log::warn!("Document {} is already indexed", document.path);
+                    continue;
+                }
+                (None, _) => {}
+            }
+
+            let document_path = document.path.clone();
+            let content_hash = document.content_hash.clone();
+            let indexed = document.open().and_then(|mut source| {
+                index_document(
+                    source.as_mut(),
+                    &document_path,
+                    content_hash.as_deref(),
+                    &mut db,
+                    &indexes,
+                    data_dir,
+                    config,
+                )?;
+                Ok(source.encrypted_attachments())
+            });
+            match indexed {
+                Ok(attachments) => encrypted_attachments.extend(attachments),
+                Err(e) => report_error(&mut encrypted, &document_path, e),
+            }
+        }
+
+        // Members that were removed from an archive or mailbox are removed from the index
+        if complete && is_container(&path) {
+            for (document_id, document_path) in db.member_documents(&path.to_string_lossy())? {
+                if !document_paths.contains(&document_path) {
+                    log::info!(
+                        "Document {} was removed from {}",
+                        document_path,
+                        path.display()
+                    );
+                    remove_document(&mut db, &indexes, document_id)?;
+                }
+            }
+        }
     }
 
-    vector_index::save_vector_index(&index)?;
+    indexes.save(data_dir)?;
+
+    if !encrypted.is_empty() {
+        println!(
+            "Skipped {} encrypted PDF files that no password opens. Give passwords with \
+             --password-file, --ask-password or --password and index them again:",
+            encrypted.len()
+        );
+        for path in encrypted {
+            println!("  {}", path);
+        }
+    }
+    if !encrypted_attachments.is_empty() {
+        println!(
+            "Skipped {} encrypted PDF attachments that no password opens. Their messages were \
+             indexed without them:",
+            encrypted_attachments.len()
+        );
+        for path in encrypted_attachments {
+            println!("  {}", path);
+        }
+    }
 
     // search_index("Conclusion or Insights of results", &db, &index)?;
 
@@ -44,14 +172,77 @@ pub fn index_files(files: &Vec<String>) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn search_with_query(query: &str) -> anyhow::Result<()> {
+/// Logs an error indexing a document, adding encrypted files no password opens to the list
+/// reported at the end of the run
+fn report_error(encrypted: &mut Vec<String>, path: &str, e: anyhow::Error) {
+    match e.downcast_ref::<EncryptedPdf>() {
+        Some(e) => {
+            log::warn!("Skipping {}", e);
+            encrypted.push(path.to_string());
+        }
+        None => log::error!("Error indexing file {}: {}", path, e),
+    }
+}
+
+/// Removes a document from the database along with the embeddings of its chunks
+fn remove_document(
+    db: &mut Database,
+    indexes: &VectorIndexes,
+    document_id: i64,
+) -> anyhow::Result<()> {
+    let tx = db.conn.transaction()?;
+    for chunk_id in delete_document(&tx, document_id)? {
+        indexes.remove(chunk_id)?;
+    }
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Lists the files to index, with directories replaced by the supported files within them.
+/// Directories are walked the way git would, skipping hidden and ignored files.
+fn input_paths(files: &[String]) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for file in files {
+        let path = PathBuf::from(file);
+        if !path.exists() {
+            log::warn!("File {} does not exists", path.display());
+            continue;
+        }
+        if !path.is_dir() {
+            paths.push(path);
+            continue;
+        }
+
+        for entry in WalkBuilder::new(&path).build() {
+            match entry {
+                Ok(entry) => {
+                    let is_file = entry.file_type().is_some_and(|kind| kind.is_file());
+                    if is_file && is_supported(entry.path()) {
+                        paths.push(entry.into_path());
+                    }
+                }
+                Err(e) => log::warn!("Error reading directory {}: {}", path.display(), e),
+            }
+        }
+    }
+    paths
+}
+
+pub fn search_with_query(
+    query: &str,
+    filter: &SearchFilter,
+    data_dir: &Path,
+    config: &Config,
+) -> anyhow::Result<()> {
     use colored::*;
 
     // Load sqlite database
-    let db = get_db(&DATA_DIR)?;
-    let index = load_vector_index(&DATA_DIR)?;
+    let db = get_db(data_dir)?;
+    let config = &collection_config(&db, config)?;
+    let indexes = VectorIndexes::load(data_dir, config)?;
 
-    for item in search_index(query, &db, &index)? {
+    for item in search_index(query, filter, &db, &indexes, config)? {
         let item = item?;
         println!(
             "{}",
@@ -59,11 +250,129 @@ pub fn search_with_query(query: &str) -> anyhow::Result<()> {
                 .red()
         );
 
+        // Text based documents are located by line, others by page
+        let location = match item.line_no {
+            Some(line_no) => format!("{}:{}", item.path, line_no).green(),
+            None => item.path.green(),
+        };
+
+        // Pages with a title, such as chapters of books, are shown by title instead of number,
+        // and source code by the lines of the chunk
+        let pages = match (&item.page_title, &item.metadata.title) {
+            (Some(page_title), Some(title)) => format!("{} — {}", title, page_title).blue(),
+            (Some(page_title), None) => page_title.blue(),
+            (None, _) if item.metadata.code_language.is_some() => format!(
+                "Lines: {}",
+                format!(
+                    "{}-{}",
+                    item.line_no.unwrap_or_default(),
+                    item.end_line_no.unwrap_or_default()
+                )
+                .blue()
+            )
+            .normal(),
+            (None, _) if item.page_no == item.end_page_no => {
+                format!("Page No: {}", item.page_no.to_string().blue()).normal()
+            }
+            (None, _) => format!(
+                "Pages: {}",
+                format!("{}-{}", item.page_no, item.end_page_no).blue()
+            )
+            .normal(),
+        };
+
+        let title = match (&item.metadata.title, &item.page_title) {
+            (Some(title), None) => format!("\nTitle: {}", title.cyan()),
+            _ => String::new(),
+        };
+        let author = item
+            .metadata
+            .author
+            .map(|author| format!("\nAuthor: {}", author.cyan()))
+            .unwrap_or_default();
+        let date = item
+            .metadata
+            .date
+            .map(|date| format!("\nDate: {}", date.cyan()))
+            .unwrap_or_default();
+        let subject = item
+            .metadata
+            .subject
+            .map(|subject| format!("\nSubject: {}", subject.cyan()))
+            .unwrap_or_default();
+        let keywords = item
+            .metadata
+            .keywords
+            .map(|keywords| format!("\nKeywords: {}", keywords.cyan()))
+            .unwrap_or_default();
+        // Chunks of comments users added to a page are told apart from the page text
+        let annotation = item
+            .annotation
+            .map(|annotation| {
+                let author = annotation
+                    .author
+                    .map(|author| format!(" by {}", author))
+                    .unwrap_or_default();
+                format!(
+                    "\nAnnotation: {}",
+                    format!("{}{}", annotation.kind, author).magenta()
+                )
+            })
+            .unwrap_or_default();
+        let language = item
+            .language
+            .map(|language| format!("\nLanguage: {}", language.cyan()))
+            .unwrap_or_default();
+        // Chunks of poorly recognized pages may be garbled or miss words
+        let confidence = item
+            .confidence
+            .filter(|confidence| *confidence < config.ocr_min_confidence.value)
+            .map(|confidence| {
+                format!(
+                    "\nLow OCR confidence: {}",
+                    format!("{:.0}%", confidence).red()
+                )
+            })
+            .unwrap_or_default();
+        // Chunks of pages read with OCR are located by the box around their words on each page
+        let regions: String = page_bounds(&item.regions)
+            .iter()
+            .map(|region| {
+                format!(
+                    "\nRegion: page {} at {}",
+                    region.page_no,
+                    format!(
+                        "x {:.0}% y {:.0}%, {:.0}% x {:.0}%",
+                        region.rect.x * 100.0,
+                        region.rect.y * 100.0,
+                        region.rect.width * 100.0,
+                        region.rect.height * 100.0
+                    )
+                    .blue()
+                )
+            })
+            .collect();
+
+        let heading = item
+            .heading
+            .map(|heading| format!("\nSection: {}", heading.yellow()))
+            .unwrap_or_default();
+
         println!(
-            "{}\nDistance: {}\nPage No: {}\n\n{}",
-            item.path.green(),
+            "{}{}{}{}{}{}{}{}{}\nDistance: {}\n{}{}{}\n\n{}",
+            location,
+            title,
+            author,
+            date,
+            subject,
+            keywords,
+            annotation,
+            language,
+            confidence,
             item.distance.to_string().bright_red(),
-            item.page_no.to_string().blue(),
+            pages,
+            regions,
+            heading,
             item.text
         );
     }
@@ -71,10 +380,512 @@ pub fn search_with_query(query: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn get_db(data_dir: &PathBuf) -> anyhow::Result<Database> {
+/// Searches the collection and renders the pages the hits cover to PNG files in the output
+/// directory, with the regions of the hits highlighted. Only hits on pages of PDF and image
+/// files read with OCR can be shown.
+pub fn highlight_search_hits(
+    query: &str,
+    filter: &SearchFilter,
+    output_dir: &Path,
+    data_dir: &Path,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let db = get_db(data_dir)?;
+    let config = &collection_config(&db, config)?;
+    let indexes = VectorIndexes::load(data_dir, config)?;
+    let pdfium = Pdfium::new(Pdfium::bind_to_statically_linked_library()?);
+    std::fs::create_dir_all(output_dir)?;
+
+    for (rank, item) in search_index(query, filter, &db, &indexes, config)?.enumerate() {
+        let item = item?;
+        if item.regions.is_empty() {
+            log::warn!("Hit {} in {} has no regions to show", rank + 1, item.path);
+            continue;
+        }
+
+        let page_nos: BTreeSet<usize> = item.regions.iter().map(|region| region.page_no).collect();
+        for page_no in page_nos {
+            let image = match page_image(&pdfium, &item.path, page_no, config) {
+                Ok(Some(image)) => image,
+                Ok(None) => {
+                    log::warn!("Page {} of {} can't be rendered", page_no, item.path);
+                    continue;
+                }
+                Err(e) => {
+                    log::error!("Error rendering page {} of {}: {}", page_no, item.path, e);
+                    continue;
+                }
+            };
+
+            let rects: Vec<Rect> = item
+                .regions
+                .iter()
+                .filter(|region| region.page_no == page_no)
+                .map(|region| region.rect)
+                .collect();
+            let path = output_dir.join(format!("hit_{}_page_{}.png", rank + 1, page_no));
+            highlight_rects(&image, &rects).save(&path)?;
+            println!("{}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Searches the collection and writes a copy of each PDF file with hits to the output directory,
+/// or with `combined` a single PDF of just the pages with hits. Hits are highlighted, with a note
+/// giving their rank and distance. Hits in other files, or without regions, are skipped.
+pub fn export_search_hits(
+    query: &str,
+    filter: &SearchFilter,
+    output_dir: &Path,
+    combined: bool,
+    data_dir: &Path,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let db = get_db(data_dir)?;
+    let config = &collection_config(&db, config)?;
+    let indexes = VectorIndexes::load(data_dir, config)?;
+    let pdfium = Pdfium::new(Pdfium::bind_to_statically_linked_library()?);
+    std::fs::create_dir_all(output_dir)?;
+
+    // The hits in each PDF file, in the order the files are first hit
+    let mut files: Vec<(String, Vec<(usize, SearchResult)>)> = Vec::new();
+    for (rank, item) in search_index(query, filter, &db, &indexes, config)?.enumerate() {
+        let item = item?;
+        let is_pdf = !item.path.contains(MEMBER_SEPARATOR)
+            && Path::new(&item.path)
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("pdf"));
+        if !is_pdf {
+            log::warn!("Hit {} in {} is not in a PDF file", rank + 1, item.path);
+            continue;
+        }
+        if item.regions.is_empty() {
+            log::warn!("Hit {} in {} has no regions to show", rank + 1, item.path);
+            continue;
+        }
+
+        match files.iter_mut().find(|(path, _)| *path == item.path) {
+            Some((_, hits)) => hits.push((rank, item)),
+            None => files.push((item.path.clone(), vec![(rank, item)])),
+        }
+    }
+
+    let mut output = pdfium.create_new_pdf()?;
+    for (path, hits) in &files {
+        let document = load_pdf(&pdfium, Path::new(path))?;
+
+        // Index of each page with hits in the combined document
+        let mut copied: HashMap<usize, u16> = HashMap::new();
+        for (rank, item) in hits {
+            let note = format!("Hit {}, distance {}", rank + 1, item.distance);
+            for bound in page_bounds(&item.regions) {
+                let rects: Vec<Rect> = item
+                    .regions
+                    .iter()
+                    .filter(|region| region.page_no == bound.page_no)
+                    .map(|region| region.rect)
+                    .collect();
+
+                let mut page = if combined {
+                    let index = match copied.get(&bound.page_no) {
+                        Some(index) => *index,
+                        None => {
+                            let index = output.pages().len();
+                            let source_index = u16::try_from(bound.page_no)?;
+                            output.pages_mut().copy_page_from_document(
+                                &document,
+                                source_index,
+                                index,
+                            )?;
+                            copied.insert(bound.page_no, index);
+                            index
+                        }
+                    };
+                    output.pages().get(index)?
+                } else {
+                    document.pages().get(u16::try_from(bound.page_no)?)?
+                };
+                annotate_hit(&mut page, &rects, &note)?;
+            }
+        }
+
+        if !combined {
+            let stem = Path::new(path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let output_path = output_dir.join(format!("hit_{}_{}.pdf", hits[0].0 + 1, stem));
+            document.save_to_file(&output_path)?;
+            println!("{}", output_path.display());
+        }
+    }
+
+    if combined {
+        if output.pages().is_empty() {
+            println!("No hits in PDF files to export");
+            return Ok(());
+        }
+        let output_path = output_dir.join("hits.pdf");
+        output.save_to_file(&output_path)?;
+        println!("{}", output_path.display());
+    }
+
+    Ok(())
+}
+
+/// Re-chunks and re-embeds every page of a collection from the stored page text with the
+/// index settings it was indexed with, changed by those given on the command line, and
+/// records them as the new index settings
+pub fn rechunk_collection(data_dir: &Path, config: &Config) -> anyhow::Result<()> {
+    let mut db = get_db(data_dir)?;
+    let mut config = config.clone();
+    config.merge_index_settings_below_command_line(&stored_settings(&db)?)?;
+    let config = &config;
+
+    // Start from empty vector indexes as the embedding models may have changed
+    let indexes = VectorIndexes::new(config)?;
+
+    let tx = db.conn.transaction()?;
+    delete_chunks(&tx)?;
+
+    let pages = get_pages(&tx)?;
+    let documents: Vec<&[StoredPage]> = pages.chunk_by(|a, b| a.0 == b.0).collect();
+    for (document_no, document_pages) in documents.iter().enumerate() {
+        log::info!(
+            "Re-chunking document {} of {}",
+            document_no + 1,
+            documents.len()
+        );
+        rechunk_document(&tx, document_pages, &indexes, config)?;
+    }
+
+    insert_settings(&tx, &config.index_settings()?)?;
+
+    indexes.save(data_dir)?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Chunks and embeds the stored pages of a document
+fn rechunk_document(
+    tx: &Transaction,
+    document_pages: &[StoredPage],
+    indexes: &VectorIndexes,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let Some((document_id, ..)) = document_pages.first() else {
+        return Ok(());
+    };
+
+    let headings = get_headings(tx, *document_id)?;
+    let code = get_metadata(tx, *document_id)?.code_language.is_some();
+    let languages: HashMap<i64, String> = document_pages
+        .iter()
+        .filter_map(|(_, page_id, _, language)| Some((*page_id, language.clone()?)))
+        .collect();
+    let document_pages: Vec<(i64, String)> = document_pages
+        .iter()
+        .map(|(_, page_id, text, _)| (*page_id, text.clone()))
+        .collect();
+
+    index_document_chunks(
+        tx,
+        &document_pages,
+        &headings,
+        code,
+        &languages,
+        indexes,
+        config,
+    )?;
+
+    let annotations = get_annotations(tx, *document_id)?;
+    index_annotations(tx, &annotations, &languages, indexes, config)
+}
+
+/// Lists the pages read with OCR whose confidence is below `ocr.min_confidence`, along with
+/// their least certain words. With `retry` the pages are read again with the current OCR and
+/// render settings.
+pub fn low_confidence_report(data_dir: &Path, config: &Config, retry: bool) -> anyhow::Result<()> {
+    use colored::*;
+
+    let mut db = get_db(data_dir)?;
+    let config = &collection_config(&db, config)?;
+    let min_confidence = config.ocr_min_confidence.value;
+
+    let pages = db.low_confidence_pages(min_confidence)?;
+    if pages.is_empty() {
+        println!("No pages below {:.0}% OCR confidence", min_confidence);
+        return Ok(());
+    }
+
+    for page in &pages {
+        let mut uncertain: Vec<&OcrWord> = page
+            .words
+            .iter()
+            .filter(|word| word.confidence < min_confidence)
+            .collect();
+        uncertain.sort_by(|a, b| a.confidence.total_cmp(&b.confidence));
+        let uncertain: Vec<&str> = uncertain
+            .iter()
+            .take(UNCERTAIN_WORDS_SHOWN)
+            .map(|word| word.text.as_str())
+            .collect();
+
+        println!(
+            "{} Page No: {} Confidence: {}",
+            page.path.green(),
+            page.page_no.to_string().blue(),
+            format!("{:.0}%", page.confidence).red()
+        );
+        if !uncertain.is_empty() {
+            println!("    Uncertain words: {}", uncertain.join(", ").yellow());
+        }
+    }
+
+    if retry {
+        retry_ocr(&mut db, &pages, data_dir, config)?;
+    }
+
+    Ok(())
+}
+
+/// Reads pages again with OCR, keeping the new text of the pages whose confidence improves
+/// and re-chunking their documents
+fn retry_ocr(
+    db: &mut Database,
+    pages: &[OcrPage],
+    data_dir: &Path,
+    config: &Config,
+) -> anyhow::Result<()> {
+    check_ocr_languages(config)?;
+    let indexes = VectorIndexes::load(data_dir, config)?;
+    let pdfium = Pdfium::new(Pdfium::bind_to_statically_linked_library()?);
+
+    let tx = db.conn.transaction()?;
+    let mut improved = BTreeSet::new();
+    for page in pages {
+        let ocr = match page_image(&pdfium, &page.path, page.page_no, config) {
+            Ok(Some(image)) => image_ocr(&image, config),
+            Ok(None) => {
+                log::warn!("Page {} of {} can't be read again", page.page_no, page.path);
+                continue;
+            }
+            Err(e) => Err(e),
+        };
+        let ocr = match ocr {
+            Ok(ocr) => ocr,
+            Err(e) => {
+                log::error!(
+                    "Error reading page {} of {}: {}",
+                    page.page_no,
+                    page.path,
+                    e
+                );
+                continue;
+            }
+        };
+
+        let confidence = ocr.confidence.unwrap_or_default();
+        if confidence <= page.confidence {
+            log::info!(
+                "Page {} of {} did not improve ({:.0}%), keeping its text",
+                page.page_no,
+                page.path,
+                confidence
+            );
+            continue;
+        }
+
+        log::info!(
+            "Page {} of {} improved from {:.0}% to {:.0}%",
+            page.page_no,
+            page.path,
+            page.confidence,
+            confidence
+        );
+        update_page_ocr(&tx, page.page_id, &SourcePage::from(ocr))?;
+        improved.insert(page.document_id);
+    }
+
+    rechunk_documents(&tx, improved, &indexes, config)?;
+    indexes.save(data_dir)?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Lists the pages that could not be read along with their error. With `retry` the pages are
+/// read again, and the documents of the pages that are read are re-chunked.
+pub fn failures_report(data_dir: &Path, config: &Config, retry: bool) -> anyhow::Result<()> {
+    use colored::*;
+
+    let mut db = get_db(data_dir)?;
+    let config = &collection_config(&db, config)?;
+
+    let pages = db.failed_pages()?;
+    if pages.is_empty() {
+        println!("No pages failed to be read");
+        return Ok(());
+    }
+
+    for page in &pages {
+        println!(
+            "{} Page No: {}",
+            page.path.green(),
+            page.page_no.to_string().blue()
+        );
+        println!("    Error: {}", page.error.red());
+    }
+
+    if retry {
+        retry_failed_pages(&mut db, &pages, data_dir, config)?;
+    }
+
+    Ok(())
+}
+
+/// Reads failed pages again, storing the text of the pages that are read and re-chunking their
+/// documents. Pages that fail again keep their new error.
+fn retry_failed_pages(
+    db: &mut Database,
+    pages: &[FailedPage],
+    data_dir: &Path,
+    config: &Config,
+) -> anyhow::Result<()> {
+    if pages
+        .iter()
+        .any(|page| may_use_ocr(Path::new(&page.path), config))
+    {
+        check_ocr_languages(config)?;
+    }
+    let indexes = VectorIndexes::load(data_dir, config)?;
+    let pdfium = Pdfium::new(Pdfium::bind_to_statically_linked_library()?);
+
+    let tx = db.conn.transaction()?;
+    let mut read = BTreeSet::new();
+    for page in pages {
+        match read_page(&pdfium, &page.path, page.page_no, config) {
+            Ok(Some(mut source_page)) => {
+                if config.detect_language.value && source_page.language.is_none() {
+                    source_page.language = detect_language(&source_page.text);
+                }
+                log::info!("Read page {} of {}", page.page_no, page.path);
+                update_page_ocr(&tx, page.page_id, &source_page)?;
+                read.insert(page.document_id);
+            }
+            Ok(None) => {
+                log::warn!("Page {} of {} can't be read again", page.page_no, page.path);
+            }
+            Err(e) => {
+                log::error!(
+                    "Error reading page {} of {}: {}",
+                    page.page_no,
+                    page.path,
+                    e
+                );
+                update_page_error(&tx, page.page_id, &e.to_string())?;
+            }
+        }
+    }
+
+    let documents = read.len();
+    rechunk_documents(&tx, read, &indexes, config)?;
+    indexes.save(data_dir)?;
+    tx.commit()?;
+
+    println!(
+        "Read {} of {} failed pages, in {} documents",
+        pages.len() - db.failed_pages()?.len(),
+        pages.len(),
+        documents
+    );
+
+    Ok(())
+}
+
+/// Reads a single page of a document again, rendering it for OCR for PDF and image files and
+/// otherwise reading the document up to the page. Returns None for documents within another
+/// file, such as archive members, which can't be opened on their own.
+fn read_page(
+    pdfium: &Pdfium,
+    path: &str,
+    page_no: usize,
+    config: &Config,
+) -> anyhow::Result<Option<SourcePage>> {
+    if let Some(image) = page_image(pdfium, path, page_no, config)? {
+        return Ok(Some(SourcePage::from(image_ocr(&image, config)?)));
+    }
+    if path.contains(MEMBER_SEPARATOR) {
+        return Ok(None);
+    }
+
+    let mut source = open_document(pdfium, Path::new(path), config)?;
+    match source.nth(page_no) {
+        Some(page) => Ok(Some(page?)),
+        None => anyhow::bail!("The document has no page {}", page_no),
+    }
+}
+
+/// Deletes the chunks of documents and chunks their stored pages again
+fn rechunk_documents(
+    tx: &Transaction,
+    document_ids: impl IntoIterator<Item = i64>,
+    indexes: &VectorIndexes,
+    config: &Config,
+) -> anyhow::Result<()> {
+    for document_id in document_ids {
+        for chunk_id in delete_document_chunks(tx, document_id)? {
+            indexes.remove(chunk_id)?;
+        }
+        rechunk_document(tx, &get_document_pages(tx, document_id)?, indexes, config)?;
+    }
+
+    Ok(())
+}
+
+/// Prints the effective configuration and the config files it was read from
+pub fn show_config(data_dir: &Path, config: &Config) -> anyhow::Result<()> {
+    let config = &collection_config(&get_db(data_dir)?, config)?;
+
+    if let Some(path) = global_config_path() {
+        println!("# Global config: {}", path.display());
+    }
+    match collection_config_path(data_dir) {
+        Some(path) => println!("# Collection config: {}\n", path.display()),
+        None => println!("# Collection config: the global config\n"),
+    }
+    print!("{}", config.to_annotated_toml()?);
+
+    Ok(())
+}
+
+fn get_db(data_dir: &Path) -> anyhow::Result<Database> {
     let db_path = data_dir.join(DB_NAME);
     let db = Database::new(&db_path)?;
     db.init_tables()?;
 
     Ok(db)
 }
+
+/// Applies the index settings recorded in the collection database to the configuration
+fn collection_config(db: &Database, config: &Config) -> anyhow::Result<Config> {
+    let mut config = config.clone();
+    config.merge_index_settings(&stored_settings(db)?)?;
+
+    Ok(config)
+}
+
+/// Returns the index settings recorded in the collection database
+fn stored_settings(db: &Database) -> anyhow::Result<Vec<(String, String)>> {
+    let stored = db.get_settings()?;
+
+    // Collections indexed before settings were recorded used the defaults
+    if stored.is_empty() && db.has_documents()? {
+        return Config::default().index_settings();
+    }
+
+    Ok(stored)
+}
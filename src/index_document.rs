@@ -0,0 +1,398 @@
+use crate::chunk_code::code_chunk_indices;
+use crate::chunk_sections::{section_indices, with_heading, Heading};
+use crate::chunk_text::{
+    concatenate_pages, grapheme_count, locate_in_pages, paragraph_chunk_indices,
+    sentence_chunk_indices, sliding_window_chunk_indices, token_count, token_window_chunk_indices,
+    untruncated_tokenizer, ChunkStrategy, ChunkUnit,
+};
+use crate::config::Config;
+use crate::database::{
+    insert_annotation, insert_chunks, insert_document, insert_failed_page, insert_headings,
+    insert_page, ChunkLocation, Database, StoredAnnotation, PAGE_SEPARATOR,
+};
+use crate::document_source::DocumentSource;
+use crate::generate_embeddings::{generate_embeddings, max_chunk_tokens};
+use crate::language::detect_language;
+use crate::lazy_init::text_embedding;
+use crate::vector_index::{EmbeddingIndex, VectorIndexes};
+use fastembed::EmbeddingModel;
+use rusqlite::Transaction;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Reads the pages of a document from its source, stores them and indexes their chunks.
+/// Pages that can't be read are stored without text along with their error, to be retried
+/// later, and the rest of the document is indexed.
+pub fn index_document(
+    source: &mut dyn DocumentSource,
+    path: &str,
+    content_hash: Option<&str>,
+    db: &mut Database,
+    indexes: &VectorIndexes,
+    data_dir: &Path,
+    config: &Config,
+) -> anyhow::Result<()> {
+    log::info!("Indexing document at path {}", path);
+
+    let headings = source.headings();
+    let metadata = source.metadata();
+    let annotations = source.annotations();
+
+    let tx = db.conn.transaction()?;
+    let doc_id = insert_document(&tx, path, &metadata, content_hash)?;
+    insert_headings(&tx, doc_id, &headings)?;
+
+    let mut pages = Vec::new();
+    let mut languages = HashMap::new();
+    let mut failures = 0;
+    for (page_no, page) in source.enumerate() {
+        log::info!("Indexing page {} of {}", page_no, path);
+        let mut page = match page {
+            Ok(page) => page,
+            Err(e) => {
+                log::error!("Error reading page {} of {}: {}", page_no, path, e);
+                let page_id = insert_failed_page(&tx, doc_id, page_no, &e.to_string())?;
+                pages.push((page_id, String::new()));
+                failures += 1;
+                continue;
+            }
+        };
+        // Pages read without OCR are detected from their text
+        if config.detect_language.value && page.language.is_none() {
+            page.language = detect_language(&page.text);
+        }
+
+        let page_id = insert_page(&tx, doc_id, page_no, &page)?;
+        if let Some(language) = page.language {
+            languages.insert(page_id, language);
+        }
+        pages.push((page_id, page.text));
+    }
+
+    // Annotations are stored with the page they are on, and indexed as chunks of their own
+    let mut stored_annotations = Vec::new();
+    for annotation in &annotations {
+        let Some((page_id, _)) = pages.get(annotation.page_no) else {
+            continue;
+        };
+        let annotation_id = insert_annotation(&tx, doc_id, *page_id, annotation)?;
+        stored_annotations.push((annotation_id, *page_id, annotation.text.clone()));
+    }
+
+    let code = metadata.code_language.is_some();
+    index_document_chunks(&tx, &pages, &headings, code, &languages, indexes, config)?;
+    index_annotations(&tx, &stored_annotations, &languages, indexes, config)?;
+
+    indexes.save(data_dir)?;
+    tx.commit()?;
+
+    if failures > 0 {
+        log::warn!(
+            "{} pages of {} could not be read, list them with the failures command",
+            failures,
+            path
+        );
+    }
+
+    Ok(())
+}
+
+/// Splits the pages of a document into chunks, stores them and adds their embeddings to the index.
+/// Pages are chunked one by one, or as a whole when chunks may span pages or follow sections.
+/// Source code is chunked at its blocks regardless of the configured strategy.
+/// Chunks are embedded in the index for the language of their page, and measured with the
+/// tokenizer of its model so they are never truncated when embedded.
+pub fn index_document_chunks(
+    tx: &Transaction,
+    pages: &[(i64, String)],
+    headings: &[Heading],
+    code: bool,
+    languages: &HashMap<i64, String>,
+    indexes: &VectorIndexes,
+    config: &Config,
+) -> anyhow::Result<()> {
+    if pages.is_empty() {
+        return Ok(());
+    }
+
+    let page_index =
+        |page_id: &i64| indexes.for_language(languages.get(page_id).map(String::as_str));
+
+    if code {
+        for (page_id, text) in pages {
+            let embedding_index = page_index(page_id);
+            let (measure, max_size) = chunk_measure(&embedding_index.model, config)?;
+            let chunks: Vec<((usize, usize), ChunkLocation)> =
+                code_chunk_indices(text, max_size, &measure)
+                    .into_iter()
+                    .map(|(start, end, heading)| {
+                        let location = ChunkLocation {
+                            start_page_id: *page_id,
+                            start,
+                            end_page_id: *page_id,
+                            end,
+                            heading,
+                            annotation_id: None,
+                        };
+                        ((start, end), location)
+                    })
+                    .collect();
+
+            embed_chunks(tx, text, &chunks, embedding_index, config)?;
+        }
+        return Ok(());
+    }
+
+    let by_section = config.chunk_strategy.value == ChunkStrategy::Section;
+    if by_section || config.span_pages.value {
+        let texts: Vec<&str> = pages.iter().map(|(_, text)| text.as_str()).collect();
+        let (text, page_ranges) = concatenate_pages(&texts, PAGE_SEPARATOR);
+
+        // Sections follow the headings of the whole document, while spanning pages chunks the
+        // document as a single section
+        let sections = if by_section {
+            section_indices(&text, &page_ranges, headings)
+        } else {
+            vec![(0, text.len(), None)]
+        };
+
+        // Consecutive pages embedded in the same index are chunked together with its model,
+        // so chunks never span pages of different indexes
+        let mut first_page = 0;
+        for run in pages.chunk_by(|(a, _), (b, _)| page_index(a).key == page_index(b).key) {
+            let last_page = first_page + run.len() - 1;
+            let run_range = (page_ranges[first_page].0, page_ranges[last_page].1);
+            first_page = last_page + 1;
+
+            let embedding_index = page_index(&run[0].0);
+            let model = &embedding_index.model;
+            let measure = by_section
+                .then(|| chunk_measure(model, config))
+                .transpose()?;
+
+            let mut chunks = Vec::new();
+            for (start, end, heading) in &sections {
+                let (start, end) = ((*start).max(run_range.0), (*end).min(run_range.1));
+                if start >= end {
+                    continue;
+                }
+
+                let section_chunks = match &measure {
+                    Some((measure, max_size)) => {
+                        // Leave room for the heading path prepended to each chunk
+                        let reserved = heading
+                            .as_ref()
+                            .map_or(0, |heading| measure(&with_heading(heading, "")));
+                        let max_size = max_size.saturating_sub(reserved).max(1);
+                        paragraph_chunk_indices(
+                            &text[start..end],
+                            max_size,
+                            config.sentence_overlap.value,
+                            measure,
+                        )
+                    }
+                    None => chunk_indices(&text[start..end], model, config)?,
+                };
+                for (from, to) in section_chunks {
+                    let chunk = (start + from, start + to);
+                    let location = page_chunk_location(pages, &page_ranges, chunk, heading.clone());
+                    chunks.push((chunk, location));
+                }
+            }
+
+            embed_chunks(tx, &text, &chunks, embedding_index, config)?;
+        }
+        return Ok(());
+    }
+
+    for (page_id, text) in pages {
+        let embedding_index = page_index(page_id);
+        let chunks: Vec<((usize, usize), ChunkLocation)> =
+            chunk_indices(text, &embedding_index.model, config)?
+                .into_iter()
+                .map(|(start, end)| {
+                    let location = ChunkLocation {
+                        start_page_id: *page_id,
+                        start,
+                        end_page_id: *page_id,
+                        end,
+                        heading: None,
+                        annotation_id: None,
+                    };
+                    ((start, end), location)
+                })
+                .collect();
+
+        embed_chunks(tx, text, &chunks, embedding_index, config)?;
+    }
+
+    Ok(())
+}
+
+/// Splits the text of each annotation of a document into chunks like the text of a page, stores
+/// them and adds their embeddings to the index for the language of the page the annotation is
+/// on. Most annotations are short enough to be a single chunk.
+pub fn index_annotations(
+    tx: &Transaction,
+    annotations: &[StoredAnnotation],
+    languages: &HashMap<i64, String>,
+    indexes: &VectorIndexes,
+    config: &Config,
+) -> anyhow::Result<()> {
+    for embedding_index in indexes.all() {
+        let annotations: Vec<&StoredAnnotation> = annotations
+            .iter()
+            .filter(|(_, page_id, _)| {
+                let language = languages.get(page_id).map(String::as_str);
+                indexes.for_language(language).key == embedding_index.key
+            })
+            .collect();
+        if annotations.is_empty() {
+            continue;
+        }
+
+        // The annotations are embedded in batches from their joined text, while the location
+        // of each chunk is within its own annotation
+        let texts: Vec<&str> = annotations
+            .iter()
+            .map(|(_, _, text)| text.as_str())
+            .collect();
+        let (text, ranges) = concatenate_pages(&texts, PAGE_SEPARATOR);
+        let mut chunks = Vec::new();
+        for ((annotation_id, page_id, annotation), (offset, _)) in annotations.iter().zip(ranges) {
+            for (start, end) in chunk_indices(annotation, &embedding_index.model, config)? {
+                let location = ChunkLocation {
+                    start_page_id: *page_id,
+                    start,
+                    end_page_id: *page_id,
+                    end,
+                    heading: None,
+                    annotation_id: Some(*annotation_id),
+                };
+                chunks.push(((offset + start, offset + end), location));
+            }
+        }
+
+        embed_chunks(tx, &text, &chunks, embedding_index, config)?;
+    }
+
+    Ok(())
+}
+
+/// Maps a chunk of the joined document text back to the pages it starts and ends in
+fn page_chunk_location(
+    pages: &[(i64, String)],
+    page_ranges: &[(usize, usize)],
+    chunk: (usize, usize),
+    heading: Option<String>,
+) -> ChunkLocation {
+    let ((start_page, start), (end_page, end)) = locate_in_pages(page_ranges, chunk);
+    ChunkLocation {
+        start_page_id: pages[start_page].0,
+        start,
+        end_page_id: pages[end_page].0,
+        end,
+        heading,
+        annotation_id: None,
+    }
+}
+
+/// Stores chunks of a text along with their location and adds their embeddings to an index
+fn embed_chunks(
+    tx: &Transaction,
+    text: &str,
+    chunks: &[((usize, usize), ChunkLocation)],
+    embedding_index: &EmbeddingIndex,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let batch_size = config.embedding_batch_size.value;
+    let index_db = &embedding_index.index;
+
+    for chunk_of_indices in chunks.chunks(batch_size) {
+        let mut text_chunks: Vec<String> = Vec::with_capacity(batch_size);
+        let mut chunk_locations: Vec<&ChunkLocation> = Vec::with_capacity(batch_size);
+
+        for ((start, end), location) in chunk_of_indices {
+            let chunk = &text[*start..*end];
+            text_chunks.push(match &location.heading {
+                Some(heading) => with_heading(heading, chunk),
+                None => chunk.to_string(),
+            });
+            chunk_locations.push(location);
+        }
+
+        let doc_ids = insert_chunks(tx, &chunk_locations)?;
+        let embeddings = generate_embeddings(
+            text_chunks.iter().map(String::as_str).collect(),
+            &embedding_index.model,
+            batch_size,
+        )?;
+
+        log::debug!(
+            "Inserting {} embeddings into vector database",
+            embeddings.len()
+        );
+
+        index_db.reserve(index_db.capacity() + doc_ids.len())?;
+        for (doc_id, embedding) in doc_ids.into_iter().zip(embeddings.into_iter()) {
+            index_db.add(doc_id.try_into()?, &embedding)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits text into chunks using the configured strategy and unit
+fn chunk_indices(
+    text: &str,
+    model: &EmbeddingModel,
+    config: &Config,
+) -> anyhow::Result<Vec<(usize, usize)>> {
+    let chunk_size = config.chunk_size.value;
+    let chunk_stride = config.chunk_stride.value;
+    let overlap = config.sentence_overlap.value;
+
+    let chunks = match (config.chunk_strategy.value, config.chunk_unit.value) {
+        (ChunkStrategy::SlidingWindow, ChunkUnit::Graphemes) => {
+            sliding_window_chunk_indices(text, chunk_size, chunk_stride).collect()
+        }
+        (ChunkStrategy::SlidingWindow, ChunkUnit::Tokens) => {
+            let tokenizer = &text_embedding(model)?.tokenizer;
+            let max_tokens = max_chunk_tokens(tokenizer)?;
+            token_window_chunk_indices(text, tokenizer, chunk_size, chunk_stride, max_tokens)?
+        }
+        (ChunkStrategy::Sentence, _) => {
+            let (measure, max_size) = chunk_measure(model, config)?;
+            sentence_chunk_indices(text, max_size, overlap, measure)
+        }
+        // Sections are split by the caller, the text within one is chunked like paragraphs
+        (ChunkStrategy::Paragraph | ChunkStrategy::Section, _) => {
+            let (measure, max_size) = chunk_measure(model, config)?;
+            paragraph_chunk_indices(text, max_size, overlap, measure)
+        }
+    };
+
+    Ok(chunks)
+}
+
+/// Measures the size of a text in the configured chunk unit
+type Measure = Box<dyn Fn(&str) -> usize>;
+
+/// Returns a function measuring text in the configured chunk unit, along with the
+/// maximum size of a chunk in that unit
+fn chunk_measure(model: &EmbeddingModel, config: &Config) -> anyhow::Result<(Measure, usize)> {
+    let chunk_size = config.chunk_size.value;
+
+    Ok(match config.chunk_unit.value {
+        ChunkUnit::Graphemes => (Box::new(grapheme_count), chunk_size),
+        ChunkUnit::Tokens => {
+            let tokenizer = &text_embedding(model)?.tokenizer;
+            let max_tokens = chunk_size.min(max_chunk_tokens(tokenizer)?);
+            let tokenizer = untruncated_tokenizer(tokenizer)?;
+            (
+                Box::new(move |text: &str| token_count(&tokenizer, text)),
+                max_tokens,
+            )
+        }
+    })
+}
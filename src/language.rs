@@ -0,0 +1,55 @@
+use whatlang::Lang;
+
+/// Number of characters of a page the language is detected from
+const SAMPLE_CHARS: usize = 2000;
+
+/// Tesseract language code of English, which the main embedding model is used for
+pub const ENGLISH: &str = "eng";
+
+/// Detects the language of a text, returning its tesseract language code, e.g. "deu".
+/// Returns None for text too short or mixed to tell reliably.
+pub fn detect_language(text: &str) -> Option<String> {
+    let sample: String = text.chars().take(SAMPLE_CHARS).collect();
+    let info = whatlang::detect(&sample)?;
+    if !info.is_reliable() {
+        return None;
+    }
+
+    Some(tesseract_language(info.lang()).to_string())
+}
+
+/// Tesseract names its traineddata by ISO 639-3 code, like whatlang, except for a few languages
+fn tesseract_language(lang: Lang) -> &'static str {
+    match lang {
+        Lang::Cmn => "chi_sim",
+        Lang::Nob => "nor",
+        Lang::Pes => "fas",
+        lang => lang.code(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::language::detect_language;
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(
+            detect_language("The quarterly report shows that revenue grew faster than expected.")
+                .as_deref(),
+            Some("eng")
+        );
+        assert_eq!(
+            detect_language(
+                "Der Quartalsbericht zeigt, dass der Umsatz schneller als erwartet gewachsen ist."
+            )
+            .as_deref(),
+            Some("deu")
+        );
+        assert_eq!(
+            detect_language("四半期報告書によると、売上高は予想よりも速く伸びました。").as_deref(),
+            Some("jpn")
+        );
+        assert_eq!(detect_language("12 34"), None);
+    }
+}
@@ -1,10 +1,13 @@
 use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
 use lazy_static::lazy_static;
+use std::collections::HashMap;
 use std::fs::create_dir_all;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
-pub const EMBEDDING_MODEL: EmbeddingModel = EmbeddingModel::BGEBaseENV15;
-const DATA_DIR_NAME: &str = "ninja.breakpoint.semantic_search_cli";
+pub const DATA_DIR_NAME: &str = "ninja.breakpoint.semantic_search_cli";
+pub const DEFAULT_COLLECTION: &str = "default";
+const COLLECTIONS_DIR_NAME: &str = "collections";
 
 lazy_static! {
     pub static ref DATA_DIR: PathBuf = {
@@ -14,11 +17,39 @@ lazy_static! {
         create_dir_all(data_dir.clone()).unwrap();
         data_dir
     };
-    pub static ref TEXT_EMBEDDING: TextEmbedding = {
-        let options = InitOptions::new(EMBEDDING_MODEL)
-            .with_cache_dir(DATA_DIR.clone())
-            .with_show_download_progress(true);
+    static ref TEXT_EMBEDDINGS: Mutex<HashMap<EmbeddingModel, Arc<TextEmbedding>>> =
+        Mutex::new(HashMap::new());
+}
 
-        TextEmbedding::try_new(options).unwrap()
-    };
+/// Returns the text embedding for the given model, loading it on first use
+pub fn text_embedding(model: &EmbeddingModel) -> anyhow::Result<Arc<TextEmbedding>> {
+    let mut text_embeddings = TEXT_EMBEDDINGS.lock().unwrap();
+    if let Some(text_embedding) = text_embeddings.get(model) {
+        return Ok(text_embedding.clone());
+    }
+
+    let options = InitOptions::new(model.clone())
+        .with_cache_dir(DATA_DIR.clone())
+        .with_show_download_progress(true);
+
+    let text_embedding = Arc::new(TextEmbedding::try_new(options)?);
+    text_embeddings.insert(model.clone(), text_embedding.clone());
+
+    Ok(text_embedding)
+}
+
+/// Returns the data directory of a collection, creating it if needed.
+/// The default collection lives directly in the data directory.
+pub fn collection_dir(name: &str) -> anyhow::Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(anyhow::anyhow!("Invalid collection name {}", name));
+    }
+
+    if name == DEFAULT_COLLECTION {
+        return Ok(DATA_DIR.clone());
+    }
+
+    let dir = DATA_DIR.join(COLLECTIONS_DIR_NAME).join(name);
+    create_dir_all(&dir)?;
+    Ok(dir)
 }
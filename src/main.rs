@@ -1,41 +1,181 @@
+mod chunk_code;
+mod chunk_sections;
 mod chunk_text;
+mod config;
 mod database;
+mod document_source;
 mod generate_embeddings;
+mod image_preprocess;
 mod image_to_text;
 mod index;
-mod index_pdf;
+mod index_document;
+mod language;
 mod lazy_init;
+mod ocr_cache;
+mod page_regions;
+mod pdf_annotate;
+mod pdf_metadata;
+mod pdf_outline;
+mod pdf_password;
 mod pdf_to_image;
 mod pdf_to_text;
 mod search_index;
+mod source_archive;
+mod source_code;
+mod source_email;
+mod source_epub;
+mod source_html;
+mod source_image;
+mod source_office;
+mod source_pdf;
+mod source_text;
 mod vector_index;
+mod zip_xml;
 
-use crate::index::{index_files, search_with_query};
+use crate::config::{Config, ConfigOverrides};
+use crate::database::SearchFilter;
+use crate::index::{
+    export_search_hits, failures_report, highlight_search_hits, index_files, low_confidence_report,
+    rechunk_collection, search_with_query, show_config,
+};
+use crate::lazy_init::{collection_dir, DEFAULT_COLLECTION};
+use crate::pdf_password::{set_pdf_passwords, PdfPasswords};
+use crate::source_code::parse_language;
 use clap::{Parser, Subcommand};
 use std::env;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[clap(name = "semantic_search_cli")]
-#[clap(about = "A semantic search tool for PDF files")]
+#[clap(
+    about = "A semantic search tool for PDF, Office, EPUB, HTML, Markdown, text, image, email and source code files"
+)]
 struct Cli {
+    /// Name of the collection to use
+    #[clap(short, long, global = true, default_value = DEFAULT_COLLECTION)]
+    collection: String,
+
+    #[clap(flatten)]
+    overrides: ConfigOverrides,
+
+    /// Password tried for encrypted PDF files. It is never stored, but may be kept in the shell
+    /// history, so prefer --password-file or --ask-password.
+    #[clap(long, global = true)]
+    password: Option<String>,
+
+    /// Ask for the password of encrypted PDF files no other password opens
+    #[clap(long, global = true)]
+    ask_password: bool,
+
     #[clap(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Index PDF files for searching
+    /// Index files for searching
     Index {
-        /// List of PDF files to index
+        /// List of PDF, DOCX, ODT, PPTX, EPUB, HTML, Markdown, text, image, EML, mbox, source code,
+        /// ZIP or tar files to index. Directories are indexed recursively, skipping files ignored
+        /// by git.
         #[clap(required = true)]
         files: Vec<String>,
     },
-    /// Search indexed PDF files
+    /// Search indexed files
     Search {
-        /// Search query
-        #[clap(required = true)]
-        query: String,
+        #[clap(flatten)]
+        search: SearchArgs,
+    },
+    /// Search indexed files and render the pages of the hits to PNG files with the hits
+    /// highlighted, for scanned PDF and image files
+    Highlight {
+        #[clap(flatten)]
+        search: SearchArgs,
+        /// Directory the PNG files are written to
+        #[clap(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+    /// Search indexed files and write a copy of each PDF file with hits, with the hits highlighted
+    /// and a note giving their rank and distance
+    Export {
+        #[clap(flatten)]
+        search: SearchArgs,
+        /// Directory the PDF files are written to
+        #[clap(short, long, default_value = ".")]
+        output: PathBuf,
+        /// Write a single PDF of just the pages with hits, hits.pdf, instead of a copy of each file
+        #[clap(long)]
+        combined: bool,
     },
+    /// List pages whose OCR confidence is below ocr.min_confidence
+    LowConfidence {
+        /// Read the listed pages again with the current OCR, preprocessing and render settings,
+        /// e.g. --preprocess deskew,binarize, keeping the text of pages that improve
+        #[clap(long)]
+        retry: bool,
+    },
+    /// List pages that could not be read when indexing, along with their error
+    Failures {
+        /// Read the listed pages again, indexing the pages that are read
+        #[clap(long)]
+        retry: bool,
+    },
+    /// Re-chunk and re-embed the collection from the stored page text, without running OCR again
+    Rechunk,
+    /// Inspect the configuration
+    Config {
+        #[clap(subcommand)]
+        command: ConfigCommands,
+    },
+}
+
+/// A search query along with the filters limiting it
+#[derive(clap::Args)]
+struct SearchArgs {
+    /// Search query
+    #[clap(required = true)]
+    query: String,
+    /// Only search documents whose author, or sender for emails, contains this text
+    #[clap(long, visible_alias = "from")]
+    author: Option<String>,
+    /// Only search documents dated on or after this date, e.g. 2024-03-01 or 2024-03
+    #[clap(long)]
+    after: Option<String>,
+    /// Only search documents dated before this date, e.g. 2024-03-01 or 2024-03
+    #[clap(long)]
+    before: Option<String>,
+    /// Only search source code in these languages, given by name or extension, e.g. rust,py
+    #[clap(long = "lang", value_delimiter = ',', value_parser = parse_language)]
+    lang: Vec<String>,
+    /// Only search documents whose title, or subject for emails, contains this text
+    #[clap(long)]
+    title: Option<String>,
+    /// Only search documents whose subject, as recorded in PDF metadata, contains this text
+    #[clap(long)]
+    subject: Option<String>,
+    /// Only search documents whose keywords, as recorded in PDF metadata, contain this text
+    #[clap(long)]
+    keywords: Option<String>,
+}
+
+impl SearchArgs {
+    fn filter(&self) -> SearchFilter {
+        SearchFilter {
+            author: self.author.clone(),
+            after: self.after.clone(),
+            before: self.before.clone(),
+            code_languages: self.lang.clone(),
+            title: self.title.clone(),
+            subject: self.subject.clone(),
+            keywords: self.keywords.clone(),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print the effective configuration and where each value came from
+    Show,
 }
 
 fn main() {
@@ -50,14 +190,59 @@ fn main() {
 
     let cli = Cli::parse();
 
+    let data_dir = collection_dir(&cli.collection).unwrap();
+    let config = Config::load(&data_dir, &cli.overrides).unwrap();
+
+    let password_file = &config.pdf_password_file.value;
+    let password_file = (!password_file.is_empty()).then(|| Path::new(password_file));
+    set_pdf_passwords(
+        PdfPasswords::new(cli.password.clone(), password_file, cli.ask_password).unwrap(),
+    );
+
     match &cli.command {
         Commands::Index { files } => {
             log::debug!("Indexing ...");
-            index_files(&files).unwrap();
+            index_files(&files, &data_dir, &config).unwrap();
         }
-        Commands::Search { query } => {
+        Commands::Search { search } => {
             log::debug!("Searching ...");
-            search_with_query(&query).unwrap();
+            search_with_query(&search.query, &search.filter(), &data_dir, &config).unwrap();
+        }
+        Commands::Highlight { search, output } => {
+            log::debug!("Highlighting search hits ...");
+            highlight_search_hits(&search.query, &search.filter(), output, &data_dir, &config)
+                .unwrap();
+        }
+        Commands::Export {
+            search,
+            output,
+            combined,
+        } => {
+            log::debug!("Exporting search hits ...");
+            export_search_hits(
+                &search.query,
+                &search.filter(),
+                output,
+                *combined,
+                &data_dir,
+                &config,
+            )
+            .unwrap();
+        }
+        Commands::LowConfidence { retry } => {
+            log::debug!("Listing low confidence pages ...");
+            low_confidence_report(&data_dir, &config, *retry).unwrap();
+        }
+        Commands::Failures { retry } => {
+            log::debug!("Listing failed pages ...");
+            failures_report(&data_dir, &config, *retry).unwrap();
+        }
+        Commands::Rechunk => {
+            log::debug!("Re-chunking ...");
+            rechunk_collection(&data_dir, &config).unwrap();
         }
+        Commands::Config { command } => match command {
+            ConfigCommands::Show => show_config(&data_dir, &config).unwrap(),
+        },
     }
 }
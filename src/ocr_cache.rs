@@ -0,0 +1,229 @@
+use crate::config::Config;
+use crate::database::{decode_words, encode_words};
+use crate::image_to_text::{ocr_args, tesseract_version, OcrText};
+use crate::lazy_init::DATA_DIR;
+use image::DynamicImage;
+use lazy_static::lazy_static;
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Name of the OCR cache database, kept in the data directory and shared by all collections
+const OCR_CACHE_NAME: &str = "ocr_cache.sqlite";
+
+lazy_static! {
+    /// The OCR cache, or None when it can't be opened, in which case every image is read
+    static ref OCR_CACHE: Option<Mutex<OcrCache>> = {
+        match OcrCache::new(&DATA_DIR.join(OCR_CACHE_NAME)) {
+            Ok(cache) => Some(Mutex::new(cache)),
+            Err(e) => {
+                log::warn!("Unable to open the OCR cache, pages are read again: {}", e);
+                None
+            }
+        }
+    };
+}
+
+/// Text recognized in images, keyed by a hash of the image and the OCR settings it was read
+/// with, so the same page is only read once whatever collection or document it is in
+pub struct OcrCache {
+    pub conn: Connection,
+}
+
+impl OcrCache {
+    pub fn new(path: &Path) -> anyhow::Result<OcrCache> {
+        let cache = OcrCache {
+            conn: Connection::open(path)?,
+        };
+        cache.init_tables()?;
+        Ok(cache)
+    }
+
+    pub fn init_tables(&self) -> anyhow::Result<()> {
+        self.conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS ocr_results (
+                key TEXT PRIMARY KEY,
+                text TEXT NOT NULL,
+                language TEXT,
+                confidence REAL,
+                words TEXT,
+                image_width INTEGER NOT NULL,
+                image_height INTEGER NOT NULL
+            )"#,
+            (),
+        )?;
+        Ok(())
+    }
+
+    /// Returns the text cached under a key, if any
+    pub fn get(&self, key: &str) -> anyhow::Result<Option<OcrText>> {
+        let ocr = self
+            .conn
+            .query_row(
+                "SELECT text, language, confidence, words, image_width, image_height FROM ocr_results WHERE key = ?1",
+                params![key],
+                |row| {
+                    Ok(OcrText {
+                        text: row.get(0)?,
+                        language: row.get(1)?,
+                        confidence: row.get(2)?,
+                        words: decode_words(row.get::<_, Option<String>>(3)?.as_deref()),
+                        image_size: (row.get(4)?, row.get(5)?),
+                    })
+                },
+            )
+            .optional()?;
+        Ok(ocr)
+    }
+
+    /// Caches the text of an image under a key, replacing what was cached before
+    pub fn insert(&self, key: &str, ocr: &OcrText) -> anyhow::Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO ocr_results (key, text, language, confidence, words, image_width, image_height) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                key,
+                ocr.text,
+                ocr.language,
+                ocr.confidence,
+                encode_words(&ocr.words),
+                ocr.image_size.0,
+                ocr.image_size.1
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+/// Key of the text of an image, the SHA-256 hash of its pixels along with every setting that
+/// changes what tesseract recognizes in them and the version of tesseract.
+///
+/// Render settings are left out as they already show in the pixels.
+pub fn ocr_cache_key(image: &DynamicImage, config: &Config) -> anyhow::Result<String> {
+    let args = ocr_args(config)?;
+    // Preprocessing steps run in the same order whatever order they are given in
+    let mut preprocess = config.ocr_preprocess.value.clone();
+    preprocess.sort();
+    preprocess.dedup();
+    let settings = [
+        format!("tesseract {}", tesseract_version()),
+        format!("lang {}", args.lang),
+        // Variables are listed sorted by name, unlike those of `args`
+        format!("variables {:?}", config.ocr_config_variables()?),
+        format!("dpi {:?} psm {:?} oem {:?}", args.dpi, args.psm, args.oem),
+        format!("preprocess {:?}", preprocess),
+        format!("detect_language {}", config.detect_language.value),
+        format!(
+            "image {}x{} {:?}",
+            image.width(),
+            image.height(),
+            image.color()
+        ),
+    ];
+
+    let mut hasher = Sha256::new();
+    hasher.update(settings.join("\n").as_bytes());
+    hasher.update(image.as_bytes());
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Returns the text of an image from the OCR cache, reading it with `read` and caching it when
+/// it isn't cached yet. Images are always read when `ocr.cache` is off or the cache can't be
+/// used.
+pub fn cached_ocr(
+    image: &DynamicImage,
+    config: &Config,
+    read: impl FnOnce() -> anyhow::Result<OcrText>,
+) -> anyhow::Result<OcrText> {
+    let Some(cache) = OCR_CACHE.as_ref().filter(|_| config.ocr_cache.value) else {
+        return read();
+    };
+
+    let key = ocr_cache_key(image, config)?;
+    match cache.lock().unwrap().get(&key) {
+        Ok(Some(ocr)) => {
+            log::debug!("Using cached text of image {}", key);
+            return Ok(ocr);
+        }
+        Ok(None) => {}
+        Err(e) => log::warn!("Unable to read the OCR cache: {}", e),
+    }
+
+    // The cache isn't locked while reading, which takes much longer than looking it up
+    let ocr = read()?;
+    if let Err(e) = cache.lock().unwrap().insert(&key, &ocr) {
+        log::warn!("Unable to write the OCR cache: {}", e);
+    }
+    Ok(ocr)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::image_preprocess::PreprocessStep;
+    use crate::image_to_text::{OcrText, OcrWord};
+    use crate::ocr_cache::{ocr_cache_key, OcrCache};
+    use image::{DynamicImage, Rgb, RgbImage};
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_ocr_cache() {
+        let cache = OcrCache {
+            conn: Connection::open_in_memory().unwrap(),
+        };
+        cache.init_tables().unwrap();
+        assert!(cache.get("page").unwrap().is_none());
+
+        let word = OcrWord {
+            text: "Invoice".to_string(),
+            confidence: 91.5,
+            left: 10,
+            top: 20,
+            width: 80,
+            height: 16,
+        };
+        cache
+            .insert(
+                "page",
+                &OcrText {
+                    text: "Invoice".to_string(),
+                    language: Some("eng".to_string()),
+                    confidence: Some(91.5),
+                    words: vec![word.clone()],
+                    image_size: (2000, 2800),
+                },
+            )
+            .unwrap();
+
+        let ocr = cache.get("page").unwrap().unwrap();
+        assert_eq!(ocr.text, "Invoice");
+        assert_eq!(ocr.language.as_deref(), Some("eng"));
+        assert_eq!(ocr.confidence, Some(91.5));
+        assert_eq!(ocr.words, vec![word]);
+        assert_eq!(ocr.image_size, (2000, 2800));
+    }
+
+    #[test]
+    fn test_ocr_cache_key() {
+        let white = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb([255, 255, 255])));
+        let gray = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb([128, 128, 128])));
+        let mut config = Config::default();
+
+        let key = ocr_cache_key(&white, &config).unwrap();
+        assert_eq!(key, ocr_cache_key(&white.clone(), &config).unwrap());
+        assert_ne!(key, ocr_cache_key(&gray, &config).unwrap());
+
+        // Settings other than those of OCR keep the key
+        config.chunk_size.value = 128;
+        config.embedding_model.value = "MultilingualE5Base".to_string();
+        assert_eq!(key, ocr_cache_key(&white, &config).unwrap());
+
+        config.ocr_psm.value = 6;
+        let psm = ocr_cache_key(&white, &config).unwrap();
+        assert_ne!(key, psm);
+
+        config.ocr_preprocess.value = vec![PreprocessStep::Binarize];
+        assert_ne!(psm, ocr_cache_key(&white, &config).unwrap());
+    }
+}
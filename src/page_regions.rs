@@ -0,0 +1,291 @@
+use crate::image_to_text::OcrWord;
+use image::{DynamicImage, RgbImage};
+
+/// Color regions are highlighted with
+const HIGHLIGHT_COLOR: [u8; 3] = [255, 200, 0];
+/// Opacity of the highlight over the page
+const HIGHLIGHT_OPACITY: f32 = 0.4;
+/// Width in pixels of the outline drawn around highlighted regions
+const OUTLINE_WIDTH: u32 = 2;
+/// Space in pixels left between highlighted words and the outline
+const HIGHLIGHT_PADDING: f32 = 4.0;
+
+/// A rectangle on a page, as fractions of the page width and height from its top left corner
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+
+    /// Smallest rectangle containing both rectangles
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect {
+            x,
+            y,
+            width: self.right().max(other.right()) - x,
+            height: self.bottom().max(other.bottom()) - y,
+        }
+    }
+}
+
+/// A rectangle of a page covered by a chunk
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PageRegion {
+    pub page_no: usize,
+    pub rect: Rect,
+}
+
+/// Byte ranges of the words recognized in a page within its text. The text is built from the
+/// words in order, so each word is looked for after the previous one. Words that can't be
+/// found, e.g. as the text was changed since, have no range.
+pub fn word_ranges(text: &str, words: &[OcrWord]) -> Vec<Option<(usize, usize)>> {
+    let mut position = 0;
+    words
+        .iter()
+        .map(|word| {
+            let start = position + text.get(position..)?.find(&word.text)?;
+            position = start + word.text.len();
+            Some((start, position))
+        })
+        .collect()
+}
+
+/// Rectangles covering the words of a page that overlap a byte range of its text, one per
+/// line of text, as fractions of the image the words are positioned in
+pub fn text_rects(
+    text: &str,
+    words: &[OcrWord],
+    image_size: (u32, u32),
+    start: usize,
+    end: usize,
+) -> Vec<Rect> {
+    let (image_width, image_height) = (image_size.0.max(1) as f32, image_size.1.max(1) as f32);
+
+    let mut rects: Vec<Rect> = Vec::new();
+    for (word, range) in words.iter().zip(word_ranges(text, words)) {
+        let Some((word_start, word_end)) = range else {
+            continue;
+        };
+        if word_end <= start || word_start >= end {
+            continue;
+        }
+
+        let rect = Rect {
+            x: word.left as f32 / image_width,
+            y: word.top as f32 / image_height,
+            width: word.width as f32 / image_width,
+            height: word.height as f32 / image_height,
+        };
+        // Words continuing a line to the right of the previous word extend its rectangle
+        match rects.last_mut() {
+            Some(line) if on_same_line(line, &rect) => *line = line.union(&rect),
+            _ => rects.push(rect),
+        }
+    }
+
+    rects
+}
+
+/// The smallest rectangle containing the regions of each page, in page order
+pub fn page_bounds(regions: &[PageRegion]) -> Vec<PageRegion> {
+    let mut bounds: Vec<PageRegion> = Vec::new();
+    for region in regions {
+        match bounds
+            .iter_mut()
+            .find(|bound| bound.page_no == region.page_no)
+        {
+            Some(bound) => bound.rect = bound.rect.union(&region.rect),
+            None => bounds.push(*region),
+        }
+    }
+    bounds.sort_by_key(|bound| bound.page_no);
+    bounds
+}
+
+/// Draws rectangles over an image as a translucent highlight with an outline
+pub fn highlight_rects(image: &DynamicImage, rects: &[Rect]) -> RgbImage {
+    let mut image = image.to_rgb8();
+    let (width, height) = (image.width() as f32, image.height() as f32);
+
+    for rect in rects {
+        let left = (rect.x * width - HIGHLIGHT_PADDING).clamp(0.0, width) as u32;
+        let top = (rect.y * height - HIGHLIGHT_PADDING).clamp(0.0, height) as u32;
+        let right = (rect.right() * width + HIGHLIGHT_PADDING).clamp(0.0, width) as u32;
+        let bottom = (rect.bottom() * height + HIGHLIGHT_PADDING).clamp(0.0, height) as u32;
+
+        for y in top..bottom {
+            for x in left..right {
+                let outline = x < left + OUTLINE_WIDTH
+                    || x + OUTLINE_WIDTH >= right
+                    || y < top + OUTLINE_WIDTH
+                    || y + OUTLINE_WIDTH >= bottom;
+                let pixel = image.get_pixel_mut(x, y);
+                for (channel, color) in pixel.0.iter_mut().zip(HIGHLIGHT_COLOR) {
+                    *channel = if outline {
+                        color
+                    } else {
+                        (*channel as f32 * (1.0 - HIGHLIGHT_OPACITY)
+                            + color as f32 * HIGHLIGHT_OPACITY)
+                            .round() as u8
+                    };
+                }
+            }
+        }
+    }
+
+    image
+}
+
+/// Whether a word follows a line of words, being to its right with its middle within the line
+fn on_same_line(line: &Rect, word: &Rect) -> bool {
+    let middle = word.y + word.height / 2.0;
+    word.x >= line.x && line.y <= middle && middle <= line.bottom()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image_to_text::OcrWord;
+    use crate::page_regions::{
+        highlight_rects, page_bounds, text_rects, word_ranges, PageRegion, Rect,
+    };
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    fn word(text: &str, left: i32, top: i32) -> OcrWord {
+        OcrWord {
+            text: text.to_string(),
+            confidence: 90.0,
+            left,
+            top,
+            width: 20,
+            height: 10,
+        }
+    }
+
+    #[test]
+    fn test_word_ranges() {
+        let text = "to be\nor not\n\nto be";
+        let words = [
+            word("to", 0, 0),
+            word("be", 0, 0),
+            word("or", 0, 0),
+            word("missing", 0, 0),
+            word("not", 0, 0),
+            word("to", 0, 0),
+        ];
+        assert_eq!(
+            word_ranges(text, &words),
+            vec![
+                Some((0, 2)),
+                Some((3, 5)),
+                Some((6, 8)),
+                None,
+                Some((9, 12)),
+                Some((14, 16)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_rects() {
+        let text = "Total due\ntoday only";
+        let words = [
+            word("Total", 10, 20),
+            word("due", 40, 21),
+            word("today", 10, 40),
+            word("only", 40, 40),
+        ];
+
+        // A chunk from the middle of "due" to the start of "today" covers both lines
+        let rects = text_rects(text, &words, (100, 200), 7, 11);
+        assert_eq!(
+            rects,
+            vec![
+                Rect {
+                    x: 0.4,
+                    y: 0.105,
+                    width: 0.2,
+                    height: 0.05,
+                },
+                Rect {
+                    x: 0.1,
+                    y: 0.2,
+                    width: 0.2,
+                    height: 0.05,
+                },
+            ]
+        );
+
+        let rects = text_rects(text, &words, (100, 200), 0, text.len());
+        assert_eq!(rects.len(), 2);
+        assert_eq!((rects[0].x, rects[0].y), (0.1, 0.1));
+        assert!((rects[0].right() - 0.6).abs() < 1e-6);
+        assert!((rects[0].bottom() - 0.155).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_page_bounds() {
+        let rect = |x: f32, y: f32| Rect {
+            x,
+            y,
+            width: 0.25,
+            height: 0.125,
+        };
+        let regions = [
+            PageRegion {
+                page_no: 3,
+                rect: rect(0.5, 0.75),
+            },
+            PageRegion {
+                page_no: 2,
+                rect: rect(0.25, 0.25),
+            },
+            PageRegion {
+                page_no: 2,
+                rect: rect(0.0, 0.5),
+            },
+        ];
+
+        let bounds = page_bounds(&regions);
+        assert_eq!(bounds.len(), 2);
+        assert_eq!(bounds[0].page_no, 2);
+        assert_eq!(
+            bounds[0].rect,
+            Rect {
+                x: 0.0,
+                y: 0.25,
+                width: 0.5,
+                height: 0.375,
+            }
+        );
+        assert_eq!(bounds[1], regions[0]);
+    }
+
+    #[test]
+    fn test_highlight_rects() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(100, 100, Rgb([255, 255, 255])));
+        let rect = Rect {
+            x: 0.2,
+            y: 0.2,
+            width: 0.5,
+            height: 0.25,
+        };
+
+        let highlighted = highlight_rects(&image, &[rect]);
+        assert_eq!(highlighted.get_pixel(5, 5), &Rgb([255, 255, 255]));
+        assert_eq!(highlighted.get_pixel(16, 30), &Rgb([255, 200, 0]));
+        assert_eq!(highlighted.get_pixel(40, 30), &Rgb([255, 233, 153]));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::page_regions::Rect;
+use pdfium_render::prelude::*;
+
+/// Color of the highlight annotations over search hits
+const HIGHLIGHT_COLOR: PdfColor = PdfColor::new(255, 200, 0, 255);
+/// Space in points left between highlighted words and the edge of the highlight
+const HIGHLIGHT_PADDING: f32 = 2.0;
+/// Width and height in points of the note icon placed next to a hit
+const NOTE_SIZE: f32 = 18.0;
+
+/// Converts a rectangle of a rendered page, as fractions of the image, to PDF points with the
+/// origin at the bottom left of the page. Landscape pages are rendered rotated by 90 degrees
+/// clockwise (see `PDFImages`), so their rectangles are rotated back.
+pub fn pdf_rect(rect: &Rect, page_width: f32, page_height: f32) -> PdfRect {
+    let (left, bottom, right, top) = if page_width > page_height {
+        // The left of the image is the bottom of the page, the top of the image its left
+        (
+            rect.y * page_width,
+            rect.x * page_height,
+            rect.bottom() * page_width,
+            rect.right() * page_height,
+        )
+    } else {
+        (
+            rect.x * page_width,
+            (1.0 - rect.bottom()) * page_height,
+            rect.right() * page_width,
+            (1.0 - rect.y) * page_height,
+        )
+    };
+
+    PdfRect::new_from_values(
+        (bottom - HIGHLIGHT_PADDING).max(0.0),
+        (left - HIGHLIGHT_PADDING).max(0.0),
+        (top + HIGHLIGHT_PADDING).min(page_height),
+        (right + HIGHLIGHT_PADDING).min(page_width),
+    )
+}
+
+/// Adds a highlight annotation over the rectangles of a hit on a page, along with a note next
+/// to it. Viewers show the note text when the note or the highlight is opened.
+pub fn annotate_hit(page: &mut PdfPage, rects: &[Rect], note: &str) -> Result<(), PdfiumError> {
+    let (page_width, page_height) = (page.width().value, page.height().value);
+    let rects: Vec<PdfRect> = rects
+        .iter()
+        .map(|rect| pdf_rect(rect, page_width, page_height))
+        .collect();
+    let Some(bounds) = rects.iter().copied().reduce(|a, b| {
+        PdfRect::new_from_values(
+            a.bottom.value.min(b.bottom.value),
+            a.left.value.min(b.left.value),
+            a.top.value.max(b.top.value),
+            a.right.value.max(b.right.value),
+        )
+    }) else {
+        return Ok(());
+    };
+
+    let annotations = page.annotations_mut();
+
+    let mut highlight = annotations.create_highlight_annotation()?;
+    highlight.set_position(bounds.left, bounds.bottom)?;
+    highlight.set_width(bounds.width())?;
+    highlight.set_height(bounds.height())?;
+    highlight.set_stroke_color(HIGHLIGHT_COLOR)?;
+    highlight.set_contents(note)?;
+    for rect in rects {
+        highlight
+            .attachment_points_mut()
+            .create_attachment_point_at_end(PdfQuadPoints::from_rect(rect))?;
+    }
+
+    // The note sits in the margin left of the hit, or inside the page when there is no room
+    let mut text = annotations.create_text_annotation(note)?;
+    let left = (bounds.left.value - NOTE_SIZE).max(0.0);
+    let bottom = (bounds.top.value - NOTE_SIZE).max(0.0);
+    text.set_position(PdfPoints::new(left), PdfPoints::new(bottom))?;
+    text.set_width(PdfPoints::new(NOTE_SIZE))?;
+    text.set_height(PdfPoints::new(NOTE_SIZE))?;
+    text.set_stroke_color(HIGHLIGHT_COLOR)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::page_regions::Rect;
+    use crate::pdf_annotate::pdf_rect;
+    use pdfium_render::prelude::PdfRect;
+
+    #[test]
+    fn test_pdf_rect() {
+        let rect = Rect {
+            x: 0.25,
+            y: 0.5,
+            width: 0.5,
+            height: 0.125,
+        };
+
+        // Portrait pages flip the vertical axis
+        assert_eq!(
+            pdf_rect(&rect, 400.0, 800.0),
+            PdfRect::new_from_values(298.0, 98.0, 402.0, 302.0)
+        );
+
+        // Landscape pages were rendered rotated clockwise
+        assert_eq!(
+            pdf_rect(&rect, 800.0, 400.0),
+            PdfRect::new_from_values(98.0, 398.0, 302.0, 502.0)
+        );
+
+        // Rectangles are kept within the page
+        let corner = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 0.5,
+            height: 0.5,
+        };
+        assert_eq!(
+            pdf_rect(&corner, 400.0, 800.0),
+            PdfRect::new_from_values(398.0, 0.0, 800.0, 202.0)
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use crate::database::{DocumentMetadata, PageAnnotation};
+use pdfium_render::prelude::*;
+
+/// Reads the title, author, subject, keywords and creation date from the document information
+/// of a PDF document. Empty values are left out.
+pub fn pdf_metadata(document: &PdfDocument) -> DocumentMetadata {
+    let metadata = document.metadata();
+    let get = |tag: PdfDocumentMetadataTagType| {
+        metadata
+            .get(tag)
+            .map(|tag| tag.value().trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    DocumentMetadata {
+        title: get(PdfDocumentMetadataTagType::Title),
+        author: get(PdfDocumentMetadataTagType::Author),
+        date: get(PdfDocumentMetadataTagType::CreationDate).and_then(|date| pdf_date(&date)),
+        subject: get(PdfDocumentMetadataTagType::Subject),
+        keywords: get(PdfDocumentMetadataTagType::Keywords),
+        ..Default::default()
+    }
+}
+
+/// Returns the annotations of a PDF document that carry text, such as notes, comments on
+/// highlights and free text boxes, in page order.
+///
+/// Links and form fields are not comments, and popups repeat the text of the annotation they
+/// belong to, so they are skipped.
+pub fn pdf_annotations(document: &PdfDocument) -> Vec<PageAnnotation> {
+    let mut annotations = Vec::new();
+    for (page_no, page) in document.pages().iter().enumerate() {
+        for annotation in page.annotations().iter() {
+            let kind = annotation.annotation_type();
+            if matches!(
+                kind,
+                PdfPageAnnotationType::Link
+                    | PdfPageAnnotationType::Widget
+                    | PdfPageAnnotationType::XfaWidget
+                    | PdfPageAnnotationType::Popup
+            ) {
+                continue;
+            }
+
+            let Some(text) = annotation.contents() else {
+                continue;
+            };
+            if text.trim().is_empty() {
+                continue;
+            }
+
+            annotations.push(PageAnnotation {
+                page_no,
+                kind: format!("{:?}", kind).to_lowercase(),
+                author: annotation
+                    .creator()
+                    .filter(|author| !author.trim().is_empty()),
+                text: text.trim().to_string(),
+            });
+        }
+    }
+
+    log::debug!("Found {} annotations", annotations.len());
+    annotations
+}
+
+/// Converts a PDF date, `D:YYYYMMDDHHmmSSOHH'mm'` with every part after the year optional, to
+/// an ISO 8601 date, or date and time when the hour is given
+pub fn pdf_date(date: &str) -> Option<String> {
+    let date = date.trim();
+    let date = date.strip_prefix("D:").unwrap_or(date);
+    let digits = date.bytes().take_while(u8::is_ascii_digit).count();
+    if digits < 4 {
+        return None;
+    }
+    let (fields, offset) = date.split_at(digits.min(14));
+
+    // Dates given to the year or month only are kept at that precision
+    let mut iso = fields[..4].to_string();
+    for range in [4..6, 6..8] {
+        if let Some(field) = fields.get(range) {
+            iso += &format!("-{}", field);
+        }
+    }
+    if fields.len() >= 10 {
+        let time = |range: std::ops::Range<usize>| fields.get(range).unwrap_or("00");
+        iso += &format!("T{}:{}:{}", time(8..10), time(10..12), time(12..14));
+
+        // The offset from UTC is Z, or a sign followed by hours and optionally minutes
+        let offset_digits: String = offset.chars().filter(char::is_ascii_digit).collect();
+        match offset.chars().next() {
+            Some('Z') => iso.push('Z'),
+            Some(sign @ ('+' | '-')) if offset_digits.len() >= 2 => {
+                let minutes = offset_digits.get(2..4).unwrap_or("00");
+                iso += &format!("{}{}:{}", sign, &offset_digits[..2], minutes);
+            }
+            _ => {}
+        }
+    }
+
+    Some(iso)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pdf_metadata::pdf_date;
+
+    #[test]
+    fn test_pdf_date() {
+        assert_eq!(
+            pdf_date("D:20240301120530+01'00'").as_deref(),
+            Some("2024-03-01T12:05:30+01:00")
+        );
+        assert_eq!(
+            pdf_date("D:20231224083000Z").as_deref(),
+            Some("2023-12-24T08:30:00Z")
+        );
+        assert_eq!(
+            pdf_date("D:20231224083000-0530").as_deref(),
+            Some("2023-12-24T08:30:00-05:30")
+        );
+        assert_eq!(
+            pdf_date("D:202403011205").as_deref(),
+            Some("2024-03-01T12:05:00")
+        );
+        assert_eq!(pdf_date("D:20240301").as_deref(), Some("2024-03-01"));
+        assert_eq!(pdf_date("D:202403").as_deref(), Some("2024-03"));
+        assert_eq!(pdf_date("2024").as_deref(), Some("2024"));
+        assert_eq!(pdf_date("D:"), None);
+        assert_eq!(pdf_date("yesterday"), None);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::chunk_sections::Heading;
+use pdfium_render::prelude::*;
+use std::collections::HashMap;
+
+/// Font sizes at least this much larger than the body text are considered headings
+const HEADING_FONT_SIZE_RATIO: f32 = 1.2;
+/// Longest line of text considered a heading
+const MAX_HEADING_LENGTH: usize = 120;
+/// Deepest heading level detected from font sizes
+const MAX_HEADING_LEVEL: usize = 3;
+
+/// Returns the section headings of a PDF document, in document order.
+///
+/// The document outline (bookmarks) is used when present. Otherwise headings are
+/// detected from lines of the text layer set in a larger font than the body text,
+/// larger fonts being higher level headings. Scanned documents without a text layer
+/// or outline have no headings.
+pub fn pdf_headings(document: &PdfDocument) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    outline_headings(document.bookmarks().root(), 1, &mut headings);
+
+    if headings.is_empty() {
+        headings = font_size_headings(document);
+    }
+
+    log::debug!("Found {} headings", headings.len());
+
+    // Bookmarks are not necessarily in page order
+    headings.sort_by_key(|heading| heading.page_no);
+    headings
+}
+
+/// Walks the bookmark tree depth first, starting at `bookmark` and its siblings
+fn outline_headings(bookmark: Option<PdfBookmark>, level: usize, headings: &mut Vec<Heading>) {
+    let mut current = bookmark;
+    while let Some(bookmark) = current {
+        let page_no = match bookmark.destination() {
+            Some(destination) => destination.page_index().ok(),
+            None => bookmark.action().and_then(|action| {
+                action
+                    .as_local_destination_action()?
+                    .destination()
+                    .ok()?
+                    .page_index()
+                    .ok()
+            }),
+        };
+
+        match (bookmark.title(), page_no) {
+            (Some(title), Some(page_no)) if !title.trim().is_empty() => headings.push(Heading {
+                page_no: page_no as usize,
+                level,
+                title: title.trim().to_string(),
+            }),
+            _ => {}
+        }
+
+        outline_headings(bookmark.first_child(), level + 1, headings);
+        current = bookmark.next_sibling();
+    }
+}
+
+/// Detects headings from the font size of the text objects on each page
+fn font_size_headings(document: &PdfDocument) -> Vec<Heading> {
+    // Runs of consecutive text objects sharing a font size, as (page, size, text)
+    let mut lines: Vec<(usize, f32, String)> = Vec::new();
+
+    for (page_no, page) in document.pages().iter().enumerate() {
+        for object in page.objects().iter() {
+            let Some(text_object) = object.as_text_object() else {
+                continue;
+            };
+
+            let size = round_font_size(text_object.scaled_font_size().value);
+            let text = text_object.text();
+
+            match lines.last_mut() {
+                Some((line_page, line_size, line))
+                    if *line_page == page_no && *line_size == size =>
+                {
+                    line.push(' ');
+                    line.push_str(text.trim());
+                }
+                _ => lines.push((page_no, size, text.trim().to_string())),
+            }
+        }
+    }
+
+    // The body font size is the one most of the text is set in
+    let mut characters_by_size: HashMap<u32, usize> = HashMap::new();
+    for (_, size, text) in &lines {
+        *characters_by_size.entry(size.to_bits()).or_default() += text.chars().count();
+    }
+    let Some(body_size) = characters_by_size
+        .into_iter()
+        .max_by_key(|(_, characters)| *characters)
+        .map(|(size, _)| f32::from_bits(size))
+    else {
+        return Vec::new();
+    };
+
+    let candidates: Vec<(usize, f32, String)> = lines
+        .into_iter()
+        .filter(|(_, size, text)| {
+            *size >= body_size * HEADING_FONT_SIZE_RATIO
+                && text.chars().count() <= MAX_HEADING_LENGTH
+                && text.chars().any(char::is_alphabetic)
+        })
+        .collect();
+
+    // Larger fonts are higher level headings
+    let mut sizes: Vec<f32> = candidates.iter().map(|(_, size, _)| *size).collect();
+    sizes.sort_by(|a, b| b.total_cmp(a));
+    sizes.dedup();
+
+    candidates
+        .into_iter()
+        .map(|(page_no, size, title)| Heading {
+            page_no,
+            level: (sizes.iter().position(|s| *s == size).unwrap() + 1).min(MAX_HEADING_LEVEL),
+            title,
+        })
+        .collect()
+}
+
+/// Rounds font sizes to half points so text set in the same font compares equal
+fn round_font_size(size: f32) -> f32 {
+    (size * 2.0).round() / 2.0
+}
@@ -0,0 +1,292 @@
+use globset::{GlobBuilder, GlobMatcher};
+use lazy_static::lazy_static;
+use pdfium_render::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+/// Number of times the password of a file is asked for before it is skipped
+const PROMPT_ATTEMPTS: usize = 3;
+
+lazy_static! {
+    static ref PDF_PASSWORDS: Mutex<PdfPasswords> = Mutex::new(PdfPasswords::default());
+}
+
+/// Passwords to open encrypted PDF files with.
+///
+/// Passwords are only kept in memory for the run and are never written to the index or the
+/// config. They are leaked to give them the lifetime pdfium asks of a password, which is
+/// harmless as they are few and live as long as the process anyway.
+#[derive(Default)]
+pub struct PdfPasswords {
+    /// Password given on the command line, tried for every encrypted file
+    password: Option<&'static str>,
+    /// Passwords from the password file, along with the pattern of the paths they open
+    patterns: Vec<(GlobMatcher, &'static str)>,
+    /// Whether to ask for the password of encrypted files no other password opens
+    prompt: bool,
+    /// Passwords entered at the prompt by the path of the file they opened
+    entered: HashMap<String, &'static str>,
+}
+
+impl PdfPasswords {
+    pub fn new(
+        password: Option<String>,
+        password_file: Option<&Path>,
+        prompt: bool,
+    ) -> anyhow::Result<PdfPasswords> {
+        let patterns = match password_file {
+            Some(path) => {
+                let text = std::fs::read_to_string(path).map_err(|e| {
+                    anyhow::anyhow!("Unable to read password file {}: {}", path.display(), e)
+                })?;
+                parse_password_file(&text).map_err(|e| {
+                    anyhow::anyhow!("Invalid password file {}: {}", path.display(), e)
+                })?
+            }
+            None => Vec::new(),
+        };
+
+        Ok(PdfPasswords {
+            password: password.map(leak),
+            patterns: patterns
+                .into_iter()
+                .map(|(pattern, password)| (pattern, leak(password)))
+                .collect(),
+            prompt,
+            entered: HashMap::new(),
+        })
+    }
+
+    /// Passwords to try for a file, the one entered for it and those of patterns matching its
+    /// path first
+    fn candidates(&self, path: &str) -> Vec<&'static str> {
+        let mut candidates: Vec<&'static str> =
+            self.entered.get(path).copied().into_iter().collect();
+        candidates.extend(
+            self.patterns
+                .iter()
+                .filter(|(pattern, _)| pattern.is_match(path))
+                .map(|(_, password)| *password),
+        );
+        candidates.extend(self.password);
+        candidates.dedup();
+        candidates
+    }
+}
+
+/// Sets the passwords PDF files are opened with for the rest of the run
+pub fn set_pdf_passwords(passwords: PdfPasswords) {
+    *PDF_PASSWORDS.lock().unwrap() = passwords;
+}
+
+/// Error for encrypted PDF files that none of the given passwords open
+#[derive(Debug)]
+pub struct EncryptedPdf {
+    pub path: String,
+}
+
+impl fmt::Display for EncryptedPdf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is encrypted and no password given opens it",
+            self.path
+        )
+    }
+}
+
+impl std::error::Error for EncryptedPdf {}
+
+/// Opens a PDF file, trying the configured passwords when it is encrypted
+pub fn load_pdf<'a>(pdfium: &'a Pdfium, path: &Path) -> anyhow::Result<PdfDocument<'a>> {
+    load_with_passwords(&path.to_string_lossy(), |password| {
+        pdfium.load_pdf_from_file(path, password)
+    })
+}
+
+/// Opens a PDF file read into memory, e.g. an email attachment, trying the configured
+/// passwords when it is encrypted. Password file patterns are matched against the name.
+pub fn load_pdf_from_bytes<'a>(
+    pdfium: &'a Pdfium,
+    data: Vec<u8>,
+    name: &str,
+) -> anyhow::Result<PdfDocument<'a>> {
+    load_with_passwords(name, |password| {
+        pdfium.load_pdf_from_byte_vec(data.clone(), password)
+    })
+}
+
+/// Opens a document without a password, then with each password that may open it, then with
+/// passwords asked for if prompting is enabled
+fn load_with_passwords<'a>(
+    path: &str,
+    load: impl Fn(Option<&'static str>) -> Result<PdfDocument<'a>, PdfiumError>,
+) -> anyhow::Result<PdfDocument<'a>> {
+    match load(None) {
+        Err(e) if is_password_error(&e) => {}
+        result => return Ok(result?),
+    }
+
+    let (candidates, prompt) = {
+        let passwords = PDF_PASSWORDS.lock().unwrap();
+        (passwords.candidates(path), passwords.prompt)
+    };
+    for password in candidates {
+        match load(Some(password)) {
+            Err(e) if is_password_error(&e) => continue,
+            result => return Ok(result?),
+        }
+    }
+
+    if prompt && std::io::stdin().is_terminal() {
+        for _ in 0..PROMPT_ATTEMPTS {
+            let Some(password) = prompt_password(path)? else {
+                break;
+            };
+            let password = leak(password);
+            match load(Some(password)) {
+                Err(e) if is_password_error(&e) => eprintln!("Wrong password"),
+                result => {
+                    let document = result?;
+                    // Pages of the file may be opened again during the run, e.g. to render them
+                    let mut passwords = PDF_PASSWORDS.lock().unwrap();
+                    passwords.entered.insert(path.to_string(), password);
+                    return Ok(document);
+                }
+            }
+        }
+    }
+
+    Err(EncryptedPdf {
+        path: path.to_string(),
+    }
+    .into())
+}
+
+fn is_password_error(error: &PdfiumError) -> bool {
+    matches!(
+        error,
+        PdfiumError::PdfiumLibraryInternalError(PdfiumInternalError::PasswordError)
+    )
+}
+
+/// Asks for the password of a file on the terminal without echoing it. Returns None when
+/// nothing is entered, to skip the file.
+fn prompt_password(path: &str) -> anyhow::Result<Option<String>> {
+    eprint!("Password for {} (empty to skip): ", path);
+    std::io::stderr().flush()?;
+
+    // Echo is turned off with stty where available, other terminals show the password
+    let echo_off = set_echo(false);
+    let mut password = String::new();
+    let read = std::io::stdin().lock().read_line(&mut password);
+    if echo_off {
+        set_echo(true);
+        eprintln!();
+    }
+    read?;
+
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    Ok((!password.is_empty()).then_some(password))
+}
+
+/// Turns echo of the terminal on stdin on or off, returning whether that succeeded
+fn set_echo(on: bool) -> bool {
+    Command::new("stty")
+        .arg(if on { "echo" } else { "-echo" })
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Parses a password file, a TOML table of glob patterns of paths to the password opening
+/// them. `*` matches within a directory and `**` across directories.
+fn parse_password_file(text: &str) -> anyhow::Result<Vec<(GlobMatcher, String)>> {
+    let table: toml::Table = text.parse()?;
+    table
+        .into_iter()
+        .map(|(pattern, password)| {
+            let toml::Value::String(password) = password else {
+                anyhow::bail!("The password for {} must be a string", pattern);
+            };
+            let pattern = expand_home(&pattern);
+            let matcher = GlobBuilder::new(&pattern)
+                .literal_separator(true)
+                .build()?
+                .compile_matcher();
+            Ok((matcher, password))
+        })
+        .collect()
+}
+
+/// Expands a leading `~/` to the home directory, as paths are matched in absolute form
+fn expand_home(pattern: &str) -> String {
+    match (pattern.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().into_owned(),
+        _ => pattern.to_string(),
+    }
+}
+
+fn leak(password: String) -> &'static str {
+    Box::leak(password.into_boxed_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pdf_password::{leak, parse_password_file, PdfPasswords};
+
+    #[test]
+    fn test_parse_password_file() {
+        let patterns = parse_password_file(
+            r#"
+            "/home/ann/bank/*.pdf" = "s3cret"
+            "**/payslips/**" = "other"
+            "**/bundle.zip!/bank/*.pdf" = "member"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(patterns.len(), 3);
+
+        let matching = |path: &str| -> Vec<&str> {
+            patterns
+                .iter()
+                .filter(|(pattern, _)| pattern.is_match(path))
+                .map(|(_, password)| password.as_str())
+                .collect()
+        };
+        assert_eq!(matching("/home/ann/bank/2024-03.pdf"), vec!["s3cret"]);
+        assert!(matching("/home/ann/bank/old/2019-01.pdf").is_empty());
+        assert_eq!(matching("/data/payslips/2024/03.pdf"), vec!["other"]);
+
+        // Archive members are matched by the path they are indexed under
+        assert_eq!(
+            matching("/home/ann/bundle.zip!/bank/2024-03.pdf"),
+            vec!["member"]
+        );
+
+        assert!(parse_password_file(r#""*.pdf" = 1234"#).is_err());
+        assert!(parse_password_file(r#""[.pdf" = "x""#).is_err());
+    }
+
+    #[test]
+    fn test_candidates() {
+        let mut passwords = PdfPasswords::new(Some("flag".to_string()), None, false).unwrap();
+        passwords.patterns = parse_password_file(r#""/tmp/*.pdf" = "file""#)
+            .unwrap()
+            .into_iter()
+            .map(|(pattern, password)| (pattern, leak(password)))
+            .collect();
+        passwords.entered.insert("/tmp/a.pdf".to_string(), "typed");
+
+        assert_eq!(
+            passwords.candidates("/tmp/a.pdf"),
+            vec!["typed", "file", "flag"]
+        );
+        assert_eq!(passwords.candidates("/home/b.pdf"), vec!["flag"]);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::config::Config;
+use crate::pdf_password::load_pdf;
 use image::DynamicImage;
 use pdfium_render::prelude::{
     PdfDocument, PdfPageRenderRotation, PdfRenderConfig, Pdfium, PdfiumError,
@@ -14,15 +16,38 @@ impl PDFImages<'_> {
     pub fn new<'a>(
         pdfium: &'a Pdfium,
         path: &impl AsRef<Path>,
-    ) -> Result<PDFImages<'a>, PdfiumError> {
-        Ok(PDFImages {
-            document: pdfium.load_pdf_from_file(path, None)?,
+        config: &Config,
+    ) -> anyhow::Result<PDFImages<'a>> {
+        Ok(PDFImages::from_document(
+            load_pdf(pdfium, path.as_ref())?,
+            config,
+        ))
+    }
+
+    /// Renders the pages of an already loaded document, e.g. one read from memory
+    pub fn from_document<'a>(document: PdfDocument<'a>, config: &Config) -> PDFImages<'a> {
+        PDFImages {
+            document,
             page: 0,
             render_config: PdfRenderConfig::new()
-                .set_target_width(2000)
-                .set_maximum_height(2000)
+                .set_target_width(config.render_width.value)
+                .set_maximum_height(config.render_max_height.value)
                 .rotate_if_landscape(PdfPageRenderRotation::Degrees90, true),
-        })
+        }
+    }
+}
+
+impl<'a> PDFImages<'a> {
+    pub fn document(&self) -> &PdfDocument<'a> {
+        &self.document
+    }
+
+    /// Renders a single page, counting from 0
+    pub fn render(&self, page: u16) -> Result<DynamicImage, PdfiumError> {
+        self.document
+            .pages()
+            .get(page)
+            .and_then(|page| Ok(page.render_with_config(&self.render_config)?.as_image()))
     }
 }
 
@@ -31,11 +56,7 @@ impl<'a> Iterator for PDFImages<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.page < self.document.pages().len() {
-            let result = self
-                .document
-                .pages()
-                .get(self.page)
-                .and_then(|page| Ok(page.render_with_config(&self.render_config)?.as_image()));
+            let result = self.render(self.page);
 
             self.page += 1;
             Some(result)
@@ -1,27 +1,46 @@
-use crate::image_to_text::image_ocr;
+use crate::config::Config;
+use crate::image_to_text::{image_ocr, OcrText};
 use crate::pdf_to_image::PDFImages;
-use pdfium_render::prelude::{Pdfium, PdfiumError};
+use pdfium_render::prelude::{PdfDocument, Pdfium};
 use std::path::Path;
 
 pub struct PDFText<'a> {
     pdf_images: PDFImages<'a>,
+    config: Config,
 }
 
 impl PDFText<'_> {
     pub fn new<'a>(
         pdfium: &'a Pdfium,
         path: &impl AsRef<Path>,
-    ) -> Result<PDFText<'a>, PdfiumError> {
+        config: &Config,
+    ) -> anyhow::Result<PDFText<'a>> {
         Ok(PDFText {
-            pdf_images: PDFImages::new(pdfium, path)?,
+            pdf_images: PDFImages::new(pdfium, path, config)?,
+            config: config.clone(),
         })
     }
+
+    pub fn from_document<'a>(document: PdfDocument<'a>, config: &Config) -> PDFText<'a> {
+        PDFText {
+            pdf_images: PDFImages::from_document(document, config),
+            config: config.clone(),
+        }
+    }
+}
+
+impl<'a> PDFText<'a> {
+    pub fn document(&self) -> &PdfDocument<'a> {
+        self.pdf_images.document()
+    }
 }
 
 impl<'a> Iterator for PDFText<'a> {
-    type Item = anyhow::Result<String>;
+    type Item = anyhow::Result<OcrText>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.pdf_images.next().map(|image| image_ocr(&image?))
+        self.pdf_images
+            .next()
+            .map(|image| image_ocr(&image?, &self.config))
     }
 }
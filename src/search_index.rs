@@ -1,33 +1,89 @@
-use crate::database::Database;
+use crate::config::Config;
+use crate::database::{Database, DocumentMetadata, PageAnnotation, SearchFilter};
 use crate::generate_embeddings::generate_embeddings;
-use usearch::ffi::Matches;
-use usearch::Index;
+use crate::page_regions::PageRegion;
+use crate::vector_index::VectorIndexes;
 
 pub struct VectorSearch<'a> {
     db: &'a Database,
-    matches: Matches,
+    /// Chunk ids and their distance from the query, closest first
+    matches: Vec<(u64, f32)>,
     index: usize,
 }
 
+/// Searches the indexes for the chunks closest to a query, among the chunks of the
+/// documents matching the filter. The query is embedded with the model of each index
+/// and the closest chunks of all indexes are merged by their rank, see `merge_by_rank`.
 pub fn search_index<'a>(
     query: &str,
+    filter: &SearchFilter,
     db: &'a Database,
-    index_db: &Index,
+    indexes: &VectorIndexes,
+    config: &Config,
 ) -> anyhow::Result<VectorSearch<'a>> {
-    let embeddings = generate_embeddings(vec![query])?;
-    let query_embedding = embeddings.into_iter().next().unwrap();
+    let top_k = config.top_k.value;
+    let chunk_ids = (!filter.is_empty())
+        .then(|| db.filtered_chunk_ids(filter))
+        .transpose()?;
+
+    let mut index_matches = Vec::new();
+    for embedding_index in indexes.all() {
+        let embeddings = generate_embeddings(
+            vec![query],
+            &embedding_index.model,
+            config.embedding_batch_size.value,
+        )?;
+        let query_embedding = embeddings.into_iter().next().unwrap();
+
+        let matches = match &chunk_ids {
+            None => embedding_index.index.search(&query_embedding, top_k)?,
+            Some(chunk_ids) => {
+                embedding_index
+                    .index
+                    .filtered_search(&query_embedding, top_k, |key| chunk_ids.contains(&key))?
+            }
+        };
+        index_matches.push(matches.keys.into_iter().zip(matches.distances).collect());
+    }
 
     Ok(VectorSearch {
         db,
-        matches: index_db.search(&query_embedding, 10)?,
+        matches: merge_by_rank(index_matches, top_k),
         index: 0,
     })
 }
 
+/// Merges the matches of several indexes, each ordered closest first, keeping the `top_k` best.
+/// The indexes are embedded by different models whose distances aren't comparable, so matches
+/// are ordered by their rank within their own index: the closest match of every index comes
+/// before the second closest of any, in the order the indexes are given.
+fn merge_by_rank(index_matches: Vec<Vec<(u64, f32)>>, top_k: usize) -> Vec<(u64, f32)> {
+    let mut ranked: Vec<(usize, (u64, f32))> = index_matches
+        .into_iter()
+        .flat_map(|matches| matches.into_iter().enumerate())
+        .collect();
+    // The sort is stable, keeping the order of the indexes among matches of the same rank
+    ranked.sort_by_key(|(rank, _)| *rank);
+    ranked.into_iter().map(|(_, m)| m).take(top_k).collect()
+}
+
 pub struct SearchResult {
     pub distance: f32,
     pub path: String,
+    pub metadata: DocumentMetadata,
     pub page_no: usize,
+    pub end_page_no: usize,
+    pub line_no: Option<usize>,
+    pub end_line_no: Option<usize>,
+    pub language: Option<String>,
+    /// OCR confidence of the page the chunk starts in, from 0 to 100
+    pub confidence: Option<f32>,
+    /// Rectangles covered by the chunk on pages read with OCR
+    pub regions: Vec<PageRegion>,
+    pub page_title: Option<String>,
+    pub heading: Option<String>,
+    /// Annotation the chunk is the text of, for chunks of comments users added to a page
+    pub annotation: Option<PageAnnotation>,
     pub text: String,
 }
 
@@ -35,19 +91,28 @@ impl Iterator for VectorSearch<'_> {
     type Item = anyhow::Result<SearchResult>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.matches.keys.len() {
-            let key = self.matches.keys[self.index];
-            let distance = self.matches.distances[self.index];
+        if self.index < self.matches.len() {
+            let (key, distance) = self.matches[self.index];
             self.index += 1;
 
             Some(
                 self.db
                     .get_document(key)
-                    .map(|(path, page_no, text)| SearchResult {
+                    .map(|chunk| SearchResult {
                         distance,
-                        path,
-                        page_no,
-                        text,
+                        path: chunk.path,
+                        metadata: chunk.metadata,
+                        page_no: chunk.page_no,
+                        end_page_no: chunk.end_page_no,
+                        line_no: chunk.line_no,
+                        end_line_no: chunk.end_line_no,
+                        language: chunk.language,
+                        confidence: chunk.confidence,
+                        regions: chunk.regions,
+                        page_title: chunk.page_title,
+                        heading: chunk.heading,
+                        annotation: chunk.annotation,
+                        text: chunk.text,
                     })
                     .map_err(|e| anyhow::anyhow!(e)),
             )
@@ -56,3 +121,24 @@ impl Iterator for VectorSearch<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::search_index::merge_by_rank;
+
+    #[test]
+    fn test_merge_by_rank() {
+        // The multilingual model gives larger distances overall, which must not push its
+        // matches out of the results
+        let main = vec![(1, 0.10), (2, 0.12), (3, 0.15)];
+        let multilingual = vec![(10, 0.40), (11, 0.45)];
+
+        assert_eq!(
+            merge_by_rank(vec![main.clone(), multilingual.clone()], 4),
+            vec![(1, 0.10), (10, 0.40), (2, 0.12), (11, 0.45)]
+        );
+        assert_eq!(merge_by_rank(vec![main.clone(), multilingual], 10).len(), 5);
+        assert_eq!(merge_by_rank(vec![main.clone()], 2), main[..2].to_vec());
+        assert!(merge_by_rank(vec![Vec::new(), Vec::new()], 3).is_empty());
+    }
+}
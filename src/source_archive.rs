@@ -0,0 +1,371 @@
+use crate::chunk_sections::Heading;
+use crate::config::Config;
+use crate::database::{DocumentMetadata, PageAnnotation};
+use crate::document_source::{
+    is_document, open_document, Document, DocumentSource, SourcePage, MEMBER_SEPARATOR,
+};
+use crate::pdf_password::load_pdf_from_bytes;
+use crate::source_email::EmailSource;
+use crate::source_pdf::PdfSource;
+use flate2::read::GzDecoder;
+use pdfium_render::prelude::Pdfium;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, Write};
+use std::path::{Component, Path};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use tempfile::NamedTempFile;
+use zip::ZipArchive;
+
+/// Archive formats, told apart by the file name
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+/// A member of an archive along with its contents
+struct ArchiveMember {
+    /// Path of the member within the archive, separated by `/`
+    name: String,
+    /// SHA-256 of the member contents, in hex
+    hash: String,
+    data: Vec<u8>,
+}
+
+/// Source of an archive member, keeping its extracted file until the member has been read
+struct MemberSource<'a> {
+    source: Box<dyn DocumentSource + 'a>,
+    _file: NamedTempFile,
+}
+
+impl DocumentSource for MemberSource<'_> {
+    fn metadata(&self) -> DocumentMetadata {
+        self.source.metadata()
+    }
+
+    fn headings(&self) -> Vec<Heading> {
+        self.source.headings()
+    }
+
+    fn annotations(&self) -> Vec<PageAnnotation> {
+        self.source.annotations()
+    }
+
+    fn encrypted_attachments(&self) -> Vec<String> {
+        self.source.encrypted_attachments()
+    }
+}
+
+impl Iterator for MemberSource<'_> {
+    type Item = anyhow::Result<SourcePage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.source.next()
+    }
+}
+
+/// Returns the kind of archive a file is, e.g. `bundle.zip` or `drop.tar.gz`
+pub fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if name.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else {
+        None
+    }
+}
+
+/// Reads the supported members of a ZIP or tar archive, each being a document of its own
+/// addressed by its path within the archive, e.g. `bundle.zip!/docs/spec.pdf`.
+/// Members carry a hash of their contents, so unchanged members need not be opened or indexed
+/// again. Members are read one at a time as the documents are listed, and only extracted to a
+/// file when they are opened.
+pub fn read_archive<'a>(
+    pdfium: &'a Pdfium,
+    path: &Path,
+    kind: ArchiveKind,
+    config: &'a Config,
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<Document<'a>>> + 'a> {
+    let archive = path.to_string_lossy().into_owned();
+    let members = spawn_members(path, kind)?;
+
+    Ok(members.into_iter().map(move |member| {
+        let ArchiveMember { name, hash, data } =
+            member.map_err(|e| anyhow::anyhow!("Unable to read archive {}: {}", archive, e))?;
+
+        let path = format!("{}{}{}", archive, MEMBER_SEPARATOR, name);
+        Ok(Document::new(path.clone(), Some(hash), move || {
+            open_member(pdfium, &path, data, config)
+        }))
+    }))
+}
+
+/// Reads the members of an archive on a thread of its own, handing them over one at a time so
+/// only the member being indexed is held in memory. tar entries borrow their archive, so they
+/// can't be read lazily by an iterator on this thread.
+fn spawn_members(
+    path: &Path,
+    kind: ArchiveKind,
+) -> anyhow::Result<Receiver<anyhow::Result<ArchiveMember>>> {
+    let reader = BufReader::new(File::open(path)?);
+    let (sender, receiver) = mpsc::sync_channel(0);
+
+    thread::spawn(move || {
+        // Reading stops once the documents are no longer listed
+        let mut send = |member| sender.send(Ok(member)).is_ok();
+        let result = match kind {
+            ArchiveKind::Zip => zip_members(reader, &mut send),
+            ArchiveKind::Tar => tar_members(reader, &mut send),
+            ArchiveKind::TarGz => tar_members(GzDecoder::new(reader), &mut send),
+        };
+        if let Err(e) = result {
+            let _ = sender.send(Err(e));
+        }
+    });
+
+    Ok(receiver)
+}
+
+/// Opens a member given the path it is indexed under. PDF files and emails are read from
+/// memory so passwords are looked up by that path, other members are extracted to a temporary
+/// file with the same extension and opened like any other file.
+fn open_member<'a>(
+    pdfium: &'a Pdfium,
+    path: &str,
+    data: Vec<u8>,
+    config: &Config,
+) -> anyhow::Result<Box<dyn DocumentSource + 'a>> {
+    let extension = Path::new(path)
+        .extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "pdf" => {
+            let document = load_pdf_from_bytes(pdfium, data, path)?;
+            return Ok(Box::new(PdfSource::from_document(document, config)));
+        }
+        "eml" => {
+            return Ok(Box::new(EmailSource::from_bytes(
+                pdfium, path, &data, config,
+            )?))
+        }
+        _ => {}
+    }
+
+    let mut file = tempfile::Builder::new()
+        .suffix(&format!(".{}", extension))
+        .tempfile()?;
+    file.write_all(&data)?;
+    file.flush()?;
+
+    let source = open_document(pdfium, file.path(), config)?;
+    Ok(Box::new(MemberSource {
+        source,
+        _file: file,
+    }))
+}
+
+/// Reads the supported members of a ZIP archive, handing each to `visit` until it returns false
+fn zip_members<R: Read + Seek>(
+    reader: R,
+    visit: &mut dyn FnMut(ArchiveMember) -> bool,
+) -> anyhow::Result<()> {
+    let mut archive = ZipArchive::new(reader)?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if !file.is_file() {
+            continue;
+        }
+        let Some(name) = file.enclosed_name() else {
+            log::warn!(
+                "Skipping archive member {} outside the archive",
+                file.name()
+            );
+            continue;
+        };
+        if let Some(member) = read_member(&name, &mut file)? {
+            if !visit(member) {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the supported members of a tar archive, handing each to `visit` until it returns false
+fn tar_members<R: Read>(
+    reader: R,
+    visit: &mut dyn FnMut(ArchiveMember) -> bool,
+) -> anyhow::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.into_owned();
+        if let Some(member) = read_member(&name, &mut entry)? {
+            if !visit(member) {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a member and hashes its contents, unless it is of a type that is not indexed. Hidden
+/// files, such as the `__MACOSX` folder of archives made on a Mac, are skipped like they are
+/// when indexing a directory.
+fn read_member(name: &Path, reader: &mut impl Read) -> anyhow::Result<Option<ArchiveMember>> {
+    let parts: Vec<String> = name
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    let hidden = parts
+        .iter()
+        .any(|part| part.starts_with('.') || part == "__MACOSX");
+    if parts.is_empty() || hidden || !is_document(name) {
+        return Ok(None);
+    }
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let hash = format!("{:x}", Sha256::digest(&data));
+
+    Ok(Some(ArchiveMember {
+        name: parts.join("/"),
+        hash,
+        data,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source_archive::{
+        archive_kind, spawn_members, tar_members, zip_members, ArchiveKind, ArchiveMember,
+    };
+    use std::io::{Cursor, Write};
+    use std::path::Path;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    #[test]
+    fn test_archive_kind() {
+        assert_eq!(
+            archive_kind(Path::new("a/Bundle.ZIP")),
+            Some(ArchiveKind::Zip)
+        );
+        assert_eq!(archive_kind(Path::new("drop.tar")), Some(ArchiveKind::Tar));
+        assert_eq!(
+            archive_kind(Path::new("drop.tar.gz")),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(
+            archive_kind(Path::new("drop.tgz")),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(archive_kind(Path::new("notes.gz")), None);
+    }
+
+    #[test]
+    fn test_zip_members() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let files = [
+            ("docs/spec.md", "# Spec"),
+            ("docs/logo.svg", "<svg/>"),
+            ("__MACOSX/docs/._spec.md", "junk"),
+            ("notes.txt", "# Spec"),
+        ];
+        for (name, contents) in files {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.add_directory("empty/", SimpleFileOptions::default())
+            .unwrap();
+        let data = zip.finish().unwrap().into_inner();
+
+        let mut members: Vec<ArchiveMember> = Vec::new();
+        zip_members(Cursor::new(data.clone()), &mut |member| {
+            members.push(member);
+            true
+        })
+        .unwrap();
+        let names: Vec<&str> = members.iter().map(|member| member.name.as_str()).collect();
+        assert_eq!(names, vec!["docs/spec.md", "notes.txt"]);
+
+        // Members with the same contents have the same hash
+        assert_eq!(members[0].hash, members[1].hash);
+        assert_eq!(members[0].hash.len(), 64);
+        assert_eq!(members[0].data, b"# Spec");
+
+        // Reading stops when the visitor is done
+        let mut names = Vec::new();
+        zip_members(Cursor::new(data), &mut |member| {
+            names.push(member.name);
+            false
+        })
+        .unwrap();
+        assert_eq!(names, vec!["docs/spec.md"]);
+    }
+
+    #[test]
+    fn test_tar_members() {
+        let mut tar = tar::Builder::new(Vec::new());
+        for (name, contents) in [("./a/report.txt", "first"), ("./.hidden.txt", "second")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, contents.as_bytes())
+                .unwrap();
+        }
+        let data = tar.into_inner().unwrap();
+
+        let mut members: Vec<ArchiveMember> = Vec::new();
+        tar_members(Cursor::new(data), &mut |member| {
+            members.push(member);
+            true
+        })
+        .unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].name, "a/report.txt");
+        assert_eq!(members[0].data, b"first");
+    }
+
+    #[test]
+    fn test_spawn_members() {
+        let mut file = tempfile::Builder::new().suffix(".zip").tempfile().unwrap();
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["a.md", "b.txt", "c.md"] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        file.write_all(&zip.finish().unwrap().into_inner()).unwrap();
+
+        let names: Vec<String> = spawn_members(file.path(), ArchiveKind::Zip)
+            .unwrap()
+            .into_iter()
+            .map(|member| member.unwrap().name)
+            .collect();
+        assert_eq!(names, vec!["a.md", "b.txt", "c.md"]);
+
+        // The reader thread stops once the members are no longer wanted
+        let members = spawn_members(file.path(), ArchiveKind::Zip).unwrap();
+        assert_eq!(members.recv().unwrap().unwrap().data, b"a.md");
+        drop(members);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::chunk_sections::Heading;
+use crate::database::DocumentMetadata;
+use crate::document_source::{DocumentSource, SourcePage};
+use crate::source_text::read_text;
+use std::path::Path;
+
+/// Programming languages by name, along with the extensions of their source files
+const LANGUAGES: &[(&str, &[&str])] = &[
+    ("c", &["c", "h"]),
+    ("cpp", &["cc", "cpp", "cxx", "hh", "hpp", "hxx"]),
+    ("csharp", &["cs"]),
+    ("go", &["go"]),
+    ("java", &["java"]),
+    ("javascript", &["js", "jsx", "mjs", "cjs"]),
+    ("kotlin", &["kt", "kts"]),
+    ("lua", &["lua"]),
+    ("php", &["php"]),
+    ("python", &["py", "pyi"]),
+    ("ruby", &["rb"]),
+    ("rust", &["rs"]),
+    ("scala", &["scala"]),
+    ("shell", &["sh", "bash", "zsh"]),
+    ("sql", &["sql"]),
+    ("swift", &["swift"]),
+    ("typescript", &["ts", "tsx", "mts", "cts"]),
+];
+
+/// Source code files, read as a single page that is chunked at its functions and blocks
+pub struct CodeSource {
+    page: Option<SourcePage>,
+    language: &'static str,
+}
+
+impl CodeSource {
+    pub fn new(path: &Path, language: &'static str) -> anyhow::Result<CodeSource> {
+        Ok(CodeSource {
+            page: Some(SourcePage {
+                text: read_text(path)?,
+                line_no: Some(1),
+                title: None,
+                language: None,
+                confidence: None,
+                words: Vec::new(),
+                image_size: None,
+            }),
+            language,
+        })
+    }
+}
+
+impl DocumentSource for CodeSource {
+    fn metadata(&self) -> DocumentMetadata {
+        DocumentMetadata {
+            code_language: Some(self.language.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn headings(&self) -> Vec<Heading> {
+        Vec::new()
+    }
+}
+
+impl Iterator for CodeSource {
+    type Item = anyhow::Result<SourcePage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.page.take().map(Ok)
+    }
+}
+
+/// Returns the language of source files with an extension, given in lowercase
+pub fn extension_language(extension: &str) -> Option<&'static str> {
+    LANGUAGES
+        .iter()
+        .find(|(_, extensions)| extensions.contains(&extension))
+        .map(|(name, _)| *name)
+}
+
+/// Parses a language given by name or file extension, e.g. `rust` or `rs`
+pub fn parse_language(name: &str) -> anyhow::Result<String> {
+    let name = name.trim().to_ascii_lowercase();
+    LANGUAGES
+        .iter()
+        .map(|(language, _)| *language)
+        .find(|language| *language == name)
+        .or_else(|| extension_language(&name))
+        .map(str::to_string)
+        .ok_or_else(|| {
+            let names: Vec<&str> = LANGUAGES.iter().map(|(language, _)| *language).collect();
+            anyhow::anyhow!(
+                "Unknown language {}, expected one of {}",
+                name,
+                names.join(", ")
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source_code::{extension_language, parse_language};
+
+    #[test]
+    fn test_languages() {
+        assert_eq!(extension_language("rs"), Some("rust"));
+        assert_eq!(extension_language("h"), Some("c"));
+        assert_eq!(extension_language("md"), None);
+
+        assert_eq!(parse_language("Python").unwrap(), "python");
+        assert_eq!(parse_language("tsx").unwrap(), "typescript");
+        assert!(parse_language("cobol").is_err());
+    }
+}
@@ -0,0 +1,354 @@
+use crate::chunk_sections::Heading;
+use crate::config::Config;
+use crate::database::DocumentMetadata;
+use crate::document_source::{Document, DocumentSource, SourcePage, MEMBER_SEPARATOR};
+use crate::pdf_password::{load_pdf_from_bytes, EncryptedPdf};
+use crate::source_html::html_text;
+use crate::source_pdf::PdfSource;
+use mail_parser::mailbox::mbox::MessageIterator;
+use mail_parser::{Address, Message, MessageParser, MimeHeaders};
+use pdfium_render::prelude::Pdfium;
+use sha2::{Digest, Sha256};
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, File};
+use std::path::Path;
+
+/// Email messages. The message is the first page, followed by the pages of its PDF
+/// attachments when `email.pdf_attachments` is enabled.
+pub struct EmailSource<'a> {
+    metadata: DocumentMetadata,
+    message: Option<String>,
+    attachments: VecDeque<(String, PdfSource<'a>)>,
+    headings: Vec<Heading>,
+    /// Paths of the attachments no password opens
+    encrypted: Vec<String>,
+}
+
+impl EmailSource<'_> {
+    pub fn eml<'a>(
+        pdfium: &'a Pdfium,
+        path: &Path,
+        config: &Config,
+    ) -> anyhow::Result<EmailSource<'a>> {
+        EmailSource::from_bytes(pdfium, &path.to_string_lossy(), &fs::read(path)?, config)
+    }
+
+    /// Reads a message from memory. Attachments are addressed within the path the message is
+    /// indexed under, e.g. `inbox.mbox!/1234@example.com!/report.pdf`, which passwords are
+    /// looked up by.
+    pub fn from_bytes<'a>(
+        pdfium: &'a Pdfium,
+        path: &str,
+        data: &[u8],
+        config: &Config,
+    ) -> anyhow::Result<EmailSource<'a>> {
+        let message = MessageParser::default()
+            .parse(data)
+            .ok_or_else(|| anyhow::anyhow!("Invalid email message"))?;
+
+        let mut attachments = VecDeque::new();
+        let mut headings = Vec::new();
+        let mut encrypted = Vec::new();
+        if config.email_pdf_attachments.value {
+            // Attachments follow the page of the message
+            let mut page_no = 1;
+            for (name, data) in pdf_attachments(&message) {
+                let attachment_path = format!("{}{}{}", path, MEMBER_SEPARATOR, name);
+                let document = match load_pdf_from_bytes(pdfium, data, &attachment_path) {
+                    Ok(document) => document,
+                    Err(e) => {
+                        if e.downcast_ref::<EncryptedPdf>().is_some() {
+                            encrypted.push(attachment_path);
+                        }
+                        log::warn!("Skipping attachment {}: {}", name, e);
+                        continue;
+                    }
+                };
+                let source = PdfSource::from_document(document, config);
+
+                headings.push(Heading {
+                    page_no,
+                    level: 1,
+                    title: name.clone(),
+                });
+                headings.extend(source.headings().into_iter().map(|heading| Heading {
+                    page_no: page_no + heading.page_no,
+                    level: heading.level + 1,
+                    title: heading.title,
+                }));
+
+                page_no += source.document().pages().len() as usize;
+                attachments.push_back((name, source));
+            }
+        }
+
+        Ok(EmailSource {
+            metadata: message_metadata(&message),
+            message: Some(message_text(&message)),
+            attachments,
+            headings,
+            encrypted,
+        })
+    }
+}
+
+impl DocumentSource for EmailSource<'_> {
+    fn metadata(&self) -> DocumentMetadata {
+        self.metadata.clone()
+    }
+
+    fn headings(&self) -> Vec<Heading> {
+        self.headings.clone()
+    }
+
+    fn encrypted_attachments(&self) -> Vec<String> {
+        self.encrypted.clone()
+    }
+}
+
+impl Iterator for EmailSource<'_> {
+    type Item = anyhow::Result<SourcePage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(text) = self.message.take() {
+            return Some(Ok(SourcePage {
+                text,
+                line_no: None,
+                title: None,
+                language: None,
+                confidence: None,
+                words: Vec::new(),
+                image_size: None,
+            }));
+        }
+
+        // Pages of attachments are titled with the name of the attachment
+        while let Some((name, source)) = self.attachments.front_mut() {
+            match source.next() {
+                Some(page) => {
+                    return Some(page.map(|page| SourcePage {
+                        title: Some(name.clone()),
+                        ..page
+                    }))
+                }
+                None => {
+                    self.attachments.pop_front();
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Lists the messages of an mbox mailbox, each being a document of its own addressed by its
+/// Message-ID, e.g. `inbox.mbox!/1234@example.com`, or by the hash of its contents when it has
+/// none. Messages carry the hash of their contents as well, so they are only parsed when they
+/// are new or changed.
+pub fn read_mailbox<'a>(
+    pdfium: &'a Pdfium,
+    path: &Path,
+    config: &'a Config,
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<Document<'a>>> + 'a> {
+    let mailbox = path.to_string_lossy().into_owned();
+    let messages = MessageIterator::new(File::open(path)?);
+    let mut keys = HashSet::new();
+
+    Ok(messages.enumerate().map(move |(index, message)| {
+        let data = message
+            .map_err(|_| anyhow::anyhow!("Unable to read message {} of mailbox", index + 1))?
+            .unwrap_contents();
+        let hash = format!("{:x}", Sha256::digest(&data));
+        // A Message-ID repeated by another message is not unique, so the hash is used instead
+        let key = message_key(&data)
+            .filter(|key| keys.insert(key.clone()))
+            .unwrap_or_else(|| hash.clone());
+
+        let path = format!("{}{}{}", mailbox, MEMBER_SEPARATOR, key);
+        Ok(Document::new(path.clone(), Some(hash), move || {
+            let source = EmailSource::from_bytes(pdfium, &path, &data, config)?;
+            Ok(Box::new(source) as Box<dyn DocumentSource>)
+        }))
+    }))
+}
+
+/// Key of a message within its mailbox, its Message-ID with slashes replaced so it is a single
+/// component of the path of the message. Only the headers are parsed.
+fn message_key(data: &[u8]) -> Option<String> {
+    let message = MessageParser::default().parse_headers(data)?;
+    let id = message.message_id()?.trim();
+    (!id.is_empty()).then(|| id.replace('/', "_"))
+}
+
+/// Takes the subject as title, the sender as author and the date the message was sent
+fn message_metadata(message: &Message) -> DocumentMetadata {
+    DocumentMetadata {
+        title: message.subject().map(str::to_string),
+        author: message.from().map(format_address),
+        date: message.date().map(|date| date.to_rfc3339()),
+        ..Default::default()
+    }
+}
+
+/// Formats a message as its main header fields followed by its body, with HTML bodies
+/// converted to text
+fn message_text(message: &Message) -> String {
+    let headers = [
+        ("Subject", message.subject().map(str::to_string)),
+        ("From", message.from().map(format_address)),
+        ("To", message.to().map(format_address)),
+        ("Date", message.date().map(|date| date.to_rfc3339())),
+    ];
+
+    let mut text = String::new();
+    for (name, value) in headers {
+        if let Some(value) = value {
+            text.push_str(&format!("{}: {}\n", name, value));
+        }
+    }
+
+    for part in message.text_bodies() {
+        let Some(contents) = part.text_contents() else {
+            continue;
+        };
+        let body = if part.is_text_html() {
+            html_text(contents).sections.join("\n\n")
+        } else {
+            contents.trim().to_string()
+        };
+
+        if !body.is_empty() {
+            text.push('\n');
+            text.push_str(&body);
+            text.push('\n');
+        }
+    }
+
+    text
+}
+
+/// Formats the addresses of a header as a comma separated list of `Name <address>`
+fn format_address<'x>(address: &'x Address<'x>) -> String {
+    address
+        .iter()
+        .map(|addr| match (addr.name(), addr.address()) {
+            (Some(name), Some(address)) => format!("{} <{}>", name, address),
+            (Some(name), None) => name.to_string(),
+            (None, Some(address)) => address.to_string(),
+            (None, None) => String::new(),
+        })
+        .filter(|addr| !addr.is_empty())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Returns the name and contents of the PDF files attached to a message
+fn pdf_attachments(message: &Message) -> Vec<(String, Vec<u8>)> {
+    message
+        .attachments()
+        .filter(|part| {
+            part.is_content_type("application", "pdf")
+                || part
+                    .attachment_name()
+                    .is_some_and(|name| name.to_ascii_lowercase().ends_with(".pdf"))
+        })
+        .enumerate()
+        .map(|(index, part)| {
+            let name = part
+                .attachment_name()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("Attachment {}", index + 1));
+            (name, part.contents().to_vec())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::DocumentMetadata;
+    use crate::source_email::{message_key, message_metadata, message_text, pdf_attachments};
+    use mail_parser::MessageParser;
+
+    #[test]
+    fn test_plain_message() {
+        let data = b"From: Ann Author <ann@example.com>\r\n\
+            To: bob@example.com, Carol <carol@example.com>\r\n\
+            Subject: Quarterly report\r\n\
+            Date: Fri, 1 Mar 2024 10:00:00 +0000\r\n\
+            Content-Type: multipart/mixed; boundary=\"outer\"\r\n\
+            \r\n\
+            --outer\r\n\
+            Content-Type: multipart/alternative; boundary=\"inner\"\r\n\
+            \r\n\
+            --inner\r\n\
+            Content-Type: text/plain\r\n\
+            \r\n\
+            The numbers are in.\r\n\
+            --inner\r\n\
+            Content-Type: text/html\r\n\
+            \r\n\
+            <p>The <b>numbers</b> are in.</p>\r\n\
+            --inner--\r\n\
+            --outer\r\n\
+            Content-Type: application/pdf; name=\"report.pdf\"\r\n\
+            Content-Disposition: attachment; filename=\"report.pdf\"\r\n\
+            Content-Transfer-Encoding: base64\r\n\
+            \r\n\
+            JVBERi0xLjQ=\r\n\
+            --outer--\r\n";
+        let message = MessageParser::default().parse(&data[..]).unwrap();
+
+        assert_eq!(
+            message_metadata(&message),
+            DocumentMetadata {
+                title: Some("Quarterly report".to_string()),
+                author: Some("Ann Author <ann@example.com>".to_string()),
+                date: Some("2024-03-01T10:00:00Z".to_string()),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            message_text(&message),
+            "Subject: Quarterly report\n\
+             From: Ann Author <ann@example.com>\n\
+             To: bob@example.com, Carol <carol@example.com>\n\
+             Date: 2024-03-01T10:00:00Z\n\
+             \n\
+             The numbers are in.\n"
+        );
+        assert_eq!(
+            pdf_attachments(&message),
+            vec![("report.pdf".to_string(), b"%PDF-1.4".to_vec())]
+        );
+    }
+
+    #[test]
+    fn test_html_message() {
+        let data = b"From: news@example.com\r\n\
+            Subject: Weekly news\r\n\
+            Content-Type: text/html\r\n\
+            \r\n\
+            <html><head><style>p { color: red }</style></head>\
+            <body><h1>Headlines</h1><p>Rain   expected.</p></body></html>\r\n";
+        let message = MessageParser::default().parse(&data[..]).unwrap();
+
+        assert_eq!(
+            message_text(&message),
+            "Subject: Weekly news\nFrom: news@example.com\n\nHeadlines\n\nRain expected.\n"
+        );
+        assert_eq!(message_metadata(&message).date, None);
+        assert!(pdf_attachments(&message).is_empty());
+    }
+
+    #[test]
+    fn test_message_key() {
+        let data = b"Message-ID: <2024/03.1234@example.com>\r\nSubject: Report\r\n\r\nBody\r\n";
+        assert_eq!(
+            message_key(&data[..]).as_deref(),
+            Some("2024_03.1234@example.com")
+        );
+
+        let data = b"Subject: Report\r\n\r\nBody\r\n";
+        assert_eq!(message_key(&data[..]), None);
+    }
+}
@@ -0,0 +1,457 @@
+use crate::chunk_sections::Heading;
+use crate::database::DocumentMetadata;
+use crate::document_source::{DocumentSource, SourcePage};
+use crate::source_html::{collapse_whitespace, html_text};
+use crate::zip_xml::{parse_xml, read_zip_entry, resolve_href};
+use scraper::{ElementRef, Html};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+use std::vec::IntoIter;
+use zip::ZipArchive;
+
+/// Location of the file pointing to the package document in every EPUB
+const CONTAINER_PATH: &str = "META-INF/container.xml";
+
+/// EPUB e-books, one logical page per chapter in reading order
+pub struct EpubSource {
+    metadata: DocumentMetadata,
+    pages: IntoIter<SourcePage>,
+    headings: Vec<Heading>,
+}
+
+/// The parts of the package (OPF) document needed to read a book
+#[derive(Debug, PartialEq)]
+struct Package {
+    metadata: DocumentMetadata,
+    /// Paths of the chapters in reading order
+    spine: Vec<String>,
+    /// Path of the EPUB 3 navigation document
+    nav: Option<String>,
+    /// Path of the EPUB 2 table of contents
+    ncx: Option<String>,
+}
+
+/// An entry of the table of contents
+#[derive(Debug, PartialEq)]
+struct TocEntry {
+    path: String,
+    title: String,
+    level: usize,
+}
+
+impl EpubSource {
+    pub fn new(path: &Path) -> anyhow::Result<EpubSource> {
+        read_epub(&mut ZipArchive::new(File::open(path)?)?)
+    }
+}
+
+impl DocumentSource for EpubSource {
+    fn metadata(&self) -> DocumentMetadata {
+        self.metadata.clone()
+    }
+
+    fn headings(&self) -> Vec<Heading> {
+        self.headings.clone()
+    }
+}
+
+impl Iterator for EpubSource {
+    type Item = anyhow::Result<SourcePage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pages.next().map(Ok)
+    }
+}
+
+/// Reads the chapters of a book in spine order, titling each with its table of contents entry.
+/// Chapters missing from the table of contents continue the previous one, as happens when
+/// long chapters are split into several files.
+fn read_epub<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<EpubSource> {
+    let package_path = rootfile_path(&read_zip_entry(archive, CONTAINER_PATH)?)?;
+    let package = parse_package(&read_zip_entry(archive, &package_path)?, &package_path)?;
+
+    let toc = match (&package.nav, &package.ncx) {
+        (Some(nav), _) => nav_toc(&read_zip_entry(archive, nav)?, nav),
+        (None, Some(ncx)) => ncx_toc(&read_zip_entry(archive, ncx)?, ncx)?,
+        (None, None) => Vec::new(),
+    };
+
+    let mut pages = Vec::new();
+    let mut headings = Vec::new();
+    let mut chapter_title = None;
+
+    for chapter_path in &package.spine {
+        let html = match read_zip_entry(archive, chapter_path) {
+            Ok(html) => html,
+            Err(e) => {
+                log::warn!("Skipping chapter {}: {}", chapter_path, e);
+                continue;
+            }
+        };
+
+        let text = html_text(&html);
+        let content = text.sections.join("\n\n");
+        if content.is_empty() {
+            continue;
+        }
+
+        let mut chapter_headings: Vec<Heading> = toc
+            .iter()
+            .filter(|entry| entry.path == *chapter_path)
+            .map(|entry| Heading {
+                page_no: pages.len(),
+                level: entry.level,
+                title: entry.title.clone(),
+            })
+            .collect();
+        // Without a table of contents chapters are titled by their first heading
+        if toc.is_empty() {
+            chapter_headings.extend(text.headings.into_iter().take(1).map(|heading| Heading {
+                page_no: pages.len(),
+                level: 1,
+                title: heading.title,
+            }));
+        }
+
+        if let Some(heading) = chapter_headings.first() {
+            chapter_title = Some(heading.title.clone());
+        }
+        headings.extend(chapter_headings);
+
+        pages.push(SourcePage {
+            text: content,
+            line_no: None,
+            title: chapter_title.clone(),
+            language: None,
+            confidence: None,
+            words: Vec::new(),
+            image_size: None,
+        });
+    }
+
+    Ok(EpubSource {
+        metadata: package.metadata,
+        pages: pages.into_iter(),
+        headings,
+    })
+}
+
+/// Path of the package document, from `META-INF/container.xml`
+fn rootfile_path(container: &str) -> anyhow::Result<String> {
+    parse_xml(container)?
+        .descendants()
+        .find(|node| node.has_tag_name("rootfile"))
+        .and_then(|node| node.attribute("full-path"))
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("No package document in {}", CONTAINER_PATH))
+}
+
+fn parse_package(opf: &str, package_path: &str) -> anyhow::Result<Package> {
+    let document = parse_xml(opf)?;
+    let elements = |name: &'static str| {
+        document
+            .descendants()
+            .filter(move |node| node.has_tag_name(name))
+    };
+    let texts = |name: &'static str| {
+        elements(name)
+            .filter_map(|node| node.text())
+            .map(collapse_whitespace)
+            .filter(|text| !text.is_empty())
+    };
+
+    let authors: Vec<String> = texts("creator").collect();
+    let metadata = DocumentMetadata {
+        title: texts("title").next(),
+        author: (!authors.is_empty()).then(|| authors.join(", ")),
+        date: texts("date").next(),
+        ..Default::default()
+    };
+
+    let manifest: HashMap<&str, roxmltree::Node> = elements("item")
+        .filter_map(|item| Some((item.attribute("id")?, item)))
+        .collect();
+    let href = |item: &roxmltree::Node| {
+        item.attribute("href")
+            .map(|href| resolve_href(package_path, href))
+    };
+
+    let spine = elements("itemref")
+        .filter_map(|itemref| manifest.get(itemref.attribute("idref")?))
+        .filter(|item| {
+            matches!(
+                item.attribute("media-type"),
+                Some("application/xhtml+xml" | "text/html")
+            )
+        })
+        .filter_map(href)
+        .collect();
+
+    let nav = manifest
+        .values()
+        .find(|item| {
+            item.attribute("properties")
+                .is_some_and(|properties| properties.split_whitespace().any(|p| p == "nav"))
+        })
+        .and_then(href);
+
+    let ncx = elements("spine")
+        .next()
+        .and_then(|spine| manifest.get(spine.attribute("toc")?))
+        .or_else(|| {
+            manifest
+                .values()
+                .find(|item| item.attribute("media-type") == Some("application/x-dtbncx+xml"))
+        })
+        .and_then(href);
+
+    Ok(Package {
+        metadata,
+        spine,
+        nav,
+        ncx,
+    })
+}
+
+/// Table of contents from an EPUB 3 navigation document, nesting levels taken from its lists
+fn nav_toc(html: &str, nav_path: &str) -> Vec<TocEntry> {
+    let document = Html::parse_document(html);
+    let navs: Vec<ElementRef> = document
+        .root_element()
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .filter(|element| element.value().name() == "nav")
+        .collect();
+    let Some(nav) = navs
+        .iter()
+        .find(|nav| {
+            nav.value()
+                .attr("epub:type")
+                .is_some_and(|kind| kind.split_whitespace().any(|kind| kind == "toc"))
+        })
+        .or(navs.first())
+    else {
+        return Vec::new();
+    };
+
+    nav.descendants()
+        .filter_map(ElementRef::wrap)
+        .filter(|element| element.value().name() == "a")
+        .filter_map(|link| {
+            let level = link
+                .ancestors()
+                .take_while(|ancestor| ancestor.id() != nav.id())
+                .filter_map(ElementRef::wrap)
+                .filter(|ancestor| ancestor.value().name() == "ol")
+                .count();
+
+            Some(TocEntry {
+                path: resolve_href(nav_path, link.value().attr("href")?),
+                title: collapse_whitespace(&link.text().collect::<String>()),
+                level: level.max(1),
+            })
+        })
+        .filter(|entry| !entry.title.is_empty())
+        .collect()
+}
+
+/// Table of contents from an EPUB 2 NCX document, nesting levels taken from its nav points
+fn ncx_toc(ncx: &str, ncx_path: &str) -> anyhow::Result<Vec<TocEntry>> {
+    let document = parse_xml(ncx)?;
+
+    let toc = document
+        .descendants()
+        .filter(|node| node.has_tag_name("navPoint"))
+        .filter_map(|nav_point| {
+            let title = nav_point
+                .children()
+                .find(|node| node.has_tag_name("navLabel"))?
+                .children()
+                .find(|node| node.has_tag_name("text"))?
+                .text()
+                .map(collapse_whitespace)?;
+            let src = nav_point
+                .children()
+                .find(|node| node.has_tag_name("content"))?
+                .attribute("src")?;
+            let level = nav_point
+                .ancestors()
+                .filter(|node| node.has_tag_name("navPoint"))
+                .count();
+
+            Some(TocEntry {
+                path: resolve_href(ncx_path, src),
+                title,
+                level,
+            })
+        })
+        .filter(|entry| !entry.title.is_empty())
+        .collect();
+
+    Ok(toc)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::DocumentMetadata;
+    use crate::source_epub::{ncx_toc, read_epub, TocEntry, CONTAINER_PATH};
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+    use zip::{ZipArchive, ZipWriter};
+
+    const CONTAINER: &str = r#"<?xml version="1.0"?>
+        <container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+          <rootfiles>
+            <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+          </rootfiles>
+        </container>"#;
+
+    const PACKAGE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+          <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+            <dc:title>A  Book</dc:title>
+            <dc:creator>First Author</dc:creator>
+            <dc:creator>Second Author</dc:creator>
+            <dc:date>2011-09-30</dc:date>
+          </metadata>
+          <manifest>
+            <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+            <item id="c1" href="text/chapter%201.xhtml" media-type="application/xhtml+xml"/>
+            <item id="c1b" href="text/chapter1b.xhtml" media-type="application/xhtml+xml"/>
+            <item id="c2" href="text/chapter2.xhtml" media-type="application/xhtml+xml"/>
+            <item id="img" href="cover.jpg" media-type="image/jpeg"/>
+          </manifest>
+          <spine>
+            <itemref idref="c1"/>
+            <itemref idref="c1b"/>
+            <itemref idref="img"/>
+            <itemref idref="c2"/>
+          </spine>
+        </package>"#;
+
+    const NAV: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+        <body>
+          <nav epub:type="landmarks"><ol><li><a href="text/chapter2.xhtml">Wrong</a></li></ol></nav>
+          <nav epub:type="toc">
+            <ol>
+              <li><a href="text/chapter%201.xhtml">Chapter 1</a></li>
+              <li><a href="text/chapter2.xhtml">Chapter 2</a>
+                <ol><li><a href="text/chapter2.xhtml#s1">A Section</a></li></ol>
+              </li>
+            </ol>
+          </nav>
+        </body>
+        </html>"#;
+
+    fn chapter(body: &str) -> String {
+        format!(
+            "<html><head><title>A Book</title></head><body>{}</body></html>",
+            body
+        )
+    }
+
+    fn epub(files: &[(&str, &str)]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer
+                .start_file(name.to_string(), SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_read_epub() {
+        let first = chapter("<h1>Chapter 1</h1><p>It begins.</p>");
+        let continued = chapter("<p>It continues.</p>");
+        let second = chapter("<h1>Chapter 2</h1><h2>A Section</h2><p>The end.</p>");
+        let mut archive = epub(&[
+            (CONTAINER_PATH, CONTAINER),
+            ("OEBPS/content.opf", PACKAGE),
+            ("OEBPS/nav.xhtml", NAV),
+            ("OEBPS/text/chapter 1.xhtml", &first),
+            ("OEBPS/text/chapter1b.xhtml", &continued),
+            ("OEBPS/text/chapter2.xhtml", &second),
+        ]);
+
+        let source = read_epub(&mut archive).unwrap();
+        assert_eq!(
+            source.metadata,
+            DocumentMetadata {
+                title: Some("A Book".to_string()),
+                author: Some("First Author, Second Author".to_string()),
+                date: Some("2011-09-30".to_string()),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            source
+                .headings
+                .iter()
+                .map(|h| (h.page_no, h.level, h.title.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, 1, "Chapter 1"),
+                (2, 1, "Chapter 2"),
+                (2, 2, "A Section")
+            ]
+        );
+
+        let pages: Vec<(String, Option<String>)> = source
+            .map(|page| page.unwrap())
+            .map(|page| (page.text, page.title))
+            .collect();
+        assert_eq!(
+            pages,
+            vec![
+                (
+                    "Chapter 1\n\nIt begins.".to_string(),
+                    Some("Chapter 1".to_string())
+                ),
+                ("It continues.".to_string(), Some("Chapter 1".to_string())),
+                (
+                    "Chapter 2\n\nA Section\n\nThe end.".to_string(),
+                    Some("Chapter 2".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ncx_toc() {
+        let ncx = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <!DOCTYPE ncx PUBLIC "-//NISO//DTD ncx 2005-1//EN" "http://www.daisy.org/z3986/2005/ncx-2005-1.dtd">
+            <ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+              <navMap>
+                <navPoint id="p1" playOrder="1">
+                  <navLabel><text>Part One</text></navLabel>
+                  <content src="part1.html"/>
+                  <navPoint id="p2" playOrder="2">
+                    <navLabel><text>Chapter 1</text></navLabel>
+                    <content src="../Text/ch1.html#start"/>
+                  </navPoint>
+                </navPoint>
+              </navMap>
+            </ncx>"#;
+
+        assert_eq!(
+            ncx_toc(ncx, "OPS/toc/toc.ncx").unwrap(),
+            vec![
+                TocEntry {
+                    path: "OPS/toc/part1.html".to_string(),
+                    title: "Part One".to_string(),
+                    level: 1
+                },
+                TocEntry {
+                    path: "OPS/Text/ch1.html".to_string(),
+                    title: "Chapter 1".to_string(),
+                    level: 2
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use crate::chunk_sections::Heading;
+use crate::database::DocumentMetadata;
+use crate::document_source::{DocumentSource, SourcePage};
+use scraper::{ElementRef, Html, Node};
+use std::fs;
+use std::path::Path;
+use std::vec::IntoIter;
+
+/// Elements that are not part of the content of a page
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "svg", "canvas", "iframe", "nav", "aside",
+    "footer", "form", "button", "select",
+];
+
+/// Elements whose text is kept as a paragraph of its own
+const PARAGRAPH_ELEMENTS: &[&str] = &[
+    "p",
+    "pre",
+    "blockquote",
+    "ul",
+    "ol",
+    "dl",
+    "table",
+    "figure",
+    "section",
+    "article",
+    "main",
+    "header",
+    "address",
+    "hr",
+];
+
+/// Elements that start a new line
+const LINE_ELEMENTS: &[&str] = &[
+    "br",
+    "div",
+    "li",
+    "dt",
+    "dd",
+    "tr",
+    "caption",
+    "figcaption",
+    "summary",
+    "details",
+];
+
+/// Text extracted from an HTML document
+pub struct HtmlText {
+    /// Title of the document, from its `<title>` element
+    pub title: Option<String>,
+    /// Sections of the text. All but the first start at a heading.
+    pub sections: Vec<String>,
+    pub headings: Vec<Heading>,
+}
+
+/// HTML files and saved web pages, one logical page per section starting at a heading
+pub struct HtmlSource {
+    title: Option<String>,
+    pages: IntoIter<String>,
+    headings: Vec<Heading>,
+}
+
+impl HtmlSource {
+    pub fn html(path: &Path) -> anyhow::Result<HtmlSource> {
+        let html = String::from_utf8_lossy(&fs::read(path)?).into_owned();
+        Ok(HtmlSource::from_text(html_text(&html)))
+    }
+
+    /// Web pages saved as a single MHTML file, which stores the page in a MIME message
+    pub fn mhtml(path: &Path) -> anyhow::Result<HtmlSource> {
+        let data = fs::read(path)?;
+        let message = mail_parser::MessageParser::default()
+            .parse(&data)
+            .ok_or_else(|| anyhow::anyhow!("Invalid MHTML file {}", path.display()))?;
+        let html = message
+            .body_html(0)
+            .ok_or_else(|| anyhow::anyhow!("No HTML page in {}", path.display()))?;
+
+        Ok(HtmlSource::from_text(html_text(&html)))
+    }
+
+    fn from_text(text: HtmlText) -> HtmlSource {
+        HtmlSource {
+            title: text.title,
+            pages: text.sections.into_iter(),
+            headings: text.headings,
+        }
+    }
+}
+
+impl DocumentSource for HtmlSource {
+    fn metadata(&self) -> DocumentMetadata {
+        DocumentMetadata {
+            title: self.title.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn headings(&self) -> Vec<Heading> {
+        self.headings.clone()
+    }
+}
+
+impl Iterator for HtmlSource {
+    type Item = anyhow::Result<SourcePage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pages.next().map(|text| {
+            Ok(SourcePage {
+                text,
+                line_no: None,
+                title: None,
+                language: None,
+                confidence: None,
+                words: Vec::new(),
+                image_size: None,
+            })
+        })
+    }
+}
+
+/// Extracts the readable text of an HTML document, leaving out scripts, styles and
+/// navigation. Paragraphs are separated by blank lines and the text is split into
+/// sections at each heading.
+pub fn html_text(html: &str) -> HtmlText {
+    let document = Html::parse_document(html);
+
+    let title = document
+        .root_element()
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .find(|element| element.value().name() == "title")
+        .map(|element| collapse_whitespace(&element.text().collect::<String>()))
+        .filter(|title| !title.is_empty());
+
+    let mut writer = TextWriter::default();
+    writer.write_element(document.root_element());
+    writer.end_section();
+
+    HtmlText {
+        title,
+        sections: writer.sections,
+        headings: writer.headings,
+    }
+}
+
+#[derive(Default)]
+struct TextWriter {
+    sections: Vec<String>,
+    headings: Vec<Heading>,
+    text: String,
+    /// Depth of `<pre>` elements, within which whitespace is kept
+    preformatted: usize,
+}
+
+impl TextWriter {
+    fn write_element(&mut self, element: ElementRef) {
+        let name = element.value().name();
+        if SKIPPED_ELEMENTS.contains(&name) || is_hidden(element) {
+            return;
+        }
+
+        if let Some(level) = heading_level(name) {
+            let title = collapse_whitespace(&element.text().collect::<String>());
+            if !title.is_empty() {
+                self.end_section();
+                self.headings.push(Heading {
+                    page_no: self.sections.len(),
+                    level,
+                    title: title.clone(),
+                });
+                self.text.push_str(&title);
+                self.break_paragraph();
+            }
+            return;
+        }
+
+        let paragraph = PARAGRAPH_ELEMENTS.contains(&name);
+        let line = LINE_ELEMENTS.contains(&name);
+        if paragraph {
+            self.break_paragraph();
+        } else if line {
+            self.break_line();
+        }
+        if name == "pre" {
+            self.preformatted += 1;
+        }
+
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => self.write_text(text),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        self.write_element(child);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if name == "pre" {
+            self.preformatted -= 1;
+        }
+        if paragraph {
+            self.break_paragraph();
+        } else if line {
+            self.break_line();
+        } else if matches!(name, "td" | "th") {
+            self.write_text(" ");
+        }
+    }
+
+    fn write_text(&mut self, text: &str) {
+        if self.preformatted > 0 {
+            self.text.push_str(text);
+            return;
+        }
+
+        for c in text.chars() {
+            if !c.is_whitespace() {
+                self.text.push(c);
+            } else if !self.text.is_empty() && !self.text.ends_with(char::is_whitespace) {
+                self.text.push(' ');
+            }
+        }
+    }
+
+    fn break_line(&mut self) {
+        self.text
+            .truncate(self.text.trim_end_matches([' ', '\t']).len());
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push('\n');
+        }
+    }
+
+    fn break_paragraph(&mut self) {
+        self.break_line();
+        if !self.text.is_empty() && !self.text.ends_with("\n\n") {
+            self.text.push('\n');
+        }
+    }
+
+    fn end_section(&mut self) {
+        let text = self.text.trim();
+        if !text.is_empty() {
+            self.sections.push(text.to_string());
+        }
+        self.text.clear();
+    }
+}
+
+fn heading_level(name: &str) -> Option<usize> {
+    match name {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+/// Elements hidden from readers or marked as navigation
+fn is_hidden(element: ElementRef) -> bool {
+    let element = element.value();
+    element.attr("hidden").is_some()
+        || element.attr("aria-hidden") == Some("true")
+        || element.attr("role") == Some("navigation")
+}
+
+/// Replaces runs of whitespace with single spaces, trimming both ends
+pub fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source_html::html_text;
+
+    #[test]
+    fn test_html_text() {
+        let html = r#"<!DOCTYPE html>
+            <html>
+            <head><title> Field   Notes </title><style>p { color: red }</style></head>
+            <body>
+                <nav><a href="/">Home</a> <a href="/about">About</a></nav>
+                <p>Intro with a <a href="/x">useful   link</a>.</p>
+                <h1>First <em>section</em></h1>
+                <p>One</p><p>Two<br>lines</p>
+                <script>alert("hi")</script>
+                <h2>Second</h2>
+                <ul><li>Item one</li><li>Item two</li></ul>
+                <div hidden>Invisible</div>
+                <pre>let x =
+    1;</pre>
+                <footer>Copyright</footer>
+            </body>
+            </html>"#;
+        let text = html_text(html);
+
+        assert_eq!(text.title.as_deref(), Some("Field Notes"));
+        assert_eq!(
+            text.sections,
+            vec![
+                "Intro with a useful link.",
+                "First section\n\nOne\n\nTwo\nlines",
+                "Second\n\nItem one\nItem two\n\nlet x =\n    1;",
+            ]
+        );
+        assert_eq!(
+            text.headings
+                .iter()
+                .map(|h| (h.page_no, h.level, h.title.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(1, 1, "First section"), (2, 2, "Second")]
+        );
+    }
+
+    #[test]
+    fn test_html_without_title() {
+        let text = html_text("<p>Just text</p>");
+        assert_eq!(text.title, None);
+        assert_eq!(text.sections, vec!["Just text"]);
+        assert!(text.headings.is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::chunk_sections::Heading;
+use crate::config::Config;
+use crate::document_source::{DocumentSource, SourcePage};
+use crate::image_to_text::image_ocr;
+use image::{DynamicImage, ImageBuffer, ImageDecoder, ImageFormat, ImageReader};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::iter;
+use std::path::Path;
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::ColorType;
+
+/// Image files such as scans and photos, one page per image read with OCR.
+/// Multi-page TIFF files have a page per frame.
+pub struct ImageSource {
+    images: Box<dyn Iterator<Item = anyhow::Result<DynamicImage>>>,
+    config: Config,
+}
+
+impl ImageSource {
+    pub fn new(path: &Path, config: &Config) -> anyhow::Result<ImageSource> {
+        let images: Box<dyn Iterator<Item = anyhow::Result<DynamicImage>>> =
+            match ImageFormat::from_path(path)? {
+                ImageFormat::Tiff => Box::new(TiffFrames::new(BufReader::new(File::open(path)?))?),
+                _ => Box::new(iter::once(read_image(path))),
+            };
+
+        Ok(ImageSource {
+            images,
+            config: config.clone(),
+        })
+    }
+}
+
+impl DocumentSource for ImageSource {
+    fn headings(&self) -> Vec<Heading> {
+        Vec::new()
+    }
+}
+
+impl Iterator for ImageSource {
+    type Item = anyhow::Result<SourcePage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.images
+            .next()
+            .map(|image| Ok(SourcePage::from(image_ocr(&image?, &self.config)?)))
+    }
+}
+
+/// Reads a single page of an image file, counting from 0, e.g. to run OCR on it again
+pub fn page_image(path: &Path, page_no: usize) -> anyhow::Result<DynamicImage> {
+    let image = match ImageFormat::from_path(path)? {
+        ImageFormat::Tiff => TiffFrames::new(BufReader::new(File::open(path)?))?.nth(page_no),
+        _ => iter::once(read_image(path)).nth(page_no),
+    };
+    image.unwrap_or_else(|| anyhow::bail!("{} has no page {}", path.display(), page_no))
+}
+
+/// Reads an image, turning it upright as recorded in its EXIF orientation, as photos
+/// taken with a phone often are stored sideways
+fn read_image(path: &Path) -> anyhow::Result<DynamicImage> {
+    let mut decoder = ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_decoder()?;
+    let orientation = decoder.orientation()?;
+
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// Frames of a TIFF file, read one at a time
+struct TiffFrames<R: Read + Seek> {
+    decoder: Option<Decoder<R>>,
+    started: bool,
+}
+
+impl<R: Read + Seek> TiffFrames<R> {
+    fn new(reader: R) -> anyhow::Result<TiffFrames<R>> {
+        Ok(TiffFrames {
+            decoder: Some(Decoder::new(reader)?),
+            started: false,
+        })
+    }
+}
+
+impl<R: Read + Seek> Iterator for TiffFrames<R> {
+    type Item = anyhow::Result<DynamicImage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let decoder = self.decoder.as_mut()?;
+
+        let result = if !self.started {
+            self.started = true;
+            tiff_frame(decoder)
+        } else if decoder.more_images() {
+            decoder
+                .next_image()
+                .map_err(anyhow::Error::from)
+                .and_then(|_| tiff_frame(decoder))
+        } else {
+            return None;
+        };
+
+        // The position of the next frame is unknown once reading a frame fails
+        if result.is_err() {
+            self.decoder = None;
+        }
+        Some(result)
+    }
+}
+
+/// Decodes the current frame of a TIFF file
+fn tiff_frame<R: Read + Seek>(decoder: &mut Decoder<R>) -> anyhow::Result<DynamicImage> {
+    let (width, height) = decoder.dimensions()?;
+    let color_type = decoder.colortype()?;
+
+    let image = match (color_type, decoder.read_image()?) {
+        (ColorType::Gray(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8)
+        }
+        (ColorType::Gray(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma16)
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8)
+        }
+        (ColorType::GrayA(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA16)
+        }
+        (ColorType::RGB(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8)
+        }
+        (ColorType::RGB(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb16)
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8)
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba16)
+        }
+        _ => anyhow::bail!("Unsupported TIFF color type {:?}", color_type),
+    };
+
+    image.ok_or_else(|| anyhow::anyhow!("Invalid TIFF frame of {}x{}", width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source_image::TiffFrames;
+    use image::GenericImageView;
+    use std::io::Cursor;
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    #[test]
+    fn test_tiff_frames() {
+        let mut data = Cursor::new(Vec::new());
+        let mut encoder = TiffEncoder::new(&mut data).unwrap();
+        encoder
+            .write_image::<colortype::Gray8>(2, 1, &[0, 255])
+            .unwrap();
+        encoder
+            .write_image::<colortype::RGB8>(1, 2, &[255, 0, 0, 0, 0, 255])
+            .unwrap();
+        data.set_position(0);
+
+        let frames: Vec<_> = TiffFrames::new(data)
+            .unwrap()
+            .map(|frame| frame.unwrap())
+            .collect();
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].dimensions(), (2, 1));
+        assert_eq!(frames[0].get_pixel(1, 0).0, [255, 255, 255, 255]);
+        assert_eq!(frames[1].dimensions(), (1, 2));
+        assert_eq!(frames[1].get_pixel(0, 1).0, [0, 0, 255, 255]);
+    }
+}
@@ -0,0 +1,766 @@
+use crate::chunk_sections::Heading;
+use crate::database::DocumentMetadata;
+use crate::document_source::{DocumentSource, SourcePage};
+use crate::source_html::collapse_whitespace;
+use crate::zip_xml::{local_attribute, parse_xml, read_zip_entry, resolve_href};
+use roxmltree::Node;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+use std::vec::IntoIter;
+use zip::ZipArchive;
+
+/// Namespace of relationship ids in Office Open XML documents
+const RELATIONSHIPS_NAMESPACE: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+/// Placeholders on slides and notes pages that hold no content of their own
+const SKIPPED_PLACEHOLDERS: &[&str] = &["sldNum", "dt", "ftr", "hdr", "sldImg"];
+
+/// Word processing documents (DOCX, ODT) and presentations (PPTX).
+///
+/// Documents are split at their headings, each section being a logical page.
+/// Presentations have one logical page per slide, titled with the slide title.
+pub struct OfficeSource {
+    metadata: DocumentMetadata,
+    pages: IntoIter<SourcePage>,
+    headings: Vec<Heading>,
+}
+
+/// A block of text of a word processing document
+#[derive(Debug, PartialEq)]
+enum Block {
+    Heading(usize, String),
+    Paragraph(String),
+}
+
+impl OfficeSource {
+    pub fn docx(path: &Path) -> anyhow::Result<OfficeSource> {
+        read_docx(&mut ZipArchive::new(File::open(path)?)?)
+    }
+
+    pub fn odt(path: &Path) -> anyhow::Result<OfficeSource> {
+        read_odt(&mut ZipArchive::new(File::open(path)?)?)
+    }
+
+    pub fn pptx(path: &Path) -> anyhow::Result<OfficeSource> {
+        read_pptx(&mut ZipArchive::new(File::open(path)?)?)
+    }
+
+    /// Splits the blocks of a document into sections starting at each heading
+    fn from_blocks(metadata: DocumentMetadata, blocks: Vec<Block>) -> OfficeSource {
+        let mut sections = Vec::new();
+        let mut headings = Vec::new();
+        let mut section = String::new();
+
+        for block in blocks {
+            let text = match block {
+                Block::Heading(level, title) => {
+                    end_section(&mut sections, &mut section);
+                    headings.push(Heading {
+                        page_no: sections.len(),
+                        level,
+                        title: title.clone(),
+                    });
+                    title
+                }
+                Block::Paragraph(text) => text,
+            };
+
+            if !section.is_empty() {
+                section.push_str("\n\n");
+            }
+            section.push_str(&text);
+        }
+        end_section(&mut sections, &mut section);
+
+        OfficeSource {
+            metadata,
+            pages: sections.into_iter(),
+            headings,
+        }
+    }
+}
+
+impl DocumentSource for OfficeSource {
+    fn metadata(&self) -> DocumentMetadata {
+        self.metadata.clone()
+    }
+
+    fn headings(&self) -> Vec<Heading> {
+        self.headings.clone()
+    }
+}
+
+impl Iterator for OfficeSource {
+    type Item = anyhow::Result<SourcePage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pages.next().map(Ok)
+    }
+}
+
+fn end_section(sections: &mut Vec<SourcePage>, section: &mut String) {
+    if !section.trim().is_empty() {
+        sections.push(SourcePage {
+            text: std::mem::take(section),
+            line_no: None,
+            title: None,
+            language: None,
+            confidence: None,
+            words: Vec::new(),
+            image_size: None,
+        });
+    }
+    section.clear();
+}
+
+/// Reads the title, author and creation date from the document properties shared by all
+/// Office Open XML formats
+fn core_properties<R: Read + Seek>(archive: &mut ZipArchive<R>) -> DocumentMetadata {
+    let Ok(core) = read_zip_entry(archive, "docProps/core.xml") else {
+        return DocumentMetadata::default();
+    };
+    properties(&core, &["title"], &["creator"], &["created"])
+}
+
+/// Reads metadata from an XML document with Dublin Core like elements, taking the
+/// first non-empty element of each of the given names
+fn properties(xml: &str, titles: &[&str], authors: &[&str], dates: &[&str]) -> DocumentMetadata {
+    let Ok(document) = parse_xml(xml) else {
+        return DocumentMetadata::default();
+    };
+    let find = |names: &[&str]| {
+        names.iter().find_map(|name| {
+            document
+                .descendants()
+                .filter(|node| node.has_tag_name(*name))
+                .filter_map(|node| node.text())
+                .map(collapse_whitespace)
+                .find(|text| !text.is_empty())
+        })
+    };
+
+    DocumentMetadata {
+        title: find(titles),
+        author: find(authors),
+        date: find(dates),
+        ..Default::default()
+    }
+}
+
+fn read_docx<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<OfficeSource> {
+    let metadata = core_properties(archive);
+    let heading_styles = read_zip_entry(archive, "word/styles.xml")
+        .map(|styles| docx_heading_styles(&styles))
+        .unwrap_or_default();
+    let document = read_zip_entry(archive, "word/document.xml")?;
+
+    Ok(OfficeSource::from_blocks(
+        metadata,
+        docx_blocks(&document, &heading_styles)?,
+    ))
+}
+
+/// Maps the ids of paragraph styles that are headings to their level, from their outline
+/// level or, failing that, their name
+fn docx_heading_styles(styles: &str) -> HashMap<String, usize> {
+    let Ok(document) = parse_xml(styles) else {
+        return HashMap::new();
+    };
+
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name("style"))
+        .filter(|style| local_attribute(*style, "type") == Some("paragraph"))
+        .filter_map(|style| {
+            let id = local_attribute(style, "styleId")?;
+            let level = docx_outline_level(style).or_else(|| {
+                let name = child(style, "name").and_then(|name| local_attribute(name, "val"))?;
+                heading_style_level(name)
+            })?;
+            Some((id.to_string(), level))
+        })
+        .collect()
+}
+
+/// Heading level of a style from its name, e.g. "heading 2" or "Title"
+fn heading_style_level(name: &str) -> Option<usize> {
+    let name = name.to_ascii_lowercase();
+    if name == "title" {
+        return Some(1);
+    }
+    name.strip_prefix("heading")?
+        .trim()
+        .parse()
+        .ok()
+        .filter(|level| (1..=9).contains(level))
+}
+
+/// Heading level from the outline level of a style or paragraph, where level 9 is body text
+fn docx_outline_level(node: Node) -> Option<usize> {
+    let level: usize = child(child(node, "pPr")?, "outlineLvl")
+        .and_then(|outline| local_attribute(outline, "val"))?
+        .parse()
+        .ok()?;
+    (level < 9).then_some(level + 1)
+}
+
+fn docx_blocks(
+    document: &str,
+    heading_styles: &HashMap<String, usize>,
+) -> anyhow::Result<Vec<Block>> {
+    let document = parse_xml(document)?;
+    let body = document
+        .descendants()
+        .find(|node| node.has_tag_name("body"))
+        .ok_or_else(|| anyhow::anyhow!("Document has no body"))?;
+
+    let mut blocks = Vec::new();
+    docx_body_blocks(body, heading_styles, &mut blocks);
+    Ok(blocks)
+}
+
+fn docx_body_blocks(node: Node, heading_styles: &HashMap<String, usize>, blocks: &mut Vec<Block>) {
+    for child_node in node.children().filter(Node::is_element) {
+        match child_node.tag_name().name() {
+            "p" => {
+                let level = docx_outline_level(child_node).or_else(|| {
+                    let style = child(child(child_node, "pPr")?, "pStyle")?;
+                    heading_styles.get(local_attribute(style, "val")?).copied()
+                });
+                push_block(blocks, level, &docx_text(child_node));
+            }
+            "tbl" => push_block(blocks, None, &table_text(child_node, "tr", "tc", docx_text)),
+            // Content controls and their content wrap paragraphs and tables
+            "sdt" | "sdtContent" | "customXml" => {
+                docx_body_blocks(child_node, heading_styles, blocks)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Text of the runs of a paragraph, or of every paragraph within a table cell
+fn docx_text(node: Node) -> String {
+    let mut text = String::new();
+    for descendant in node.descendants().skip(1) {
+        // Tabs also appear as tab stops in paragraph properties, so only runs are read
+        let in_run = descendant
+            .parent_element()
+            .is_some_and(|parent| parent.has_tag_name("r"));
+        match descendant.tag_name().name() {
+            "t" if in_run => text.push_str(descendant.text().unwrap_or_default()),
+            "tab" if in_run => text.push('\t'),
+            "br" | "cr" if in_run => text.push('\n'),
+            "p" if !text.is_empty() => text.push('\n'),
+            _ => {}
+        }
+    }
+    text
+}
+
+fn read_odt<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<OfficeSource> {
+    let metadata = read_zip_entry(archive, "meta.xml")
+        .map(|meta| {
+            properties(
+                &meta,
+                &["title"],
+                &["initial-creator", "creator"],
+                &["creation-date", "date"],
+            )
+        })
+        .unwrap_or_default();
+    let content = read_zip_entry(archive, "content.xml")?;
+
+    Ok(OfficeSource::from_blocks(metadata, odt_blocks(&content)?))
+}
+
+fn odt_blocks(content: &str) -> anyhow::Result<Vec<Block>> {
+    let document = parse_xml(content)?;
+    let text = document
+        .descendants()
+        .find(|node| node.has_tag_name("body"))
+        .and_then(|body| child(body, "text"))
+        .ok_or_else(|| anyhow::anyhow!("Document has no text body"))?;
+
+    let mut blocks = Vec::new();
+    odt_body_blocks(text, &mut blocks);
+    Ok(blocks)
+}
+
+fn odt_body_blocks(node: Node, blocks: &mut Vec<Block>) {
+    for child_node in node.children().filter(Node::is_element) {
+        match child_node.tag_name().name() {
+            "h" => {
+                let level = local_attribute(child_node, "outline-level")
+                    .and_then(|level| level.parse().ok())
+                    .unwrap_or(1);
+                push_block(blocks, Some(level), &odt_text(child_node));
+            }
+            "p" => push_block(blocks, None, &odt_text(child_node)),
+            "table" => push_block(
+                blocks,
+                None,
+                &table_text(child_node, "table-row", "table-cell", odt_text),
+            ),
+            "list" | "list-item" | "list-header" | "section" | "table-of-content"
+            | "index-body" => odt_body_blocks(child_node, blocks),
+            _ => {}
+        }
+    }
+}
+
+/// Text of a paragraph or heading, with the elements ODF uses for repeated spaces,
+/// tabs and line breaks expanded
+fn odt_text(node: Node) -> String {
+    let mut text = String::new();
+    for child_node in node.children() {
+        if child_node.is_text() {
+            text.push_str(child_node.text().unwrap_or_default());
+            continue;
+        }
+
+        match child_node.tag_name().name() {
+            "s" => {
+                let count = local_attribute(child_node, "c")
+                    .and_then(|count| count.parse().ok())
+                    .unwrap_or(1);
+                text.push_str(&" ".repeat(count));
+            }
+            "tab" => text.push('\t'),
+            "line-break" => text.push('\n'),
+            "note-citation" | "annotation" => {}
+            "p" | "h" => {
+                if !text.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(&odt_text(child_node));
+            }
+            _ => text.push_str(&odt_text(child_node)),
+        }
+    }
+    text
+}
+
+fn read_pptx<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<OfficeSource> {
+    let metadata = core_properties(archive);
+
+    let presentation_path = "ppt/presentation.xml";
+    let presentation = read_zip_entry(archive, presentation_path)?;
+    let slide_relationships = relationships(archive, presentation_path);
+    let slide_paths: Vec<String> = parse_xml(&presentation)?
+        .descendants()
+        .filter(|node| node.has_tag_name("sldId"))
+        .filter_map(|slide| slide.attribute((RELATIONSHIPS_NAMESPACE, "id")))
+        .filter_map(|id| slide_relationships.get(id))
+        .map(|(_, target)| target.clone())
+        .collect();
+
+    let mut pages = Vec::new();
+    let mut headings = Vec::new();
+
+    for slide_path in slide_paths {
+        let (title, mut text) = slide_text(&read_zip_entry(archive, &slide_path)?)?;
+
+        let notes_path = relationships(archive, &slide_path)
+            .into_values()
+            .find(|(kind, _)| kind.ends_with("/notesSlide"))
+            .map(|(_, target)| target);
+        if let Some(notes_path) = notes_path {
+            let (_, notes) = slide_text(&read_zip_entry(archive, &notes_path)?)?;
+            if !notes.is_empty() {
+                text.push_str("\n\nSpeaker notes:\n");
+                text.push_str(&notes);
+            }
+        }
+
+        if let Some(title) = &title {
+            headings.push(Heading {
+                page_no: pages.len(),
+                level: 1,
+                title: title.clone(),
+            });
+        }
+
+        // Slides are kept even when empty so pages match slide numbers
+        pages.push(SourcePage {
+            text,
+            line_no: None,
+            title,
+            language: None,
+            confidence: None,
+            words: Vec::new(),
+            image_size: None,
+        });
+    }
+
+    Ok(OfficeSource {
+        metadata,
+        pages: pages.into_iter(),
+        headings,
+    })
+}
+
+/// Reads the relationships of a part of a package, mapping their ids to their type and
+/// the path of their target. Missing relationships are treated as none.
+fn relationships<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    part_path: &str,
+) -> HashMap<String, (String, String)> {
+    let (directory, file_name) = part_path.rsplit_once('/').unwrap_or(("", part_path));
+    let relationships_path = format!("{}/_rels/{}.rels", directory, file_name);
+    let Ok(xml) = read_zip_entry(archive, relationships_path.trim_start_matches('/')) else {
+        return HashMap::new();
+    };
+    let Ok(document) = parse_xml(&xml) else {
+        return HashMap::new();
+    };
+
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name("Relationship"))
+        .filter(|relationship| relationship.attribute("TargetMode") != Some("External"))
+        .filter_map(|relationship| {
+            Some((
+                relationship.attribute("Id")?.to_string(),
+                (
+                    relationship.attribute("Type")?.to_string(),
+                    resolve_href(part_path, relationship.attribute("Target")?),
+                ),
+            ))
+        })
+        .collect()
+}
+
+/// Text of the shapes and tables of a slide or notes page, one line per paragraph or
+/// table row, along with the title of the slide
+fn slide_text(xml: &str) -> anyhow::Result<(Option<String>, String)> {
+    let document = parse_xml(xml)?;
+    let mut title = None;
+    let mut lines = Vec::new();
+
+    for node in document.descendants() {
+        match node.tag_name().name() {
+            "sp" => {
+                let placeholder = child(node, "nvSpPr")
+                    .and_then(|properties| child(properties, "nvPr"))
+                    .and_then(|properties| child(properties, "ph"))
+                    .map(|placeholder| placeholder.attribute("type").unwrap_or("body"));
+                if placeholder.is_some_and(|kind| SKIPPED_PLACEHOLDERS.contains(&kind)) {
+                    continue;
+                }
+
+                let paragraphs: Vec<String> = node
+                    .descendants()
+                    .filter(|node| node.has_tag_name("p"))
+                    .map(drawing_text)
+                    .filter(|text| !text.trim().is_empty())
+                    .collect();
+
+                if matches!(placeholder, Some("title" | "ctrTitle")) && title.is_none() {
+                    title = Some(collapse_whitespace(&paragraphs.join(" ")));
+                }
+                lines.extend(paragraphs);
+            }
+            "tbl" => lines.push(table_text(node, "tr", "tc", |cell| {
+                cell.descendants()
+                    .filter(|node| node.has_tag_name("p"))
+                    .map(drawing_text)
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            })),
+            _ => {}
+        }
+    }
+
+    Ok((
+        title.filter(|title| !title.is_empty()),
+        lines.join("\n").trim().to_string(),
+    ))
+}
+
+/// Text of a DrawingML paragraph
+fn drawing_text(paragraph: Node) -> String {
+    let mut text = String::new();
+    for node in paragraph.descendants() {
+        match node.tag_name().name() {
+            "t" if node.is_element() => text.push_str(node.text().unwrap_or_default()),
+            "br" => text.push('\n'),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Text of a table, one line per row with cells separated by " | "
+fn table_text(
+    table: Node,
+    row_name: &str,
+    cell_name: &str,
+    cell_text: fn(Node) -> String,
+) -> String {
+    table
+        .descendants()
+        .filter(|node| node.has_tag_name(row_name))
+        // Rows of nested tables are part of the text of their cell
+        .filter(|row| {
+            row.ancestors()
+                .skip(1)
+                .find(|node| node.tag_name() == table.tag_name())
+                == Some(table)
+        })
+        .map(|row| {
+            row.children()
+                .filter(|cell| cell.has_tag_name(cell_name))
+                .map(|cell| collapse_whitespace(&cell_text(cell)))
+                .collect::<Vec<String>>()
+                .join(" | ")
+        })
+        .filter(|row| !row.trim_matches([' ', '|']).is_empty())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn push_block(blocks: &mut Vec<Block>, heading_level: Option<usize>, text: &str) {
+    match heading_level {
+        Some(level) => {
+            let title = collapse_whitespace(text);
+            if !title.is_empty() {
+                blocks.push(Block::Heading(level, title));
+            }
+        }
+        None => {
+            let text = text.trim();
+            if !text.is_empty() {
+                blocks.push(Block::Paragraph(text.to_string()));
+            }
+        }
+    }
+}
+
+/// First child element with the given local name
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::DocumentMetadata;
+    use crate::source_office::{
+        docx_blocks, docx_heading_styles, odt_blocks, properties, read_pptx, slide_text, Block,
+        OfficeSource,
+    };
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+    use zip::{ZipArchive, ZipWriter};
+
+    #[test]
+    fn test_docx_blocks() {
+        let styles = r#"<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+            <w:style w:type="paragraph" w:styleId="Normal"><w:name w:val="Normal"/></w:style>
+            <w:style w:type="paragraph" w:styleId="Berschrift1"><w:name w:val="heading 1"/></w:style>
+            <w:style w:type="paragraph" w:styleId="Custom">
+                <w:name w:val="My Heading"/><w:pPr><w:outlineLvl w:val="1"/></w:pPr>
+            </w:style>
+        </w:styles>"#;
+        let document = r#"<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+            <w:body>
+                <w:p><w:pPr><w:pStyle w:val="Berschrift1"/></w:pPr><w:r><w:t>Intro</w:t></w:r><w:r><w:t xml:space="preserve">duction </w:t></w:r></w:p>
+                <w:p><w:r><w:t>First</w:t><w:tab/><w:t>line</w:t><w:br/><w:t>second line</w:t></w:r></w:p>
+                <w:p><w:r><w:delText>deleted</w:delText></w:r></w:p>
+                <w:p><w:pPr><w:pStyle w:val="Custom"/></w:pPr><w:r><w:t>Details</w:t></w:r></w:p>
+                <w:tbl>
+                    <w:tr><w:tc><w:p><w:r><w:t>Name</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>Value</w:t></w:r></w:p></w:tc></w:tr>
+                    <w:tr><w:tc><w:p><w:r><w:t>a</w:t></w:r></w:p><w:p><w:r><w:t>b</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>1</w:t></w:r></w:p></w:tc></w:tr>
+                </w:tbl>
+                <w:sdt><w:sdtContent><w:p><w:r><w:t>In a control</w:t></w:r></w:p></w:sdtContent></w:sdt>
+            </w:body>
+        </w:document>"#;
+
+        assert_eq!(
+            docx_blocks(document, &docx_heading_styles(styles)).unwrap(),
+            vec![
+                Block::Heading(1, "Introduction".to_string()),
+                Block::Paragraph("First\tline\nsecond line".to_string()),
+                Block::Heading(2, "Details".to_string()),
+                Block::Paragraph("Name | Value\na b | 1".to_string()),
+                Block::Paragraph("In a control".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_odt_blocks() {
+        let content = r#"<office:document-content
+            xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0"
+            xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0"
+            xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0">
+            <office:body><office:text>
+                <text:sequence-decls/>
+                <text:h text:outline-level="2">Setup</text:h>
+                <text:p>Two<text:s text:c="2"/>spaces<text:tab/>and <text:span>spans</text:span></text:p>
+                <text:list><text:list-item><text:p>Item</text:p></text:list-item></text:list>
+                <table:table>
+                    <table:table-row><table:table-cell><text:p>A</text:p></table:table-cell><table:table-cell><text:p>B</text:p></table:table-cell></table:table-row>
+                </table:table>
+            </office:text></office:body>
+        </office:document-content>"#;
+
+        assert_eq!(
+            odt_blocks(content).unwrap(),
+            vec![
+                Block::Heading(2, "Setup".to_string()),
+                Block::Paragraph("Two  spaces\tand spans".to_string()),
+                Block::Paragraph("Item".to_string()),
+                Block::Paragraph("A | B".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slide_text() {
+        let slide = r#"<p:sld xmlns:p="http://schemas.openxmlformats.org/presentationml/2006/main"
+            xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main">
+            <p:cSld><p:spTree>
+                <p:sp><p:nvSpPr><p:nvPr><p:ph type="title"/></p:nvPr></p:nvSpPr>
+                    <p:txBody><a:p><a:r><a:t>Quarterly</a:t></a:r><a:r><a:t> results</a:t></a:r></a:p></p:txBody></p:sp>
+                <p:sp><p:nvSpPr><p:nvPr><p:ph idx="1"/></p:nvPr></p:nvSpPr>
+                    <p:txBody><a:p><a:r><a:t>Revenue up</a:t></a:r></a:p><a:p><a:r><a:t>Costs down</a:t></a:r></a:p></p:txBody></p:sp>
+                <p:sp><p:nvSpPr><p:nvPr><p:ph type="sldNum"/></p:nvPr></p:nvSpPr>
+                    <p:txBody><a:p><a:fld><a:t>3</a:t></a:fld></a:p></p:txBody></p:sp>
+                <p:graphicFrame><a:graphic><a:graphicData><a:tbl>
+                    <a:tr><a:tc><a:txBody><a:p><a:r><a:t>Q1</a:t></a:r></a:p></a:txBody></a:tc><a:tc><a:txBody><a:p><a:r><a:t>10</a:t></a:r></a:p></a:txBody></a:tc></a:tr>
+                </a:tbl></a:graphicData></a:graphic></p:graphicFrame>
+            </p:spTree></p:cSld>
+        </p:sld>"#;
+
+        assert_eq!(
+            slide_text(slide).unwrap(),
+            (
+                Some("Quarterly results".to_string()),
+                "Quarterly results\nRevenue up\nCosts down\nQ1 | 10".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_read_pptx() {
+        let presentation = r#"<p:presentation
+            xmlns:p="http://schemas.openxmlformats.org/presentationml/2006/main"
+            xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+            <p:sldIdLst><p:sldId id="257" r:id="rId3"/><p:sldId id="256" r:id="rId2"/></p:sldIdLst>
+        </p:presentation>"#;
+        let presentation_relationships = r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+            <Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/slide" Target="slides/slide1.xml"/>
+            <Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/slide" Target="/ppt/slides/slide2.xml"/>
+        </Relationships>"#;
+        let slide_relationships = r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+            <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/notesSlide" Target="../notesSlides/notesSlide1.xml"/>
+        </Relationships>"#;
+        let slide = |title: &str, body: &str| {
+            format!(
+                r#"<p:sld xmlns:p="http://schemas.openxmlformats.org/presentationml/2006/main"
+                    xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main"><p:cSld><p:spTree>
+                    <p:sp><p:nvSpPr><p:nvPr><p:ph type="title"/></p:nvPr></p:nvSpPr>
+                        <p:txBody><a:p><a:r><a:t>{}</a:t></a:r></a:p></p:txBody></p:sp>
+                    <p:sp><p:txBody><a:p><a:r><a:t>{}</a:t></a:r></a:p></p:txBody></p:sp>
+                </p:spTree></p:cSld></p:sld>"#,
+                title, body
+            )
+        };
+        let notes = r#"<p:notes xmlns:p="http://schemas.openxmlformats.org/presentationml/2006/main"
+            xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main"><p:cSld><p:spTree>
+            <p:sp><p:nvSpPr><p:nvPr><p:ph type="sldImg"/></p:nvPr></p:nvSpPr></p:sp>
+            <p:sp><p:nvSpPr><p:nvPr><p:ph type="body" idx="1"/></p:nvPr></p:nvSpPr>
+                <p:txBody><a:p><a:r><a:t>Mention the budget</a:t></a:r></a:p></p:txBody></p:sp>
+        </p:spTree></p:cSld></p:notes>"#;
+
+        let files = [
+            ("ppt/presentation.xml", presentation.to_string()),
+            (
+                "ppt/_rels/presentation.xml.rels",
+                presentation_relationships.to_string(),
+            ),
+            ("ppt/slides/slide1.xml", slide("Welcome", "Hello")),
+            ("ppt/slides/slide2.xml", slide("Agenda", "Budget")),
+            (
+                "ppt/slides/_rels/slide1.xml.rels",
+                slide_relationships.to_string(),
+            ),
+            ("ppt/notesSlides/notesSlide1.xml", notes.to_string()),
+        ];
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in &files {
+            writer
+                .start_file(name.to_string(), SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        let pages: Vec<(String, Option<String>)> = read_pptx(&mut archive)
+            .unwrap()
+            .map(|page| page.unwrap())
+            .map(|page| (page.text, page.title))
+            .collect();
+        assert_eq!(
+            pages,
+            vec![
+                ("Agenda\nBudget".to_string(), Some("Agenda".to_string())),
+                (
+                    "Welcome\nHello\n\nSpeaker notes:\nMention the budget".to_string(),
+                    Some("Welcome".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sections_from_blocks() {
+        let source = OfficeSource::from_blocks(
+            DocumentMetadata::default(),
+            vec![
+                Block::Paragraph("Preface".to_string()),
+                Block::Heading(1, "One".to_string()),
+                Block::Paragraph("Text".to_string()),
+                Block::Heading(2, "Two".to_string()),
+            ],
+        );
+
+        assert_eq!(
+            source
+                .headings
+                .iter()
+                .map(|h| (h.page_no, h.level, h.title.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(1, 1, "One"), (2, 2, "Two")]
+        );
+        assert_eq!(
+            source.map(|page| page.unwrap().text).collect::<Vec<_>>(),
+            vec!["Preface", "One\n\nText", "Two"]
+        );
+    }
+
+    #[test]
+    fn test_properties() {
+        let core = r#"<cp:coreProperties
+            xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties"
+            xmlns:dc="http://purl.org/dc/elements/1.1/"
+            xmlns:dcterms="http://purl.org/dc/terms/">
+            <dc:title> Plan </dc:title><dc:creator>Ann</dc:creator>
+            <dcterms:created>2024-01-15T08:30:00Z</dcterms:created>
+        </cp:coreProperties>"#;
+
+        assert_eq!(
+            properties(core, &["title"], &["creator"], &["created"]),
+            DocumentMetadata {
+                title: Some("Plan".to_string()),
+                author: Some("Ann".to_string()),
+                date: Some("2024-01-15T08:30:00Z".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use crate::chunk_sections::Heading;
+use crate::config::Config;
+use crate::database::{DocumentMetadata, PageAnnotation};
+use crate::document_source::{DocumentSource, SourcePage};
+use crate::pdf_metadata::{pdf_annotations, pdf_metadata};
+use crate::pdf_outline::pdf_headings;
+use crate::pdf_to_text::PDFText;
+use pdfium_render::prelude::{PdfDocument, Pdfium};
+use std::path::Path;
+
+/// PDF documents, one page per PDF page read with OCR
+pub struct PdfSource<'a> {
+    pdf_text: PDFText<'a>,
+    metadata: DocumentMetadata,
+    headings: Vec<Heading>,
+    annotations: Vec<PageAnnotation>,
+}
+
+impl PdfSource<'_> {
+    pub fn new<'a>(
+        pdfium: &'a Pdfium,
+        path: &Path,
+        config: &Config,
+    ) -> anyhow::Result<PdfSource<'a>> {
+        Ok(PdfSource::from_pdf_text(PDFText::new(
+            pdfium, &path, config,
+        )?))
+    }
+
+    /// Reads a PDF document that is already loaded, e.g. an attachment read from memory
+    pub fn from_document<'a>(document: PdfDocument<'a>, config: &Config) -> PdfSource<'a> {
+        PdfSource::from_pdf_text(PDFText::from_document(document, config))
+    }
+
+    /// Reads the metadata, outline and annotations up front, as the pages are read lazily
+    fn from_pdf_text<'a>(pdf_text: PDFText<'a>) -> PdfSource<'a> {
+        let document = pdf_text.document();
+        PdfSource {
+            metadata: pdf_metadata(document),
+            headings: pdf_headings(document),
+            annotations: pdf_annotations(document),
+            pdf_text,
+        }
+    }
+}
+
+impl<'a> PdfSource<'a> {
+    pub fn document(&self) -> &PdfDocument<'a> {
+        self.pdf_text.document()
+    }
+}
+
+impl DocumentSource for PdfSource<'_> {
+    fn metadata(&self) -> DocumentMetadata {
+        self.metadata.clone()
+    }
+
+    fn headings(&self) -> Vec<Heading> {
+        self.headings.clone()
+    }
+
+    fn annotations(&self) -> Vec<PageAnnotation> {
+        self.annotations.clone()
+    }
+}
+
+impl Iterator for PdfSource<'_> {
+    type Item = anyhow::Result<SourcePage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pdf_text.next().map(|ocr| Ok(SourcePage::from(ocr?)))
+    }
+}
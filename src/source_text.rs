@@ -0,0 +1,200 @@
+use crate::chunk_sections::Heading;
+use crate::document_source::{DocumentSource, SourcePage};
+use std::fs;
+use std::path::Path;
+use std::vec::IntoIter;
+
+/// Plain text and Markdown files.
+///
+/// Markdown files are split at their headings, each section being a logical page.
+/// Plain text files are a single page.
+pub struct TextSource {
+    pages: IntoIter<SourcePage>,
+    headings: Vec<Heading>,
+}
+
+impl TextSource {
+    pub fn plain(path: &Path) -> anyhow::Result<TextSource> {
+        let text = read_text(path)?;
+        Ok(TextSource {
+            pages: text_pages(vec![(1, text.as_str())]).into_iter(),
+            headings: Vec::new(),
+        })
+    }
+
+    pub fn markdown(path: &Path) -> anyhow::Result<TextSource> {
+        let text = read_text(path)?;
+        let (sections, headings) = markdown_sections(&text);
+        Ok(TextSource {
+            pages: text_pages(sections).into_iter(),
+            headings,
+        })
+    }
+}
+
+impl DocumentSource for TextSource {
+    fn headings(&self) -> Vec<Heading> {
+        self.headings.clone()
+    }
+}
+
+impl Iterator for TextSource {
+    type Item = anyhow::Result<SourcePage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pages.next().map(Ok)
+    }
+}
+
+/// Reads a text file, replacing invalid UTF-8 rather than failing
+pub fn read_text(path: &Path) -> anyhow::Result<String> {
+    Ok(String::from_utf8_lossy(&fs::read(path)?).into_owned())
+}
+
+fn text_pages(sections: Vec<(usize, &str)>) -> Vec<SourcePage> {
+    sections
+        .into_iter()
+        .map(|(line_no, text)| SourcePage {
+            text: text.to_string(),
+            line_no: Some(line_no),
+            title: None,
+            language: None,
+            confidence: None,
+            words: Vec::new(),
+            image_size: None,
+        })
+        .collect()
+}
+
+/// Splits Markdown text into sections starting at each ATX heading (`# Title`),
+/// ignoring headings inside fenced code blocks.
+///
+/// # Returns
+///
+/// The sections as the line they start at and their text, which is kept verbatim so
+/// line numbers can be counted within it, along with the headings of the sections.
+/// Sections that are blank are left out.
+fn markdown_sections(text: &str) -> (Vec<(usize, &str)>, Vec<Heading>) {
+    let mut sections = Vec::new();
+    let mut headings = Vec::new();
+
+    let mut section_start = 0;
+    let mut section_line = 1;
+    let mut fence: Option<&str> = None;
+    let mut offset = 0;
+
+    for (line_index, line) in text.split_inclusive('\n').enumerate() {
+        let trimmed = line.trim_start();
+
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+        } else if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+        } else if let Some((level, title)) = atx_heading(line) {
+            push_section(&mut sections, section_line, &text[section_start..offset]);
+            section_start = offset;
+            section_line = line_index + 1;
+
+            headings.push(Heading {
+                page_no: sections.len(),
+                level,
+                title,
+            });
+        }
+
+        offset += line.len();
+    }
+    push_section(&mut sections, section_line, &text[section_start..]);
+
+    (sections, headings)
+}
+
+fn push_section<'a>(sections: &mut Vec<(usize, &'a str)>, line_no: usize, text: &'a str) {
+    if !text.trim().is_empty() {
+        sections.push((line_no, text));
+    }
+}
+
+/// Parses an ATX heading line into its level and title
+fn atx_heading(line: &str) -> Option<(usize, String)> {
+    let line = line.trim_end();
+    let rest = line.trim_start_matches(' ');
+    if line.len() - rest.len() > 3 {
+        return None;
+    }
+
+    let level = rest.len() - rest.trim_start_matches('#').len();
+    let title = &rest[level..];
+    if !(1..=6).contains(&level) || !(title.is_empty() || title.starts_with([' ', '\t'])) {
+        return None;
+    }
+
+    // Remove an optional closing sequence of #s, which must follow a space
+    let title = match title.trim_end_matches('#') {
+        stripped if stripped.ends_with([' ', '\t']) => stripped,
+        _ => title,
+    }
+    .trim();
+
+    if title.is_empty() {
+        None
+    } else {
+        Some((level, title.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk_sections::Heading;
+    use crate::source_text::{atx_heading, markdown_sections};
+
+    #[test]
+    fn test_atx_heading() {
+        assert_eq!(atx_heading("# Title\n"), Some((1, "Title".to_string())));
+        assert_eq!(atx_heading("  ### Deep ###"), Some((3, "Deep".to_string())));
+        assert_eq!(atx_heading("## C#"), Some((2, "C#".to_string())));
+        assert_eq!(atx_heading("#hashtag"), None);
+        assert_eq!(atx_heading("####### Too deep"), None);
+        assert_eq!(atx_heading("    # Code"), None);
+        assert_eq!(atx_heading("#"), None);
+    }
+
+    #[test]
+    fn test_markdown_sections() {
+        let text = "Intro\n\n# One\nText\n```\n# not a heading\n```\n## Two\nMore\n";
+        let (sections, headings) = markdown_sections(text);
+
+        assert_eq!(
+            sections,
+            vec![
+                (1, "Intro\n\n"),
+                (3, "# One\nText\n```\n# not a heading\n```\n"),
+                (8, "## Two\nMore\n"),
+            ]
+        );
+        assert_eq!(
+            headings,
+            vec![
+                Heading {
+                    page_no: 1,
+                    level: 1,
+                    title: "One".to_string()
+                },
+                Heading {
+                    page_no: 2,
+                    level: 2,
+                    title: "Two".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markdown_starting_with_heading() {
+        let (sections, headings) = markdown_sections("\n# Title\nBody");
+        assert_eq!(sections, vec![(2, "# Title\nBody")]);
+        assert_eq!(headings[0].page_no, 0);
+    }
+}
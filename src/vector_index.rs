@@ -1,18 +1,126 @@
-use crate::generate_embeddings::EMBEDDING_DIMENSIONS;
-use crate::lazy_init::DATA_DIR;
-use std::path::PathBuf;
+use crate::config::Config;
+use crate::generate_embeddings::embedding_dimensions;
+use crate::language::ENGLISH;
+use fastembed::EmbeddingModel;
+use std::path::Path;
 use usearch::{Index, IndexOptions, MetricKind, ScalarKind};
 
 const INDEX_NAME: &str = "index.usearch";
+const MULTILINGUAL_INDEX_NAME: &str = "index_multilingual.usearch";
 
-pub fn load_vector_index(data_dir: &PathBuf) -> anyhow::Result<Index> {
+/// Identifies one of the vector indexes of a collection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexKey {
+    Main,
+    Multilingual,
+}
+
+impl IndexKey {
+    fn file_name(self) -> &'static str {
+        match self {
+            IndexKey::Main => INDEX_NAME,
+            IndexKey::Multilingual => MULTILINGUAL_INDEX_NAME,
+        }
+    }
+}
+
+/// A vector index along with the model its embeddings are generated by
+pub struct EmbeddingIndex {
+    pub key: IndexKey,
+    pub model: EmbeddingModel,
+    pub index: Index,
+}
+
+/// The vector indexes of a collection. Pages detected to be in a language other than
+/// English are embedded in the multilingual index, when a multilingual model is configured.
+pub struct VectorIndexes {
+    pub main: EmbeddingIndex,
+    pub multilingual: Option<EmbeddingIndex>,
+}
+
+impl VectorIndexes {
+    /// Loads the indexes of a collection, starting from empty ones where they don't exist yet
+    pub fn load(data_dir: &Path, config: &Config) -> anyhow::Result<VectorIndexes> {
+        VectorIndexes::open(config, |model, name| {
+            load_vector_index(data_dir, name, embedding_dimensions(model)?)
+        })
+    }
+
+    /// Creates empty indexes, e.g. to rebuild a collection with another embedding model
+    pub fn new(config: &Config) -> anyhow::Result<VectorIndexes> {
+        VectorIndexes::open(config, |model, _| {
+            new_vector_index(embedding_dimensions(model)?)
+        })
+    }
+
+    fn open(
+        config: &Config,
+        open_index: impl Fn(&EmbeddingModel, &str) -> anyhow::Result<Index>,
+    ) -> anyhow::Result<VectorIndexes> {
+        let model = config.embedding_model()?;
+        let main = EmbeddingIndex {
+            key: IndexKey::Main,
+            index: open_index(&model, IndexKey::Main.file_name())?,
+            model,
+        };
+
+        let multilingual = match config.multilingual_model()? {
+            Some(model) => Some(EmbeddingIndex {
+                key: IndexKey::Multilingual,
+                index: open_index(&model, IndexKey::Multilingual.file_name())?,
+                model,
+            }),
+            None => None,
+        };
+
+        Ok(VectorIndexes { main, multilingual })
+    }
+
+    /// Returns the main index, and the multilingual one if there is one
+    pub fn all(&self) -> impl Iterator<Item = &EmbeddingIndex> {
+        std::iter::once(&self.main).chain(self.multilingual.as_ref())
+    }
+
+    /// Returns the index that chunks of a page are embedded in, given the page language
+    pub fn for_language(&self, language: Option<&str>) -> &EmbeddingIndex {
+        match (&self.multilingual, language) {
+            (Some(multilingual), Some(language)) if language != ENGLISH => multilingual,
+            _ => &self.main,
+        }
+    }
+
+    /// Removes the embedding of a chunk from whichever index holds it
+    pub fn remove(&self, key: u64) -> anyhow::Result<()> {
+        for embedding_index in self.all() {
+            embedding_index.index.remove(key)?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, data_dir: &Path) -> anyhow::Result<()> {
+        for embedding_index in self.all() {
+            save_vector_index(
+                &embedding_index.index,
+                data_dir,
+                embedding_index.key.file_name(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub fn new_vector_index(dimensions: usize) -> anyhow::Result<Index> {
     let mut options = IndexOptions::default();
-    options.dimensions = EMBEDDING_DIMENSIONS; // Set the number of dimensions for vectors
+    options.dimensions = dimensions; // Set the number of dimensions for vectors
     options.metric = MetricKind::Cos; // Use cosine similarity for distance measurement
     options.quantization = ScalarKind::F32; // Use 32-bit floating point numbers
 
-    let index = Index::new(&options)?;
-    let vector_index_path = data_dir.join(INDEX_NAME);
+    Ok(Index::new(&options)?)
+}
+
+fn load_vector_index(data_dir: &Path, name: &str, dimensions: usize) -> anyhow::Result<Index> {
+    let index = new_vector_index(dimensions)?;
+    let vector_index_path = data_dir.join(name);
     if vector_index_path.exists() {
         // TODO: Handle non-unicode paths
         index.load(&vector_index_path.to_str().unwrap())?;
@@ -21,8 +129,8 @@ pub fn load_vector_index(data_dir: &PathBuf) -> anyhow::Result<Index> {
     Ok(index)
 }
 
-pub fn save_vector_index(index: &Index) -> anyhow::Result<()> {
-    let vector_index_path = DATA_DIR.join(INDEX_NAME);
+fn save_vector_index(index: &Index, data_dir: &Path, name: &str) -> anyhow::Result<()> {
+    let vector_index_path = data_dir.join(name);
     index.save(&vector_index_path.to_str().unwrap())?;
 
     Ok(())
@@ -0,0 +1,100 @@
+use std::io::{Read, Seek};
+use zip::ZipArchive;
+
+/// Reads a file from a ZIP archive as text, replacing invalid UTF-8
+pub fn read_zip_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> anyhow::Result<String> {
+    let mut data = Vec::new();
+    archive.by_name(name)?.read_to_end(&mut data)?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// Parses an XML document, allowing the DTDs some formats declare
+pub fn parse_xml(text: &str) -> anyhow::Result<roxmltree::Document<'_>> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    Ok(roxmltree::Document::parse_with_options(text, options)?)
+}
+
+/// Gets an attribute by its local name, whatever its namespace
+pub fn local_attribute<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|attribute| attribute.name() == name)
+        .map(|attribute| attribute.value())
+}
+
+/// Resolves a link relative to the file it appears in to a path within the archive,
+/// dropping any fragment
+pub fn resolve_href(base_path: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+
+    // Links starting with a slash are relative to the root of the archive
+    let mut parts: Vec<&str> = match href.starts_with('/') {
+        true => Vec::new(),
+        false => base_path.split('/').collect(),
+    };
+    parts.pop();
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+
+    percent_decode(&parts.join("/"))
+}
+
+/// Decodes %XX escapes in links, e.g. spaces in file names
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zip_xml::resolve_href;
+
+    #[test]
+    fn test_resolve_href() {
+        assert_eq!(resolve_href("content.opf", "ch1.xhtml"), "ch1.xhtml");
+        assert_eq!(
+            resolve_href("OEBPS/content.opf", "./text/my%20chapter.xhtml#top"),
+            "OEBPS/text/my chapter.xhtml"
+        );
+        assert_eq!(
+            resolve_href("OEBPS/nav/nav.xhtml", "../text/ch%C3%A9.xhtml"),
+            "OEBPS/text/ché.xhtml"
+        );
+        assert_eq!(
+            resolve_href("ppt/slides/slide1.xml", "/ppt/media/image1.png"),
+            "ppt/media/image1.png"
+        );
+    }
+}